@@ -28,7 +28,7 @@ impl BgpDumper {
             stream: tcpstream,
         }
     }
-    fn recv_message_head(&mut self) -> Result<(BgpMessageType, usize), BgpError> {
+    fn recv_message_head(&mut self) -> Result<BgpMessageHead, BgpError> {
         let mut buf = [0_u8; 19];
         self.stream.read_exact(&mut buf)?;
         self.params.decode_message_head(&buf)
@@ -52,11 +52,11 @@ impl BgpDumper {
             }
             Ok(msg) => msg,
         };
-        if msg.0 != BgpMessageType::Open {
+        if msg.msgtype != BgpMessageType::Open {
             return Err(BgpError::static_str("Invalid state to start_active"));
         }
-        self.stream.read_exact(&mut buf[0..msg.1])?;
-        bom.decode_from(&self.params, &buf[0..msg.1])?;
+        self.stream.read_exact(&mut buf[0..msg.body_len])?;
+        bom.decode_from(&self.params, &buf[0..msg.body_len])?;
         self.params.hold_time = bom.hold_time;
         self.params.caps = bom.caps;
         self.params.check_caps();
@@ -94,11 +94,11 @@ impl BgpDumper {
                     return Err(e);
                 }
             };
-            if msg.0 == BgpMessageType::Keepalive {
+            if msg.msgtype == BgpMessageType::Keepalive {
                 continue;
             }
-            self.stream.read_exact(&mut buf[0..msg.1])?;
-            match msg.0 {
+            self.stream.read_exact(&mut buf[0..msg.body_len])?;
+            match msg.msgtype {
                 BgpMessageType::Open => {
                     eprintln!("Incorrect open message!");
                     break;
@@ -106,7 +106,7 @@ impl BgpDumper {
                 BgpMessageType::Keepalive => {}
                 BgpMessageType::Notification => {
                     let mut msgnotification = BgpNotificationMessage::new();
-                    match msgnotification.decode_from(&self.params, &buf[0..msg.1]) {
+                    match msgnotification.decode_from(&self.params, &buf[0..msg.body_len]) {
                         Err(e) => {
                             eprintln!("BGP notification decode error: {:?}", e);
                         }
@@ -122,12 +122,15 @@ impl BgpDumper {
                 }
                 BgpMessageType::Update => {
                     let mut msgupdate = BgpUpdateMessage::new();
-                    if let Err(e) = msgupdate.decode_from(&self.params, &buf[0..msg.1]) {
+                    if let Err(e) = msgupdate.decode_from(&self.params, &buf[0..msg.body_len]) {
                         eprintln!("BGP update decode error: {:?}", e);
                         continue;
                     }
                     println!("{:?}", msgupdate);
                 }
+                BgpMessageType::Other(n) => {
+                    println!("BGP message type {} - {} bytes", n, msg.body_len);
+                }
             }
         }
         Ok(())
@@ -151,7 +154,7 @@ fn main() {
             return;
         }
     };
-    let targetasn: u32 = match vargs[2].parse() {
+    let targetasn: Asn = match vargs[2].parse() {
         Ok(x) => x,
         Err(_) => {
             eprintln!("Invalid peer ASn - {}", vargs[2]);
@@ -0,0 +1,227 @@
+// Copyright 2021 Vladimir Melnikov.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! This module contains exact identity for NLRI
+//!
+//! [MplsLabels] and [Labeled] ignore labels in Eq/Hash/Ord, because labels do not
+//! produce unique FEC. [ExactEq] and [ExactKey] include labels, so the same prefix
+//! with different labels is a different key.
+
+use crate::afi::*;
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
+/// Comparison including MPLS labels and path identifiers
+pub trait ExactEq {
+    fn exact_cmp(&self, other: &Self) -> Ordering;
+    fn exact_hash<H: Hasher>(&self, state: &mut H);
+    fn exact_eq(&self, other: &Self) -> bool {
+        self.exact_cmp(other) == Ordering::Equal
+    }
+}
+
+/// Map key wrapper with exact identity
+#[derive(Debug, Clone)]
+pub struct ExactKey<T: ExactEq>(pub T);
+
+/// Labeled NLRI with labels included in identity
+pub type ExactLabeled<T> = ExactKey<Labeled<T>>;
+
+impl<T: ExactEq> ExactKey<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+impl<T: ExactEq> From<T> for ExactKey<T> {
+    fn from(v: T) -> Self {
+        ExactKey(v)
+    }
+}
+impl<T: ExactEq> std::ops::Deref for ExactKey<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+impl<T: ExactEq> PartialEq for ExactKey<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.exact_eq(&other.0)
+    }
+}
+impl<T: ExactEq> Eq for ExactKey<T> {}
+impl<T: ExactEq> PartialOrd for ExactKey<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl<T: ExactEq> Ord for ExactKey<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.exact_cmp(&other.0)
+    }
+}
+impl<T: ExactEq> Hash for ExactKey<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.exact_hash(state)
+    }
+}
+
+impl ExactEq for MplsLabels {
+    fn exact_cmp(&self, other: &Self) -> Ordering {
+        self.labels.cmp(&other.labels)
+    }
+    fn exact_hash<H: Hasher>(&self, state: &mut H) {
+        self.labels.hash(state)
+    }
+}
+impl ExactEq for BgpAddrV4 {
+    fn exact_cmp(&self, other: &Self) -> Ordering {
+        self.cmp(other)
+    }
+    fn exact_hash<H: Hasher>(&self, state: &mut H) {
+        self.hash(state)
+    }
+}
+impl ExactEq for BgpAddrV6 {
+    fn exact_cmp(&self, other: &Self) -> Ordering {
+        self.cmp(other)
+    }
+    fn exact_hash<H: Hasher>(&self, state: &mut H) {
+        self.hash(state)
+    }
+}
+impl<T: BgpItem<T> + ExactEq> ExactEq for Labeled<T> {
+    fn exact_cmp(&self, other: &Self) -> Ordering {
+        self.prefix
+            .exact_cmp(&other.prefix)
+            .then_with(|| self.labels.exact_cmp(&other.labels))
+    }
+    fn exact_hash<H: Hasher>(&self, state: &mut H) {
+        self.prefix.exact_hash(state);
+        self.labels.exact_hash(state);
+    }
+}
+impl<T: BgpItem<T> + ExactEq> ExactEq for WithRd<T> {
+    fn exact_cmp(&self, other: &Self) -> Ordering {
+        self.rd
+            .cmp(&other.rd)
+            .then_with(|| self.prefix.exact_cmp(&other.prefix))
+    }
+    fn exact_hash<H: Hasher>(&self, state: &mut H) {
+        self.rd.hash(state);
+        self.prefix.exact_hash(state);
+    }
+}
+impl<T: Clone + PartialEq + Eq + PartialOrd + ExactEq> ExactEq for WithPathId<T> {
+    fn exact_cmp(&self, other: &Self) -> Ordering {
+        self.nlri
+            .exact_cmp(&other.nlri)
+            .then_with(|| self.pathid.cmp(&other.pathid))
+    }
+    fn exact_hash<H: Hasher>(&self, state: &mut H) {
+        self.pathid.hash(state);
+        self.nlri.exact_hash(state);
+    }
+}
+
+fn exact_cmp_slice<T: ExactEq>(a: &[T], b: &[T]) -> Ordering {
+    for (x, y) in a.iter().zip(b.iter()) {
+        match x.exact_cmp(y) {
+            Ordering::Equal => {}
+            o => return o,
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+fn exact_hash_slice<T: ExactEq, H: Hasher>(v: &[T], state: &mut H) {
+    v.len().hash(state);
+    for x in v.iter() {
+        x.exact_hash(state);
+    }
+}
+
+impl ExactEq for BgpAddrs {
+    fn exact_cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (BgpAddrs::IPV4LU(a), BgpAddrs::IPV4LU(b)) => exact_cmp_slice(a, b),
+            (BgpAddrs::VPNV4U(a), BgpAddrs::VPNV4U(b)) => exact_cmp_slice(a, b),
+            (BgpAddrs::VPNV4M(a), BgpAddrs::VPNV4M(b)) => exact_cmp_slice(a, b),
+            (BgpAddrs::IPV6LU(a), BgpAddrs::IPV6LU(b)) => exact_cmp_slice(a, b),
+            (BgpAddrs::VPNV6U(a), BgpAddrs::VPNV6U(b)) => exact_cmp_slice(a, b),
+            (BgpAddrs::VPNV6M(a), BgpAddrs::VPNV6M(b)) => exact_cmp_slice(a, b),
+            (BgpAddrs::IPV4LUP(a), BgpAddrs::IPV4LUP(b)) => exact_cmp_slice(a, b),
+            (BgpAddrs::VPNV4UP(a), BgpAddrs::VPNV4UP(b)) => exact_cmp_slice(a, b),
+            (BgpAddrs::VPNV4MP(a), BgpAddrs::VPNV4MP(b)) => exact_cmp_slice(a, b),
+            (BgpAddrs::IPV6LUP(a), BgpAddrs::IPV6LUP(b)) => exact_cmp_slice(a, b),
+            (BgpAddrs::VPNV6UP(a), BgpAddrs::VPNV6UP(b)) => exact_cmp_slice(a, b),
+            (BgpAddrs::VPNV6MP(a), BgpAddrs::VPNV6MP(b)) => exact_cmp_slice(a, b),
+            _ => self.cmp(other),
+        }
+    }
+    fn exact_hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            BgpAddrs::IPV4LU(v) => exact_hash_slice(v, state),
+            BgpAddrs::VPNV4U(v) => exact_hash_slice(v, state),
+            BgpAddrs::VPNV4M(v) => exact_hash_slice(v, state),
+            BgpAddrs::IPV6LU(v) => exact_hash_slice(v, state),
+            BgpAddrs::VPNV6U(v) => exact_hash_slice(v, state),
+            BgpAddrs::VPNV6M(v) => exact_hash_slice(v, state),
+            BgpAddrs::IPV4LUP(v) => exact_hash_slice(v, state),
+            BgpAddrs::VPNV4UP(v) => exact_hash_slice(v, state),
+            BgpAddrs::VPNV4MP(v) => exact_hash_slice(v, state),
+            BgpAddrs::IPV6LUP(v) => exact_hash_slice(v, state),
+            BgpAddrs::VPNV6UP(v) => exact_hash_slice(v, state),
+            BgpAddrs::VPNV6MP(v) => exact_hash_slice(v, state),
+            v => v.hash(state),
+        }
+    }
+}
+impl ExactEq for BgpNlriItem {
+    fn exact_cmp(&self, other: &Self) -> Ordering {
+        self.addrs().exact_cmp(other.addrs())
+    }
+    fn exact_hash<H: Hasher>(&self, state: &mut H) {
+        self.addrs().exact_hash(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_exact_key() {
+        let pfx = BgpAddrV4::new(std::net::Ipv4Addr::new(10, 0, 0, 0), 8);
+        let a = Labeled::new(
+            MplsLabels::fromvec(vec![100]),
+            WithRd::new(BgpRD::new(1, 1), pfx.clone()),
+        );
+        let b = Labeled::new(
+            MplsLabels::fromvec(vec![200]),
+            WithRd::new(BgpRD::new(1, 1), pfx),
+        );
+        assert_eq!(a, b);
+        assert!(!a.exact_eq(&b));
+        let set: HashSet<ExactLabeled<WithRd<BgpAddrV4>>> =
+            vec![a.clone().into(), b.clone().into(), a.clone().into()]
+                .into_iter()
+                .collect();
+        assert_eq!(set.len(), 2);
+        let pa = BgpAddrs::VPNV4UP(vec![WithPathId::new(1, a.clone())]);
+        let pb = BgpAddrs::VPNV4UP(vec![WithPathId::new(1, b)]);
+        assert_eq!(pa, pb);
+        assert_ne!(ExactKey(pa.clone()), ExactKey(pb));
+        assert_eq!(ExactKey(pa.clone()), ExactKey(pa));
+        assert_ne!(
+            ExactKey(BgpAddrs::VPNV4U(vec![a.clone()])),
+            ExactKey(BgpAddrs::VPNV4M(vec![a]))
+        );
+    }
+}
@@ -28,10 +28,16 @@ pub mod vpls;
 pub use vpls::*;
 pub mod evpn;
 pub use evpn::*;
+pub mod exact;
+pub use exact::*;
 pub mod flowspec;
 pub use flowspec::*;
 pub mod mdt;
 pub use mdt::*;
+pub mod nlri;
+pub use nlri::*;
+pub mod pathid;
+pub use pathid::*;
 
 /// NLRI with bits length
 pub trait BgpItem<T: std::marker::Sized> {
@@ -98,6 +104,16 @@ impl BgpNet {
     }
 }
 
+impl From<BgpAddrV4> for BgpNet {
+    fn from(v: BgpAddrV4) -> Self {
+        BgpNet::V4(v)
+    }
+}
+impl From<BgpAddrV6> for BgpNet {
+    fn from(v: BgpAddrV6) -> Self {
+        BgpNet::V6(v)
+    }
+}
 impl std::str::FromStr for BgpNet {
     type Err = BgpError;
 
@@ -155,6 +171,100 @@ impl<'de> serde::Deserialize<'de> for BgpNet {
     }
 }
 
+/// Access to components of nested NLRI wrappers
+pub trait BgpNlriParts {
+    /// bare prefix type
+    type Prefix;
+    /// bare prefix
+    fn base_prefix(&self) -> &Self::Prefix;
+    /// bare prefix, all wrappers dropped
+    fn into_base_prefix(self) -> Self::Prefix;
+    /// route distinguisher, if any
+    fn rd(&self) -> Option<&BgpRD> {
+        None
+    }
+    /// label stack, if any
+    fn labels(&self) -> Option<&MplsLabels> {
+        None
+    }
+    /// path identifier, if any
+    fn pathid(&self) -> Option<BgpPathId> {
+        None
+    }
+}
+impl BgpNlriParts for BgpAddrV4 {
+    type Prefix = BgpAddrV4;
+    fn base_prefix(&self) -> &BgpAddrV4 {
+        self
+    }
+    fn into_base_prefix(self) -> BgpAddrV4 {
+        self
+    }
+}
+impl BgpNlriParts for BgpAddrV6 {
+    type Prefix = BgpAddrV6;
+    fn base_prefix(&self) -> &BgpAddrV6 {
+        self
+    }
+    fn into_base_prefix(self) -> BgpAddrV6 {
+        self
+    }
+}
+impl<T: BgpItem<T> + BgpNlriParts> BgpNlriParts for Labeled<T> {
+    type Prefix = T::Prefix;
+    fn base_prefix(&self) -> &T::Prefix {
+        self.prefix.base_prefix()
+    }
+    fn into_base_prefix(self) -> T::Prefix {
+        self.prefix.into_base_prefix()
+    }
+    fn rd(&self) -> Option<&BgpRD> {
+        self.prefix.rd()
+    }
+    fn labels(&self) -> Option<&MplsLabels> {
+        Some(&self.labels)
+    }
+    fn pathid(&self) -> Option<BgpPathId> {
+        self.prefix.pathid()
+    }
+}
+impl<T: BgpItem<T> + BgpNlriParts> BgpNlriParts for WithRd<T> {
+    type Prefix = T::Prefix;
+    fn base_prefix(&self) -> &T::Prefix {
+        self.prefix.base_prefix()
+    }
+    fn into_base_prefix(self) -> T::Prefix {
+        self.prefix.into_base_prefix()
+    }
+    fn rd(&self) -> Option<&BgpRD> {
+        Some(&self.rd)
+    }
+    fn labels(&self) -> Option<&MplsLabels> {
+        self.prefix.labels()
+    }
+    fn pathid(&self) -> Option<BgpPathId> {
+        self.prefix.pathid()
+    }
+}
+impl<T: Clone + PartialEq + Eq + PartialOrd + BgpNlriParts> BgpNlriParts for WithPathId<T> {
+    type Prefix = T::Prefix;
+    fn base_prefix(&self) -> &T::Prefix {
+        self.nlri.base_prefix()
+    }
+    fn into_base_prefix(self) -> T::Prefix {
+        self.nlri.into_base_prefix()
+    }
+    fn rd(&self) -> Option<&BgpRD> {
+        self.nlri.rd()
+    }
+    fn labels(&self) -> Option<&MplsLabels> {
+        self.nlri.labels()
+    }
+    fn pathid(&self) -> Option<BgpPathId> {
+        Some(self.pathid)
+    }
+}
+
 /// Represents variance of NLRI collections
 #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 #[cfg(feature = "serialization")]
@@ -193,6 +303,35 @@ pub enum BgpAddrs {
     VPNV6MP(Vec<WithPathId<Labeled<WithRd<BgpAddrV6>>>>),
 }
 
+impl From<Vec<BgpAddrV4>> for BgpAddrs {
+    fn from(v: Vec<BgpAddrV4>) -> BgpAddrs {
+        BgpAddrs::IPV4U(v)
+    }
+}
+impl From<Vec<BgpAddrV6>> for BgpAddrs {
+    fn from(v: Vec<BgpAddrV6>) -> BgpAddrs {
+        BgpAddrs::IPV6U(v)
+    }
+}
+impl std::convert::TryFrom<Vec<BgpNet>> for BgpAddrs {
+    type Error = BgpError;
+
+    /// Builds unicast NLRI collection, all prefixes should be of the same family.
+    fn try_from(v: Vec<BgpNet>) -> Result<BgpAddrs, BgpError> {
+        let mut ret = BgpAddrs::None;
+        for net in v.into_iter() {
+            match net {
+                BgpNet::V4(n) => ret.try_append(BgpAddrs::IPV4U(vec![n]))?,
+                BgpNet::V6(n) => ret.try_append(BgpAddrs::IPV6U(vec![n]))?,
+                BgpNet::MAC(_) => {
+                    return Err(BgpError::static_str("MAC prefix is not unicast NLRI"))
+                }
+            }
+        }
+        Ok(ret)
+    }
+}
+
 pub fn decode_bgpitem_from<T: BgpItem<T>>(buf: &[u8]) -> Result<(T, usize), BgpError> {
     let bits = buf[0];
     let r = T::extract_bits_from(bits, &buf[1..])?;
@@ -519,6 +658,28 @@ impl<T: BgpItem<T>> Labeled<T> {
             prefix: inner,
         }
     }
+    /// transforms underlying NLRI keeping labels
+    pub fn map_prefix<U: BgpItem<U>, F: FnOnce(T) -> U>(self, f: F) -> Labeled<U> {
+        Labeled {
+            labels: self.labels,
+            prefix: f(self.prefix),
+        }
+    }
+    /// drops labels
+    pub fn into_inner(self) -> T {
+        self.prefix
+    }
+    /// splits into labels and underlying NLRI
+    pub fn into_parts(self) -> (MplsLabels, T) {
+        (self.labels, self.prefix)
+    }
+    /// adds path identifier
+    pub fn with_pathid(self, pathid: BgpPathId) -> WithPathId<Labeled<T>>
+    where
+        T: Clone + PartialEq + Eq + PartialOrd,
+    {
+        WithPathId::new(pathid, self)
+    }
 }
 impl<T: BgpItem<T> + PartialEq> PartialEq for Labeled<T> {
     fn eq(&self, other: &Self) -> bool {
@@ -581,6 +742,25 @@ impl<T: BgpItem<T>> WithRd<T> {
     pub fn new(rd: BgpRD, prefix: T) -> WithRd<T> {
         WithRd { rd, prefix }
     }
+    /// transforms underlying NLRI keeping RD
+    pub fn map_prefix<U: BgpItem<U>, F: FnOnce(T) -> U>(self, f: F) -> WithRd<U> {
+        WithRd {
+            rd: self.rd,
+            prefix: f(self.prefix),
+        }
+    }
+    /// drops RD
+    pub fn into_inner(self) -> T {
+        self.prefix
+    }
+    /// splits into RD and underlying NLRI
+    pub fn into_parts(self) -> (BgpRD, T) {
+        (self.rd, self.prefix)
+    }
+    /// adds label stack
+    pub fn labeled(self, labels: MplsLabels) -> Labeled<WithRd<T>> {
+        Labeled::new(labels, self)
+    }
 }
 impl<T: BgpItem<T> + PartialOrd> PartialOrd for WithRd<T> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
@@ -667,6 +847,20 @@ impl<T: Clone + PartialEq + Eq + PartialOrd> WithPathId<T> {
     pub fn new(pathid: BgpPathId, nlri: T) -> WithPathId<T> {
         WithPathId { pathid, nlri }
     }
+    /// transforms NLRI keeping path identifier
+    pub fn map_nlri<U: Clone + PartialEq + Eq + PartialOrd, F: FnOnce(T) -> U>(
+        self,
+        f: F,
+    ) -> WithPathId<U> {
+        WithPathId {
+            pathid: self.pathid,
+            nlri: f(self.nlri),
+        }
+    }
+    /// drops path identifier
+    pub fn into_inner(self) -> T {
+        self.nlri
+    }
 }
 impl<T: Clone + PartialEq + Eq + PartialOrd> PartialOrd for WithPathId<T> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
@@ -708,6 +902,12 @@ impl<T: Clone + PartialEq + Eq + PartialOrd + std::fmt::Display> std::fmt::Displ
         }
     }
 }
+fn pathid_items<T>(v: Vec<T>, pathid: BgpPathId) -> Vec<WithPathId<T>>
+where
+    T: Clone + PartialEq + Eq + PartialOrd,
+{
+    v.into_iter().map(|x| WithPathId::new(pathid, x)).collect()
+}
 impl Default for BgpAddrs {
     fn default() -> Self {
         Self::new()
@@ -753,6 +953,131 @@ impl BgpAddrs {
             BgpAddrs::IPV6MDTP(v) => v.is_empty(),
         }
     }
+    /// keeps only NLRI which prefix satisfies predicate, RD, labels and path identifiers are ignored.
+    /// MDT NLRI are matched by their source prefix.
+    /// NLRI kinds without IP prefix (EVPN, flowspec, MVPN, VPLS) never satisfy predicate and are dropped.
+    pub fn retain<F: FnMut(&BgpNet) -> bool>(&mut self, mut f: F) {
+        match self {
+            BgpAddrs::IPV4U(v) => v.retain(|x| f(&x.base_prefix().clone().into())),
+            BgpAddrs::IPV4M(v) => v.retain(|x| f(&x.base_prefix().clone().into())),
+            BgpAddrs::IPV4LU(v) => v.retain(|x| f(&x.base_prefix().clone().into())),
+            BgpAddrs::VPNV4U(v) => v.retain(|x| f(&x.base_prefix().clone().into())),
+            BgpAddrs::VPNV4M(v) => v.retain(|x| f(&x.base_prefix().clone().into())),
+            BgpAddrs::IPV6U(v) => v.retain(|x| f(&x.base_prefix().clone().into())),
+            BgpAddrs::IPV6M(v) => v.retain(|x| f(&x.base_prefix().clone().into())),
+            BgpAddrs::IPV6LU(v) => v.retain(|x| f(&x.base_prefix().clone().into())),
+            BgpAddrs::VPNV6U(v) => v.retain(|x| f(&x.base_prefix().clone().into())),
+            BgpAddrs::VPNV6M(v) => v.retain(|x| f(&x.base_prefix().clone().into())),
+            BgpAddrs::IPV4UP(v) => v.retain(|x| f(&x.base_prefix().clone().into())),
+            BgpAddrs::IPV4MP(v) => v.retain(|x| f(&x.base_prefix().clone().into())),
+            BgpAddrs::IPV4LUP(v) => v.retain(|x| f(&x.base_prefix().clone().into())),
+            BgpAddrs::VPNV4UP(v) => v.retain(|x| f(&x.base_prefix().clone().into())),
+            BgpAddrs::VPNV4MP(v) => v.retain(|x| f(&x.base_prefix().clone().into())),
+            BgpAddrs::IPV6UP(v) => v.retain(|x| f(&x.base_prefix().clone().into())),
+            BgpAddrs::IPV6MP(v) => v.retain(|x| f(&x.base_prefix().clone().into())),
+            BgpAddrs::IPV6LUP(v) => v.retain(|x| f(&x.base_prefix().clone().into())),
+            BgpAddrs::VPNV6UP(v) => v.retain(|x| f(&x.base_prefix().clone().into())),
+            BgpAddrs::VPNV6MP(v) => v.retain(|x| f(&x.base_prefix().clone().into())),
+            BgpAddrs::IPV4MDT(v) => v.retain(|x| f(&x.prefix.addr.clone().into())),
+            BgpAddrs::IPV4MDTP(v) => v.retain(|x| f(&x.nlri.prefix.addr.clone().into())),
+            BgpAddrs::IPV6MDT(v) => v.retain(|x| f(&x.prefix.addr.clone().into())),
+            BgpAddrs::IPV6MDTP(v) => v.retain(|x| f(&x.nlri.prefix.addr.clone().into())),
+            BgpAddrs::L2VPLS(v) => v.clear(),
+            BgpAddrs::MVPN(v) => v.clear(),
+            BgpAddrs::EVPN(v) => v.clear(),
+            BgpAddrs::FS4U(v) => v.clear(),
+            BgpAddrs::FS6U(v) => v.clear(),
+            BgpAddrs::FSV4U(v) => v.clear(),
+            BgpAddrs::None => {}
+        }
+    }
+    /// returns NLRI which prefixes are within any of given networks
+    pub fn filtered(&self, prefix_set: &[BgpNet]) -> BgpAddrs {
+        let mut ret = self.clone();
+        ret.retain(|net| prefix_set.iter().any(|p| p.contains(net)));
+        ret
+    }
+    /// appends NLRI of the same kind, fails on afi/safi or path identifier mismatch
+    pub fn try_append(&mut self, other: BgpAddrs) -> Result<(), BgpError> {
+        match (self, other) {
+            (_, BgpAddrs::None) => {}
+            (s @ BgpAddrs::None, o) => *s = o,
+            (BgpAddrs::IPV4U(v), BgpAddrs::IPV4U(o)) => v.extend(o),
+            (BgpAddrs::IPV4M(v), BgpAddrs::IPV4M(o)) => v.extend(o),
+            (BgpAddrs::IPV4LU(v), BgpAddrs::IPV4LU(o)) => v.extend(o),
+            (BgpAddrs::VPNV4U(v), BgpAddrs::VPNV4U(o)) => v.extend(o),
+            (BgpAddrs::VPNV4M(v), BgpAddrs::VPNV4M(o)) => v.extend(o),
+            (BgpAddrs::IPV6U(v), BgpAddrs::IPV6U(o)) => v.extend(o),
+            (BgpAddrs::IPV6M(v), BgpAddrs::IPV6M(o)) => v.extend(o),
+            (BgpAddrs::IPV6LU(v), BgpAddrs::IPV6LU(o)) => v.extend(o),
+            (BgpAddrs::VPNV6U(v), BgpAddrs::VPNV6U(o)) => v.extend(o),
+            (BgpAddrs::VPNV6M(v), BgpAddrs::VPNV6M(o)) => v.extend(o),
+            (BgpAddrs::L2VPLS(v), BgpAddrs::L2VPLS(o)) => v.extend(o),
+            (BgpAddrs::MVPN(v), BgpAddrs::MVPN(o)) => v.extend(o),
+            (BgpAddrs::EVPN(v), BgpAddrs::EVPN(o)) => v.extend(o),
+            (BgpAddrs::FS4U(v), BgpAddrs::FS4U(o)) => v.extend(o),
+            (BgpAddrs::FS6U(v), BgpAddrs::FS6U(o)) => v.extend(o),
+            (BgpAddrs::FSV4U(v), BgpAddrs::FSV4U(o)) => v.extend(o),
+            (BgpAddrs::IPV4UP(v), BgpAddrs::IPV4UP(o)) => v.extend(o),
+            (BgpAddrs::IPV4MP(v), BgpAddrs::IPV4MP(o)) => v.extend(o),
+            (BgpAddrs::IPV4LUP(v), BgpAddrs::IPV4LUP(o)) => v.extend(o),
+            (BgpAddrs::VPNV4UP(v), BgpAddrs::VPNV4UP(o)) => v.extend(o),
+            (BgpAddrs::VPNV4MP(v), BgpAddrs::VPNV4MP(o)) => v.extend(o),
+            (BgpAddrs::IPV6UP(v), BgpAddrs::IPV6UP(o)) => v.extend(o),
+            (BgpAddrs::IPV6MP(v), BgpAddrs::IPV6MP(o)) => v.extend(o),
+            (BgpAddrs::IPV6LUP(v), BgpAddrs::IPV6LUP(o)) => v.extend(o),
+            (BgpAddrs::VPNV6UP(v), BgpAddrs::VPNV6UP(o)) => v.extend(o),
+            (BgpAddrs::VPNV6MP(v), BgpAddrs::VPNV6MP(o)) => v.extend(o),
+            (BgpAddrs::IPV4MDT(v), BgpAddrs::IPV4MDT(o)) => v.extend(o),
+            (BgpAddrs::IPV4MDTP(v), BgpAddrs::IPV4MDTP(o)) => v.extend(o),
+            (BgpAddrs::IPV6MDT(v), BgpAddrs::IPV6MDT(o)) => v.extend(o),
+            (BgpAddrs::IPV6MDTP(v), BgpAddrs::IPV6MDTP(o)) => v.extend(o),
+            (s, o) => {
+                return Err(BgpError::from_string(format!(
+                    "Unable to append {:?} NLRI to {:?}",
+                    o.get_afi_safi(),
+                    s.get_afi_safi()
+                )))
+            }
+        }
+        Ok(())
+    }
+    /// sorts NLRI, so identical collections encode identically
+    pub fn sort(&mut self) {
+        match self {
+            BgpAddrs::None => {}
+            BgpAddrs::IPV4U(v) => v.sort(),
+            BgpAddrs::IPV4M(v) => v.sort(),
+            BgpAddrs::IPV4LU(v) => v.sort(),
+            BgpAddrs::VPNV4U(v) => v.sort(),
+            BgpAddrs::VPNV4M(v) => v.sort(),
+            BgpAddrs::IPV6U(v) => v.sort(),
+            BgpAddrs::IPV6M(v) => v.sort(),
+            BgpAddrs::IPV6LU(v) => v.sort(),
+            BgpAddrs::VPNV6U(v) => v.sort(),
+            BgpAddrs::VPNV6M(v) => v.sort(),
+            BgpAddrs::L2VPLS(v) => v.sort(),
+            BgpAddrs::MVPN(v) => v.sort(),
+            BgpAddrs::EVPN(v) => v.sort(),
+            BgpAddrs::FS4U(v) => v.sort(),
+            BgpAddrs::FS6U(v) => v.sort(),
+            BgpAddrs::FSV4U(v) => v.sort(),
+            BgpAddrs::IPV4UP(v) => v.sort(),
+            BgpAddrs::IPV4MP(v) => v.sort(),
+            BgpAddrs::IPV4LUP(v) => v.sort(),
+            BgpAddrs::VPNV4UP(v) => v.sort(),
+            BgpAddrs::VPNV4MP(v) => v.sort(),
+            BgpAddrs::IPV6UP(v) => v.sort(),
+            BgpAddrs::IPV6MP(v) => v.sort(),
+            BgpAddrs::IPV6LUP(v) => v.sort(),
+            BgpAddrs::VPNV6UP(v) => v.sort(),
+            BgpAddrs::VPNV6MP(v) => v.sort(),
+            BgpAddrs::IPV4MDT(v) => v.sort(),
+            BgpAddrs::IPV4MDTP(v) => v.sort(),
+            BgpAddrs::IPV6MDT(v) => v.sort(),
+            BgpAddrs::IPV6MDTP(v) => v.sort(),
+        }
+    }
     /// returns collection length
     pub fn len(&self) -> usize {
         match self {
@@ -789,6 +1114,60 @@ impl BgpAddrs {
             BgpAddrs::IPV6MDTP(v) => v.len(),
         }
     }
+    /// returns true if NLRI carries path identifiers
+    pub fn has_pathid(&self) -> bool {
+        matches!(
+            self,
+            BgpAddrs::IPV4UP(_)
+                | BgpAddrs::IPV4MP(_)
+                | BgpAddrs::IPV4LUP(_)
+                | BgpAddrs::VPNV4UP(_)
+                | BgpAddrs::VPNV4MP(_)
+                | BgpAddrs::IPV6UP(_)
+                | BgpAddrs::IPV6MP(_)
+                | BgpAddrs::IPV6LUP(_)
+                | BgpAddrs::VPNV6UP(_)
+                | BgpAddrs::VPNV6MP(_)
+                | BgpAddrs::IPV4MDTP(_)
+                | BgpAddrs::IPV6MDTP(_)
+        )
+    }
+    /// drops path identifiers, if any
+    pub fn without_pathid(self) -> BgpAddrs {
+        match self {
+            BgpAddrs::IPV4UP(v) => BgpAddrs::IPV4U(v.into_iter().map(|x| x.nlri).collect()),
+            BgpAddrs::IPV4MP(v) => BgpAddrs::IPV4M(v.into_iter().map(|x| x.nlri).collect()),
+            BgpAddrs::IPV4LUP(v) => BgpAddrs::IPV4LU(v.into_iter().map(|x| x.nlri).collect()),
+            BgpAddrs::VPNV4UP(v) => BgpAddrs::VPNV4U(v.into_iter().map(|x| x.nlri).collect()),
+            BgpAddrs::VPNV4MP(v) => BgpAddrs::VPNV4M(v.into_iter().map(|x| x.nlri).collect()),
+            BgpAddrs::IPV6UP(v) => BgpAddrs::IPV6U(v.into_iter().map(|x| x.nlri).collect()),
+            BgpAddrs::IPV6MP(v) => BgpAddrs::IPV6M(v.into_iter().map(|x| x.nlri).collect()),
+            BgpAddrs::IPV6LUP(v) => BgpAddrs::IPV6LU(v.into_iter().map(|x| x.nlri).collect()),
+            BgpAddrs::VPNV6UP(v) => BgpAddrs::VPNV6U(v.into_iter().map(|x| x.nlri).collect()),
+            BgpAddrs::VPNV6MP(v) => BgpAddrs::VPNV6M(v.into_iter().map(|x| x.nlri).collect()),
+            BgpAddrs::IPV4MDTP(v) => BgpAddrs::IPV4MDT(v.into_iter().map(|x| x.nlri).collect()),
+            BgpAddrs::IPV6MDTP(v) => BgpAddrs::IPV6MDT(v.into_iter().map(|x| x.nlri).collect()),
+            n => n,
+        }
+    }
+    /// adds path identifier, if NLRI kind supports it and has no path identifiers yet
+    pub fn with_pathid(self, pathid: BgpPathId) -> BgpAddrs {
+        match self {
+            BgpAddrs::IPV4U(v) => BgpAddrs::IPV4UP(pathid_items(v, pathid)),
+            BgpAddrs::IPV4M(v) => BgpAddrs::IPV4MP(pathid_items(v, pathid)),
+            BgpAddrs::IPV4LU(v) => BgpAddrs::IPV4LUP(pathid_items(v, pathid)),
+            BgpAddrs::VPNV4U(v) => BgpAddrs::VPNV4UP(pathid_items(v, pathid)),
+            BgpAddrs::VPNV4M(v) => BgpAddrs::VPNV4MP(pathid_items(v, pathid)),
+            BgpAddrs::IPV6U(v) => BgpAddrs::IPV6UP(pathid_items(v, pathid)),
+            BgpAddrs::IPV6M(v) => BgpAddrs::IPV6MP(pathid_items(v, pathid)),
+            BgpAddrs::IPV6LU(v) => BgpAddrs::IPV6LUP(pathid_items(v, pathid)),
+            BgpAddrs::VPNV6U(v) => BgpAddrs::VPNV6UP(pathid_items(v, pathid)),
+            BgpAddrs::VPNV6M(v) => BgpAddrs::VPNV6MP(pathid_items(v, pathid)),
+            BgpAddrs::IPV4MDT(v) => BgpAddrs::IPV4MDTP(pathid_items(v, pathid)),
+            BgpAddrs::IPV6MDT(v) => BgpAddrs::IPV6MDTP(pathid_items(v, pathid)),
+            n => n,
+        }
+    }
     /// returns BGP afi+safi codes
     pub fn get_afi_safi(&self) -> (u16, u8) {
         match &self {
@@ -830,6 +1209,16 @@ impl BgpAddrs {
         afi: u16,
         safi: u8,
         buf: &[u8],
+    ) -> Result<(BgpAddrs, usize), BgpError> {
+        let r = BgpAddrs::decode_addrs(peer, afi, safi, buf);
+        peer.record_decode(BgpDecodeCategory::AfiSafi(afi, safi), &r);
+        r
+    }
+    fn decode_addrs(
+        peer: &BgpSessionParams,
+        afi: u16,
+        safi: u8,
+        buf: &[u8],
     ) -> Result<(BgpAddrs, usize), BgpError> {
         match afi {
             1 => {
@@ -1508,4 +1897,89 @@ mod tests {
                 ))
         );
     }
+
+    #[test]
+    fn test_addrs_try_append() {
+        let nets: Vec<BgpNet> = vec![
+            "10.0.0.0/8".parse().unwrap(),
+            "10.1.0.0/16".parse().unwrap(),
+        ];
+        let mut addrs = BgpAddrs::try_from(nets).unwrap();
+        addrs
+            .try_append(BgpAddrs::from(vec![BgpAddrV4::new(
+                std::net::Ipv4Addr::new(10, 2, 0, 0),
+                16,
+            )]))
+            .unwrap();
+        assert_eq!(addrs.len(), 3);
+        assert!(addrs.try_append(BgpAddrs::IPV6U(vec![])).is_err());
+        assert!(BgpAddrs::try_from(vec![
+            "10.0.0.0/8".parse::<BgpNet>().unwrap(),
+            "2001:db8::/32".parse::<BgpNet>().unwrap()
+        ])
+        .is_err());
+        let mut none = BgpAddrs::None;
+        none.try_append(addrs.clone()).unwrap();
+        assert_eq!(none, addrs);
+    }
+    #[test]
+    fn test_nlri_parts() {
+        let pfx = BgpAddrV4::new(std::net::Ipv4Addr::new(10, 0, 0, 0), 8);
+        let route = WithRd::new(BgpRD::new(65000, 1), pfx.clone())
+            .labeled(MplsLabels::fromvec(vec![100]))
+            .with_pathid(7);
+        assert_eq!(route.rd(), Some(&BgpRD::new(65000, 1)));
+        assert_eq!(route.labels().map(|l| l.labels.clone()), Some(vec![100]));
+        assert_eq!(BgpNlriParts::pathid(&route), Some(7));
+        assert_eq!(route.base_prefix(), &pfx);
+        let relabeled = route
+            .clone()
+            .map_nlri(|l| l.map_prefix(|r| r.map_prefix(|p| BgpAddrV4::new(p.addr, 16))));
+        assert_eq!(relabeled.base_prefix().prefixlen, 16);
+        assert_eq!(relabeled.rd(), Some(&BgpRD::new(65000, 1)));
+        let (rd, bare) = route.into_inner().into_inner().into_parts();
+        assert_eq!(rd, BgpRD::new(65000, 1));
+        assert_eq!(bare, pfx);
+    }
+    #[test]
+    fn test_addrs_retain() {
+        let mk = |rd: u32, s: &str| {
+            WithPathId::new(
+                rd,
+                WithRd::new(BgpRD::new(1, rd), s.parse::<BgpAddrV4>().unwrap())
+                    .labeled(MplsLabels::fromvec(vec![100])),
+            )
+        };
+        let mut addrs = BgpAddrs::VPNV4UP(vec![
+            mk(1, "10.0.0.0/24"),
+            mk(2, "10.1.0.0/16"),
+            mk(3, "192.168.0.0/24"),
+        ]);
+        let set: Vec<BgpNet> = vec!["10.0.0.0/8".parse().unwrap()];
+        assert_eq!(addrs.filtered(&set).len(), 2);
+        addrs.retain(|net| match net {
+            BgpNet::V4(a) => a.prefixlen == 24,
+            _ => false,
+        });
+        assert_eq!(
+            addrs,
+            BgpAddrs::VPNV4UP(vec![mk(1, "10.0.0.0/24"), mk(3, "192.168.0.0/24")])
+        );
+        let mut v6 = BgpAddrs::IPV6U(vec!["2001:db8::/32".parse().unwrap()]);
+        v6.retain(|net| set.iter().any(|p| p.contains(net)));
+        assert!(v6.is_empty());
+        let mdt = |s: &str| {
+            WithRd::new(
+                BgpRD::new(1, 1),
+                BgpMdtV4::new(s.parse().unwrap(), std::net::Ipv4Addr::new(239, 1, 1, 1)),
+            )
+        };
+        let mdts = BgpAddrs::IPV4MDT(vec![mdt("10.0.0.1/32"), mdt("172.16.0.1/32")]);
+        assert_eq!(
+            mdts.filtered(&set),
+            BgpAddrs::IPV4MDT(vec![mdt("10.0.0.1/32")])
+        );
+        let fs = BgpAddrs::FS4U(vec![BgpFlowSpec::PrefixDst("10.0.0.0/24".parse().unwrap())]);
+        assert!(fs.filtered(&set).is_empty());
+    }
 }
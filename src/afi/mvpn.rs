@@ -62,7 +62,7 @@ impl BgpAddrItem<BgpMVPN1> for BgpMVPN1 {
 #[derive(Serialize, Deserialize)]
 pub struct BgpMVPN2 {
     pub rd: BgpRD,
-    pub asn: u32,
+    pub asn: Asn,
 }
 impl std::fmt::Display for BgpMVPN2 {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -76,7 +76,7 @@ impl BgpAddrItem<BgpMVPN2> for BgpMVPN2 {
             return Ok((
                 BgpMVPN2 {
                     rd: rdp.0,
-                    asn: getn_u32(&buf[8..12]),
+                    asn: Asn(getn_u32(&buf[8..12])),
                 },
                 rdp.1 + 4,
             ));
@@ -85,7 +85,7 @@ impl BgpAddrItem<BgpMVPN2> for BgpMVPN2 {
     }
     fn encode_to(&self, mode: BgpTransportMode, buf: &mut [u8]) -> Result<usize, BgpError> {
         let p1 = self.rd.encode_to(mode, buf)?;
-        setn_u32(self.asn, &mut buf[p1..]);
+        setn_u32(self.asn.value(), &mut buf[p1..]);
         Ok(p1 + 4)
     }
 }
@@ -256,7 +256,7 @@ impl BgpAddrItem<BgpMVPN5> for BgpMVPN5 {
 #[derive(Serialize, Deserialize)]
 pub struct BgpMVPN67 {
     pub rd: BgpRD,
-    pub asn: u32,
+    pub asn: Asn,
     pub rp: std::net::IpAddr,
     pub group: std::net::IpAddr,
 }
@@ -275,7 +275,7 @@ impl BgpAddrItem<BgpMVPN67> for BgpMVPN67 {
             return Ok((
                 BgpMVPN67 {
                     rd: rdp.0,
-                    asn: getn_u32(&buf[8..12]),
+                    asn: Asn(getn_u32(&buf[8..12])),
                     rp: decode_addr_from(&buf[13..17])?,
                     group: decode_addr_from(&buf[18..22])?,
                 },
@@ -289,7 +289,7 @@ impl BgpAddrItem<BgpMVPN67> for BgpMVPN67 {
             return Ok((
                 BgpMVPN67 {
                     rd: rdp.0,
-                    asn: getn_u32(&buf[8..12]),
+                    asn: Asn(getn_u32(&buf[8..12])),
                     rp: decode_addr_from(&buf[13..29])?,
                     group: decode_addr_from(&buf[30..46])?,
                 },
@@ -300,7 +300,7 @@ impl BgpAddrItem<BgpMVPN67> for BgpMVPN67 {
     }
     fn encode_to(&self, mode: BgpTransportMode, buf: &mut [u8]) -> Result<usize, BgpError> {
         let mut p1 = self.rd.encode_to(mode, buf)?;
-        setn_u32(self.asn, &mut buf[p1..]);
+        setn_u32(self.asn.value(), &mut buf[p1..]);
         p1 += 4;
         p1 += encode_addr_to(&self.rp, &mut buf[p1..])?;
         p1 += encode_addr_to(&self.group, &mut buf[p1..])?;
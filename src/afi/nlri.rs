@@ -0,0 +1,513 @@
+// Copyright 2021 Vladimir Melnikov.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! This module contains object-safe NLRI abstraction over all NLRI kinds
+//!
+//! [Nlri] is implemented by NLRI item types. Some item types are shared between SAFIs
+//! (the same prefix type is used for unicast and multicast), such items report unicast one.
+//! [BgpNlriItem] adapter carries single item of any [BgpAddrs] variant with its exact afi/safi.
+
+use crate::afi::*;
+
+/// Object-safe NLRI interface for generic storage, logging and filtering.
+pub trait Nlri: std::fmt::Display + std::fmt::Debug + Send + Sync {
+    /// converts to single-item NLRI collection
+    fn to_addrs(&self) -> BgpAddrs;
+    /// clones into box
+    fn box_clone(&self) -> Box<dyn Nlri>;
+    /// IP prefix length in bits (without RD and labels), None for NLRI kinds without prefix
+    fn prefixlen(&self) -> Option<usize> {
+        None
+    }
+    /// path identifier, if any
+    fn pathid(&self) -> Option<BgpPathId> {
+        None
+    }
+    /// BGP afi+safi codes
+    fn afi_safi(&self) -> (u16, u8) {
+        self.to_addrs().get_afi_safi()
+    }
+    /// encodes NLRI in wire format
+    fn encode_to(&self, peer: &BgpSessionParams, buf: &mut [u8]) -> Result<usize, BgpError> {
+        self.to_addrs().encode_to(peer, buf)
+    }
+}
+
+impl Clone for Box<dyn Nlri> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+impl Nlri for BgpAddrV4 {
+    fn to_addrs(&self) -> BgpAddrs {
+        BgpAddrs::IPV4U(vec![self.clone()])
+    }
+    fn box_clone(&self) -> Box<dyn Nlri> {
+        Box::new(self.clone())
+    }
+    fn prefixlen(&self) -> Option<usize> {
+        Some(self.prefixlen as usize)
+    }
+}
+impl Nlri for BgpAddrV6 {
+    fn to_addrs(&self) -> BgpAddrs {
+        BgpAddrs::IPV6U(vec![self.clone()])
+    }
+    fn box_clone(&self) -> Box<dyn Nlri> {
+        Box::new(self.clone())
+    }
+    fn prefixlen(&self) -> Option<usize> {
+        Some(self.prefixlen as usize)
+    }
+}
+impl Nlri for Labeled<BgpAddrV4> {
+    fn to_addrs(&self) -> BgpAddrs {
+        BgpAddrs::IPV4LU(vec![self.clone()])
+    }
+    fn box_clone(&self) -> Box<dyn Nlri> {
+        Box::new(self.clone())
+    }
+    fn prefixlen(&self) -> Option<usize> {
+        Nlri::prefixlen(&self.prefix)
+    }
+}
+impl Nlri for Labeled<BgpAddrV6> {
+    fn to_addrs(&self) -> BgpAddrs {
+        BgpAddrs::IPV6LU(vec![self.clone()])
+    }
+    fn box_clone(&self) -> Box<dyn Nlri> {
+        Box::new(self.clone())
+    }
+    fn prefixlen(&self) -> Option<usize> {
+        Nlri::prefixlen(&self.prefix)
+    }
+}
+impl Nlri for Labeled<WithRd<BgpAddrV4>> {
+    fn to_addrs(&self) -> BgpAddrs {
+        BgpAddrs::VPNV4U(vec![self.clone()])
+    }
+    fn box_clone(&self) -> Box<dyn Nlri> {
+        Box::new(self.clone())
+    }
+    fn prefixlen(&self) -> Option<usize> {
+        Nlri::prefixlen(&self.prefix.prefix)
+    }
+}
+impl Nlri for Labeled<WithRd<BgpAddrV6>> {
+    fn to_addrs(&self) -> BgpAddrs {
+        BgpAddrs::VPNV6U(vec![self.clone()])
+    }
+    fn box_clone(&self) -> Box<dyn Nlri> {
+        Box::new(self.clone())
+    }
+    fn prefixlen(&self) -> Option<usize> {
+        Nlri::prefixlen(&self.prefix.prefix)
+    }
+}
+impl Nlri for WithRd<BgpMdtV4> {
+    fn to_addrs(&self) -> BgpAddrs {
+        BgpAddrs::IPV4MDT(vec![self.clone()])
+    }
+    fn box_clone(&self) -> Box<dyn Nlri> {
+        Box::new(self.clone())
+    }
+    fn prefixlen(&self) -> Option<usize> {
+        Some(self.prefix.addr.prefixlen as usize)
+    }
+}
+impl Nlri for WithRd<BgpMdtV6> {
+    fn to_addrs(&self) -> BgpAddrs {
+        BgpAddrs::IPV6MDT(vec![self.clone()])
+    }
+    fn box_clone(&self) -> Box<dyn Nlri> {
+        Box::new(self.clone())
+    }
+    fn prefixlen(&self) -> Option<usize> {
+        Some(self.prefix.addr.prefixlen as usize)
+    }
+}
+impl Nlri for BgpAddrL2 {
+    fn to_addrs(&self) -> BgpAddrs {
+        BgpAddrs::L2VPLS(vec![self.clone()])
+    }
+    fn box_clone(&self) -> Box<dyn Nlri> {
+        Box::new(self.clone())
+    }
+}
+impl Nlri for BgpMVPN {
+    fn to_addrs(&self) -> BgpAddrs {
+        BgpAddrs::MVPN(vec![self.clone()])
+    }
+    fn box_clone(&self) -> Box<dyn Nlri> {
+        Box::new(self.clone())
+    }
+}
+impl Nlri for BgpEVPN {
+    fn to_addrs(&self) -> BgpAddrs {
+        BgpAddrs::EVPN(vec![self.clone()])
+    }
+    fn box_clone(&self) -> Box<dyn Nlri> {
+        Box::new(self.clone())
+    }
+}
+impl Nlri for BgpFlowSpec<BgpAddrV4> {
+    fn to_addrs(&self) -> BgpAddrs {
+        BgpAddrs::FS4U(vec![self.clone()])
+    }
+    fn box_clone(&self) -> Box<dyn Nlri> {
+        Box::new(self.clone())
+    }
+}
+impl Nlri for BgpFlowSpec<FS6> {
+    fn to_addrs(&self) -> BgpAddrs {
+        BgpAddrs::FS6U(vec![self.clone()])
+    }
+    fn box_clone(&self) -> Box<dyn Nlri> {
+        Box::new(self.clone())
+    }
+}
+impl Nlri for BgpFlowSpec<FSV4U> {
+    fn to_addrs(&self) -> BgpAddrs {
+        BgpAddrs::FSV4U(vec![self.clone()])
+    }
+    fn box_clone(&self) -> Box<dyn Nlri> {
+        Box::new(self.clone())
+    }
+}
+impl Nlri for WithPathId<BgpAddrV4> {
+    fn to_addrs(&self) -> BgpAddrs {
+        BgpAddrs::IPV4UP(vec![self.clone()])
+    }
+    fn box_clone(&self) -> Box<dyn Nlri> {
+        Box::new(self.clone())
+    }
+    fn prefixlen(&self) -> Option<usize> {
+        Nlri::prefixlen(&self.nlri)
+    }
+    fn pathid(&self) -> Option<BgpPathId> {
+        Some(self.pathid)
+    }
+}
+impl Nlri for WithPathId<BgpAddrV6> {
+    fn to_addrs(&self) -> BgpAddrs {
+        BgpAddrs::IPV6UP(vec![self.clone()])
+    }
+    fn box_clone(&self) -> Box<dyn Nlri> {
+        Box::new(self.clone())
+    }
+    fn prefixlen(&self) -> Option<usize> {
+        Nlri::prefixlen(&self.nlri)
+    }
+    fn pathid(&self) -> Option<BgpPathId> {
+        Some(self.pathid)
+    }
+}
+impl Nlri for WithPathId<Labeled<BgpAddrV4>> {
+    fn to_addrs(&self) -> BgpAddrs {
+        BgpAddrs::IPV4LUP(vec![self.clone()])
+    }
+    fn box_clone(&self) -> Box<dyn Nlri> {
+        Box::new(self.clone())
+    }
+    fn prefixlen(&self) -> Option<usize> {
+        Nlri::prefixlen(&self.nlri)
+    }
+    fn pathid(&self) -> Option<BgpPathId> {
+        Some(self.pathid)
+    }
+}
+impl Nlri for WithPathId<Labeled<BgpAddrV6>> {
+    fn to_addrs(&self) -> BgpAddrs {
+        BgpAddrs::IPV6LUP(vec![self.clone()])
+    }
+    fn box_clone(&self) -> Box<dyn Nlri> {
+        Box::new(self.clone())
+    }
+    fn prefixlen(&self) -> Option<usize> {
+        Nlri::prefixlen(&self.nlri)
+    }
+    fn pathid(&self) -> Option<BgpPathId> {
+        Some(self.pathid)
+    }
+}
+impl Nlri for WithPathId<Labeled<WithRd<BgpAddrV4>>> {
+    fn to_addrs(&self) -> BgpAddrs {
+        BgpAddrs::VPNV4UP(vec![self.clone()])
+    }
+    fn box_clone(&self) -> Box<dyn Nlri> {
+        Box::new(self.clone())
+    }
+    fn prefixlen(&self) -> Option<usize> {
+        Nlri::prefixlen(&self.nlri)
+    }
+    fn pathid(&self) -> Option<BgpPathId> {
+        Some(self.pathid)
+    }
+}
+impl Nlri for WithPathId<Labeled<WithRd<BgpAddrV6>>> {
+    fn to_addrs(&self) -> BgpAddrs {
+        BgpAddrs::VPNV6UP(vec![self.clone()])
+    }
+    fn box_clone(&self) -> Box<dyn Nlri> {
+        Box::new(self.clone())
+    }
+    fn prefixlen(&self) -> Option<usize> {
+        Nlri::prefixlen(&self.nlri)
+    }
+    fn pathid(&self) -> Option<BgpPathId> {
+        Some(self.pathid)
+    }
+}
+impl Nlri for WithPathId<WithRd<BgpMdtV4>> {
+    fn to_addrs(&self) -> BgpAddrs {
+        BgpAddrs::IPV4MDTP(vec![self.clone()])
+    }
+    fn box_clone(&self) -> Box<dyn Nlri> {
+        Box::new(self.clone())
+    }
+    fn prefixlen(&self) -> Option<usize> {
+        Nlri::prefixlen(&self.nlri)
+    }
+    fn pathid(&self) -> Option<BgpPathId> {
+        Some(self.pathid)
+    }
+}
+impl Nlri for WithPathId<WithRd<BgpMdtV6>> {
+    fn to_addrs(&self) -> BgpAddrs {
+        BgpAddrs::IPV6MDTP(vec![self.clone()])
+    }
+    fn box_clone(&self) -> Box<dyn Nlri> {
+        Box::new(self.clone())
+    }
+    fn prefixlen(&self) -> Option<usize> {
+        Nlri::prefixlen(&self.nlri)
+    }
+    fn pathid(&self) -> Option<BgpPathId> {
+        Some(self.pathid)
+    }
+}
+
+/// Single NLRI of any kind, adapter keeping exact afi/safi of its collection
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BgpNlriItem(BgpAddrs);
+
+impl BgpNlriItem {
+    /// Decodes NLRI of given afi/safi from buffer.
+    pub fn decode_from(
+        peer: &BgpSessionParams,
+        afi: u16,
+        safi: u8,
+        buf: &[u8],
+    ) -> Result<Vec<BgpNlriItem>, BgpError> {
+        Ok(BgpAddrs::decode_from(peer, afi, safi, buf)?.0.nlri_items())
+    }
+    /// Inner single-item collection
+    pub fn addrs(&self) -> &BgpAddrs {
+        &self.0
+    }
+    /// Inner NLRI item
+    pub fn item(&self) -> Option<&dyn Nlri> {
+        match &self.0 {
+            BgpAddrs::None => None,
+            BgpAddrs::IPV4U(v) | BgpAddrs::IPV4M(v) => first_item(v),
+            BgpAddrs::IPV4LU(v) => first_item(v),
+            BgpAddrs::VPNV4U(v) | BgpAddrs::VPNV4M(v) => first_item(v),
+            BgpAddrs::IPV4MDT(v) => first_item(v),
+            BgpAddrs::IPV4MDTP(v) => first_item(v),
+            BgpAddrs::IPV6U(v) | BgpAddrs::IPV6M(v) => first_item(v),
+            BgpAddrs::IPV6LU(v) => first_item(v),
+            BgpAddrs::VPNV6U(v) | BgpAddrs::VPNV6M(v) => first_item(v),
+            BgpAddrs::IPV6MDT(v) => first_item(v),
+            BgpAddrs::IPV6MDTP(v) => first_item(v),
+            BgpAddrs::L2VPLS(v) => first_item(v),
+            BgpAddrs::MVPN(v) => first_item(v),
+            BgpAddrs::EVPN(v) => first_item(v),
+            BgpAddrs::FS4U(v) => first_item(v),
+            BgpAddrs::FS6U(v) => first_item(v),
+            BgpAddrs::FSV4U(v) => first_item(v),
+            BgpAddrs::IPV4UP(v) | BgpAddrs::IPV4MP(v) => first_item(v),
+            BgpAddrs::IPV4LUP(v) => first_item(v),
+            BgpAddrs::VPNV4UP(v) | BgpAddrs::VPNV4MP(v) => first_item(v),
+            BgpAddrs::IPV6UP(v) | BgpAddrs::IPV6MP(v) => first_item(v),
+            BgpAddrs::IPV6LUP(v) => first_item(v),
+            BgpAddrs::VPNV6UP(v) | BgpAddrs::VPNV6MP(v) => first_item(v),
+        }
+    }
+}
+
+fn first_item<T: Nlri>(v: &[T]) -> Option<&dyn Nlri> {
+    v.first().map(|x| x as &dyn Nlri)
+}
+
+impl Nlri for BgpNlriItem {
+    fn afi_safi(&self) -> (u16, u8) {
+        self.0.get_afi_safi()
+    }
+    fn prefixlen(&self) -> Option<usize> {
+        self.item().and_then(|x| x.prefixlen())
+    }
+    fn pathid(&self) -> Option<BgpPathId> {
+        self.item().and_then(|x| x.pathid())
+    }
+    fn encode_to(&self, peer: &BgpSessionParams, buf: &mut [u8]) -> Result<usize, BgpError> {
+        self.0.encode_to(peer, buf)
+    }
+    fn to_addrs(&self) -> BgpAddrs {
+        self.0.clone()
+    }
+    fn box_clone(&self) -> Box<dyn Nlri> {
+        Box::new(self.clone())
+    }
+}
+
+impl std::fmt::Display for BgpNlriItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.item() {
+            None => Ok(()),
+            Some(x) => std::fmt::Display::fmt(x, f),
+        }
+    }
+}
+
+fn single_items<T: Clone>(v: &[T], f: fn(Vec<T>) -> BgpAddrs) -> Vec<BgpNlriItem> {
+    v.iter().map(|x| BgpNlriItem(f(vec![x.clone()]))).collect()
+}
+
+impl BgpAddrs {
+    /// splits collection into single NLRI items
+    pub fn nlri_items(&self) -> Vec<BgpNlriItem> {
+        match self {
+            BgpAddrs::None => Vec::new(),
+            BgpAddrs::IPV4U(v) => single_items(v, BgpAddrs::IPV4U),
+            BgpAddrs::IPV4M(v) => single_items(v, BgpAddrs::IPV4M),
+            BgpAddrs::IPV4LU(v) => single_items(v, BgpAddrs::IPV4LU),
+            BgpAddrs::VPNV4U(v) => single_items(v, BgpAddrs::VPNV4U),
+            BgpAddrs::VPNV4M(v) => single_items(v, BgpAddrs::VPNV4M),
+            BgpAddrs::IPV6U(v) => single_items(v, BgpAddrs::IPV6U),
+            BgpAddrs::IPV6M(v) => single_items(v, BgpAddrs::IPV6M),
+            BgpAddrs::IPV6LU(v) => single_items(v, BgpAddrs::IPV6LU),
+            BgpAddrs::VPNV6U(v) => single_items(v, BgpAddrs::VPNV6U),
+            BgpAddrs::VPNV6M(v) => single_items(v, BgpAddrs::VPNV6M),
+            BgpAddrs::L2VPLS(v) => single_items(v, BgpAddrs::L2VPLS),
+            BgpAddrs::MVPN(v) => single_items(v, BgpAddrs::MVPN),
+            BgpAddrs::EVPN(v) => single_items(v, BgpAddrs::EVPN),
+            BgpAddrs::FS4U(v) => single_items(v, BgpAddrs::FS4U),
+            BgpAddrs::FS6U(v) => single_items(v, BgpAddrs::FS6U),
+            BgpAddrs::FSV4U(v) => single_items(v, BgpAddrs::FSV4U),
+            BgpAddrs::IPV4UP(v) => single_items(v, BgpAddrs::IPV4UP),
+            BgpAddrs::IPV4MP(v) => single_items(v, BgpAddrs::IPV4MP),
+            BgpAddrs::IPV4LUP(v) => single_items(v, BgpAddrs::IPV4LUP),
+            BgpAddrs::VPNV4UP(v) => single_items(v, BgpAddrs::VPNV4UP),
+            BgpAddrs::VPNV4MP(v) => single_items(v, BgpAddrs::VPNV4MP),
+            BgpAddrs::IPV6UP(v) => single_items(v, BgpAddrs::IPV6UP),
+            BgpAddrs::IPV6MP(v) => single_items(v, BgpAddrs::IPV6MP),
+            BgpAddrs::IPV6LUP(v) => single_items(v, BgpAddrs::IPV6LUP),
+            BgpAddrs::VPNV6UP(v) => single_items(v, BgpAddrs::VPNV6UP),
+            BgpAddrs::VPNV6MP(v) => single_items(v, BgpAddrs::VPNV6MP),
+            BgpAddrs::IPV4MDT(v) => single_items(v, BgpAddrs::IPV4MDT),
+            BgpAddrs::IPV4MDTP(v) => single_items(v, BgpAddrs::IPV4MDTP),
+            BgpAddrs::IPV6MDT(v) => single_items(v, BgpAddrs::IPV6MDT),
+            BgpAddrs::IPV6MDTP(v) => single_items(v, BgpAddrs::IPV6MDTP),
+        }
+    }
+    /// splits collection into boxed NLRI items
+    pub fn boxed_nlri(&self) -> Vec<Box<dyn Nlri>> {
+        self.nlri_items()
+            .into_iter()
+            .map(|x| Box::new(x) as Box<dyn Nlri>)
+            .collect()
+    }
+    /// collects NLRI items of the same afi/safi back into collection
+    pub fn from_nlri<'a, I: IntoIterator<Item = &'a dyn Nlri>>(
+        items: I,
+    ) -> Result<BgpAddrs, BgpError> {
+        let mut ret = BgpAddrs::None;
+        for item in items {
+            ret.try_append(item.to_addrs())?;
+        }
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nlri_items() {
+        let params = BgpSessionParams::new(
+            Asn(64512),
+            180,
+            BgpTransportMode::IPv4,
+            std::net::Ipv4Addr::new(1, 1, 1, 1),
+            vec![],
+        );
+        let addrs = BgpAddrs::VPNV4U(vec![
+            Labeled::new(
+                MplsLabels::fromvec(vec![100]),
+                WithRd::new(
+                    BgpRD::new(1, 1),
+                    BgpAddrV4::new(std::net::Ipv4Addr::new(10, 0, 0, 0), 8),
+                ),
+            ),
+            Labeled::new(
+                MplsLabels::fromvec(vec![200]),
+                WithRd::new(
+                    BgpRD::new(1, 2),
+                    BgpAddrV4::new(std::net::Ipv4Addr::new(10, 1, 0, 0), 16),
+                ),
+            ),
+        ]);
+        let items = addrs.boxed_nlri();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1].afi_safi(), (1, 128));
+        assert_eq!(items[1].prefixlen(), Some(16));
+        assert_eq!(items[1].pathid(), None);
+        let filtered: Vec<&dyn Nlri> = items
+            .iter()
+            .filter(|x| x.prefixlen() > Some(8))
+            .map(|x| x.as_ref())
+            .collect();
+        assert_eq!(BgpAddrs::from_nlri(filtered).unwrap().len(), 1);
+        let mut buf = vec![0_u8; 64];
+        let len = items[0].encode_to(&params, &mut buf).unwrap();
+        let decoded = BgpNlriItem::decode_from(&params, 1, 128, &buf[..len]).unwrap();
+        assert_eq!(decoded[0].to_string(), items[0].to_string());
+        assert_eq!(
+            BgpAddrs::from_nlri(items.iter().map(|x| x.as_ref())).unwrap(),
+            addrs
+        );
+    }
+    #[test]
+    fn test_nlri_item_types() {
+        let pfx = BgpAddrV4::new(std::net::Ipv4Addr::new(10, 1, 0, 0), 16);
+        let vpn = Labeled::new(
+            MplsLabels::fromvec(vec![100]),
+            WithRd::new(BgpRD::new(1, 1), pfx.clone()),
+        );
+        assert_eq!(Nlri::prefixlen(&vpn), Some(16));
+        assert_eq!(Nlri::afi_safi(&vpn), (1, 128));
+        let wp = WithPathId::new(7, vpn.clone());
+        assert_eq!(Nlri::prefixlen(&wp), Some(16));
+        assert_eq!(Nlri::pathid(&wp), Some(7));
+        assert_eq!(Nlri::afi_safi(&wp), (1, 128));
+        let l2 = BgpAddrL2 {
+            rd: BgpRD::new(1, 1),
+            site: 1,
+            offset: 1,
+            range: 1,
+            labels: MplsLabels::fromvec(vec![100]),
+        };
+        assert_eq!(Nlri::prefixlen(&l2), None);
+        let item = BgpAddrs::IPV4M(vec![pfx]).nlri_items().remove(0);
+        assert_eq!(item.afi_safi(), (1, 2));
+        assert_eq!(item.prefixlen(), Some(16));
+        assert_eq!(item.to_string(), "10.1.0.0/16");
+    }
+}
@@ -0,0 +1,187 @@
+// Copyright 2021 Vladimir Melnikov.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! This module contains ADD-PATH path identifier allocator for senders
+
+use crate::afi::{BgpAddrs, BgpNlriItem, BgpPathId};
+use crate::{BgpError, BgpSessionParams};
+use std::collections::BTreeMap;
+
+/// Path identifier allocator.
+///
+/// Assigns stable path identifiers per NLRI (which implies afi and safi) for every advertised path,
+/// identified by key P (for example originating peer address).
+/// Identifiers start from 1 and released ones are reused.
+#[derive(Debug, Clone)]
+pub struct BgpPathIdAllocator<P: Ord + Clone> {
+    paths: BTreeMap<BgpNlriItem, BTreeMap<P, BgpPathId>>,
+}
+
+impl<P: Ord + Clone> Default for BgpPathIdAllocator<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: Ord + Clone> BgpPathIdAllocator<P> {
+    pub fn new() -> BgpPathIdAllocator<P> {
+        BgpPathIdAllocator {
+            paths: BTreeMap::new(),
+        }
+    }
+    /// Returns path identifier for given NLRI and path, if allocated.
+    pub fn get(&self, nlri: &BgpNlriItem, path: &P) -> Option<BgpPathId> {
+        self.paths.get(nlri).and_then(|m| m.get(path).cloned())
+    }
+    /// Returns existing path identifier or allocates lowest free one.
+    pub fn allocate(&mut self, nlri: &BgpNlriItem, path: &P) -> BgpPathId {
+        let ids = self.paths.entry(nlri.clone()).or_default();
+        if let Some(id) = ids.get(path) {
+            return *id;
+        }
+        let mut id: BgpPathId = 1;
+        while ids.values().any(|x| *x == id) {
+            id += 1;
+        }
+        ids.insert(path.clone(), id);
+        id
+    }
+    /// Releases path identifier, returns it if it was allocated.
+    pub fn release(&mut self, nlri: &BgpNlriItem, path: &P) -> Option<BgpPathId> {
+        let ids = self.paths.get_mut(nlri)?;
+        let ret = ids.remove(path);
+        if ids.is_empty() {
+            self.paths.remove(nlri);
+        }
+        ret
+    }
+    /// Number of NLRI with allocated identifiers.
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+    /// Prepares NLRI for announcement to peer: assigns path identifiers when
+    /// peer accepts ADD-PATH for this afi/safi, drops them otherwise.
+    pub fn announce(
+        &mut self,
+        peer: &BgpSessionParams,
+        addrs: BgpAddrs,
+        path: &P,
+    ) -> Result<BgpAddrs, BgpError> {
+        let (afi, safi) = addrs.get_afi_safi();
+        let addrs = addrs.without_pathid();
+        if !peer.check_addpath_send(afi, safi) {
+            return Ok(addrs);
+        }
+        let mut ret = BgpAddrs::None;
+        for nlri in addrs.nlri_items() {
+            let id = self.allocate(&nlri, path);
+            ret.try_append(nlri.addrs().clone().with_pathid(id))?;
+        }
+        Ok(ret)
+    }
+    /// Prepares NLRI for withdraw from peer with the same path identifiers
+    /// that were announced, and releases them.
+    /// Fails without releasing anything if peer accepts ADD-PATH
+    /// and some NLRI was not announced for this path.
+    pub fn withdraw(
+        &mut self,
+        peer: &BgpSessionParams,
+        addrs: BgpAddrs,
+        path: &P,
+    ) -> Result<BgpAddrs, BgpError> {
+        let (afi, safi) = addrs.get_afi_safi();
+        let addrs = addrs.without_pathid();
+        let items = addrs.nlri_items();
+        if !peer.check_addpath_send(afi, safi) {
+            for nlri in items.iter() {
+                self.release(nlri, path);
+            }
+            return Ok(addrs);
+        }
+        if let Some(nlri) = items.iter().find(|n| self.get(n, path).is_none()) {
+            return Err(BgpError::from_string(format!(
+                "No path identifier allocated for {}",
+                nlri
+            )));
+        }
+        let mut ret = BgpAddrs::None;
+        for nlri in items {
+            let id = self.release(&nlri, path).unwrap_or_default();
+            ret.try_append(nlri.addrs().clone().with_pathid(id))?;
+        }
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_pathid_allocator() {
+        let peer = BgpSessionParams::new(
+            Asn(64512),
+            180,
+            BgpTransportMode::IPv4,
+            std::net::Ipv4Addr::new(1, 1, 1, 1),
+            vec![
+                BgpCapability::SafiIPv4u,
+                BgpCapability::CapAddPath(vec![BgpCapAddPath::new_from_cap(
+                    BgpCapability::SafiIPv4u,
+                    true,
+                    true,
+                )
+                .unwrap()]),
+            ],
+        );
+        let net = BgpAddrs::IPV4U(vec![BgpAddrV4::new(
+            std::net::Ipv4Addr::new(10, 0, 0, 0),
+            8,
+        )]);
+        let mut alloc = BgpPathIdAllocator::<u32>::new();
+        let a1 = alloc.announce(&peer, net.clone(), &1).unwrap();
+        let a2 = alloc.announce(&peer, net.clone(), &2).unwrap();
+        assert!(matches!(a1, BgpAddrs::IPV4UP(ref v) if v[0].pathid == 1));
+        assert!(matches!(a2, BgpAddrs::IPV4UP(ref v) if v[0].pathid == 2));
+        // stable
+        let a1 = alloc.announce(&peer, net.clone(), &1).unwrap();
+        assert!(matches!(a1, BgpAddrs::IPV4UP(ref v) if v[0].pathid == 1));
+        let w1 = alloc.withdraw(&peer, net.clone(), &1).unwrap();
+        assert!(matches!(w1, BgpAddrs::IPV4UP(ref v) if v[0].pathid == 1));
+        // unknown path
+        assert!(alloc.withdraw(&peer, net.clone(), &1).is_err());
+        // reused
+        let a3 = alloc.announce(&peer, net.clone(), &3).unwrap();
+        assert!(matches!(a3, BgpAddrs::IPV4UP(ref v) if v[0].pathid == 1));
+        assert_eq!(alloc.len(), 1);
+        // same prefix in other safi is separate NLRI
+        let mcast = BgpAddrs::IPV4M(vec![BgpAddrV4::new(
+            std::net::Ipv4Addr::new(10, 0, 0, 0),
+            8,
+        )]);
+        assert_eq!(alloc.allocate(&mcast.nlri_items()[0], &2), 1);
+        assert_eq!(alloc.len(), 2);
+        // no addpath - no identifiers, withdraw still releases
+        let mut plain = peer.clone();
+        plain
+            .caps
+            .retain(|c| !matches!(c, BgpCapability::CapAddPath(_)));
+        assert!(matches!(
+            alloc.announce(&plain, net.clone(), &1).unwrap(),
+            BgpAddrs::IPV4U(_)
+        ));
+        alloc.withdraw(&plain, net.clone(), &2).unwrap();
+        alloc.withdraw(&plain, net.clone(), &3).unwrap();
+        assert_eq!(alloc.get(&net.nlri_items()[0], &2), None);
+        assert_eq!(alloc.len(), 1);
+    }
+}
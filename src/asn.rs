@@ -0,0 +1,132 @@
+// Copyright 2021 Vladimir Melnikov.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! This module contains autonomous system number type
+//!
+//! AS numbers are always 32-bit in this crate, 2-byte encodings (OPEN message
+//! "My AS" field, AS2 paths) are converted on the wire.
+
+use crate::BgpError;
+#[cfg(feature = "serialization")]
+use serde::{Deserialize, Serialize};
+
+/// Autonomous system number
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialization", serde(transparent))]
+pub struct Asn(pub u32);
+
+/// AS_TRANS - 2-byte placeholder for 4-byte AS numbers (RFC 6793)
+pub const AS_TRANS: Asn = Asn(23456);
+
+impl Asn {
+    /// Numeric value
+    pub fn value(&self) -> u32 {
+        self.0
+    }
+    /// Returns true if AS number fits into 2 bytes.
+    pub fn is_16bit(&self) -> bool {
+        self.0 <= 0xffff
+    }
+    /// Value for 2-byte fields, AS_TRANS for 4-byte AS numbers.
+    pub fn to_u16_or_trans(&self) -> u16 {
+        if self.is_16bit() {
+            self.0 as u16
+        } else {
+            AS_TRANS.0 as u16
+        }
+    }
+    /// Private use AS number (RFC 6996).
+    pub fn is_private(&self) -> bool {
+        (64512..=65534).contains(&self.0) || (4200000000..=4294967294).contains(&self.0)
+    }
+    /// Reserved, documentation or AS_TRANS number (RFC 1930, 5398, 6793, 7300).
+    pub fn is_reserved(&self) -> bool {
+        matches!(self.0, 0 | 23456 | 65535 | 4294967295)
+            || (64496..=64511).contains(&self.0)
+            || (65536..=65551).contains(&self.0)
+            || (65552..=131071).contains(&self.0)
+    }
+    /// asdot notation (RFC 5396): "1.10" for 4-byte numbers, asplain for 2-byte ones.
+    pub fn to_asdot(&self) -> String {
+        if self.is_16bit() {
+            self.0.to_string()
+        } else {
+            format!("{}.{}", self.0 >> 16, self.0 & 0xffff)
+        }
+    }
+}
+
+impl From<u32> for Asn {
+    fn from(v: u32) -> Self {
+        Asn(v)
+    }
+}
+impl From<u16> for Asn {
+    fn from(v: u16) -> Self {
+        Asn(v as u32)
+    }
+}
+impl From<Asn> for u32 {
+    fn from(v: Asn) -> Self {
+        v.0
+    }
+}
+
+impl std::fmt::Debug for Asn {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+impl std::fmt::Display for Asn {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::str::FromStr for Asn {
+    type Err = BgpError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let t = s.trim();
+        let t = t
+            .strip_prefix("AS")
+            .or_else(|| t.strip_prefix("as"))
+            .unwrap_or(t);
+        let err = || BgpError::from_string(format!("Invalid AS number: {}", s));
+        match t.split_once('.') {
+            Some((h, l)) => {
+                let h: u16 = h.parse().map_err(|_| err())?;
+                let l: u16 = l.parse().map_err(|_| err())?;
+                Ok(Asn(((h as u32) << 16) | l as u32))
+            }
+            None => Ok(Asn(t.parse().map_err(|_| err())?)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_asn() {
+        assert_eq!("65000".parse::<Asn>().unwrap(), Asn(65000));
+        assert_eq!("AS1.10".parse::<Asn>().unwrap(), Asn(65546));
+        assert!("1.70000".parse::<Asn>().is_err());
+        assert_eq!(Asn(65546).to_asdot(), "1.10");
+        assert_eq!(Asn(65546).to_string(), "65546");
+        assert_eq!(Asn(4200000000).to_u16_or_trans(), 23456);
+        assert!(Asn(64512).is_private());
+        assert!(Asn(4200000000).is_private());
+        assert!(!Asn(3356).is_private());
+        assert!(AS_TRANS.is_reserved());
+        assert!(Asn(64496).is_reserved());
+        assert!(!Asn(3356).is_reserved());
+    }
+}
@@ -52,6 +52,54 @@ pub fn encode_bmp_addr_to(addr: &std::net::IpAddr, buf: &mut [u8]) -> Result<usi
     Ok(16)
 }
 
+/// Peer distinguisher interpreted according to peer type
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BmpPeerDistinguisher {
+    /// global instance peer, distinguisher is zero
+    Global,
+    /// RD instance peer
+    Rd(BgpRD),
+    /// local instance peer, locally defined instance identifier
+    Local(u64),
+    /// unknown peer type, raw value
+    Other(u8, u64),
+}
+
+impl BmpPeerDistinguisher {
+    /// Interprets raw distinguisher for given peer type.
+    pub fn new(peertype: u8, rd: &BgpRD) -> BmpPeerDistinguisher {
+        match peertype {
+            0 => BmpPeerDistinguisher::Global,
+            1 => BmpPeerDistinguisher::Rd(rd.clone()),
+            2 => BmpPeerDistinguisher::Local(((rd.rdh as u64) << 32) | rd.rdl as u64),
+            n => BmpPeerDistinguisher::Other(n, ((rd.rdh as u64) << 32) | rd.rdl as u64),
+        }
+    }
+}
+
+impl From<&BmpPeerDistinguisher> for BgpRD {
+    fn from(pd: &BmpPeerDistinguisher) -> BgpRD {
+        match pd {
+            BmpPeerDistinguisher::Global => BgpRD::new(0, 0),
+            BmpPeerDistinguisher::Rd(rd) => rd.clone(),
+            BmpPeerDistinguisher::Local(v) | BmpPeerDistinguisher::Other(_, v) => {
+                BgpRD::new((*v >> 32) as u32, *v as u32)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for BmpPeerDistinguisher {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BmpPeerDistinguisher::Global => write!(f, "global"),
+            BmpPeerDistinguisher::Rd(rd) => write!(f, "rd:{}", rd),
+            BmpPeerDistinguisher::Local(v) => write!(f, "local:{}", v),
+            BmpPeerDistinguisher::Other(t, v) => write!(f, "type{}:{:#018x}", t, v),
+        }
+    }
+}
+
 /// peer header
 #[derive(Debug, Clone)]
 pub struct BmpMessagePeerHeader {
@@ -59,7 +107,7 @@ pub struct BmpMessagePeerHeader {
     pub peertype: u8,
     /// flags
     pub flags: u8,
-    /// route distinguisher of VRF of this peer
+    /// raw peer distinguisher, see [distinguisher](Self::distinguisher)
     pub peerdistinguisher: BgpRD,
     /// peer IP address
     pub peeraddress: std::net::IpAddr,
@@ -72,6 +120,10 @@ pub struct BmpMessagePeerHeader {
 }
 
 impl BmpMessagePeerHeader {
+    /// Peer distinguisher interpreted according to peer type
+    pub fn distinguisher(&self) -> BmpPeerDistinguisher {
+        BmpPeerDistinguisher::new(self.peertype, &self.peerdistinguisher)
+    }
     pub fn decode_from(buf: &[u8]) -> Result<(BmpMessagePeerHeader, usize), BgpError> {
         if buf.len() < 42 {
             return Err(BgpError::InsufficientBufferSize);
@@ -185,7 +237,7 @@ impl From<&BmpMessagePeerHeader> for BgpSessionParams {
     #[inline]
     fn from(bmph: &BmpMessagePeerHeader) -> BgpSessionParams {
         BgpSessionParams::new(
-            bmph.asnum,
+            Asn(bmph.asnum),
             0,
             bmph.peeraddress.into(),
             bmph.routerid,
@@ -193,3 +245,29 @@ impl From<&BmpMessagePeerHeader> for BgpSessionParams {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peer_distinguisher() {
+        let rd = BgpRD::new(65001, 100);
+        assert_eq!(
+            BmpPeerDistinguisher::new(0, &rd),
+            BmpPeerDistinguisher::Global
+        );
+        assert_eq!(
+            BmpPeerDistinguisher::new(1, &rd).to_string(),
+            "rd:65001:100"
+        );
+        let local = BmpPeerDistinguisher::new(2, &BgpRD::new(0, 7));
+        assert_eq!(local, BmpPeerDistinguisher::Local(7));
+        assert_eq!(local.to_string(), "local:7");
+        assert_eq!(BgpRD::from(&local), BgpRD::new(0, 7));
+        assert_eq!(
+            BmpPeerDistinguisher::new(9, &BgpRD::new(0, 1)).to_string(),
+            "type9:0x0000000000000001"
+        );
+    }
+}
@@ -0,0 +1,286 @@
+// Copyright 2021 Vladimir Melnikov.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! BMP to BGP re-advertisement bridge
+
+use crate::bmp::msgrmon::BmpMessageRouteMonitoring;
+use crate::prelude::*;
+
+/// AS4_PATH path attribute typecode
+const BGP_ATTR_AS4PATH: u8 = 17;
+/// AS4_AGGREGATOR path attribute typecode
+const BGP_ATTR_AS4AGGREGATOR: u8 = 18;
+
+/// Converts BMP route monitoring updates into updates suitable for target BGP session
+#[derive(Debug, Clone)]
+pub struct BmpBgpBridge {
+    /// Target BGP session params
+    pub target: BgpSessionParams,
+    /// Next hop to set, if any
+    pub nexthop: Option<std::net::IpAddr>,
+    /// Path identifier for NLRI without one when target expects addpath.
+    /// It is the same for all such NLRI, so paths of different monitored peers
+    /// need separate bridges with distinct identifiers.
+    pub pathid: BgpPathId,
+}
+
+impl BmpBgpBridge {
+    pub fn new(target: BgpSessionParams) -> BmpBgpBridge {
+        BmpBgpBridge {
+            target,
+            nexthop: None,
+            pathid: 0,
+        }
+    }
+    /// Produces update for target session from BMP route monitoring message.
+    pub fn convert(&self, rm: &BmpMessageRouteMonitoring) -> Result<BgpUpdateMessage, BgpError> {
+        let mut upd = rm.update.clone();
+        self.remap_aspath(&mut upd)?;
+        self.remap_aggregator(&mut upd)?;
+        upd.updates = self.remap_addrs(std::mem::take(&mut upd.updates));
+        upd.withdraws = self.remap_addrs(std::mem::take(&mut upd.withdraws));
+        for attr in upd.attrs.iter_mut() {
+            match attr {
+                BgpAttrItem::MPUpdates(mp) => {
+                    mp.addrs = self.remap_addrs(std::mem::take(&mut mp.addrs));
+                }
+                BgpAttrItem::MPWithdraws(mp) => {
+                    mp.addrs = self.remap_addrs(std::mem::take(&mut mp.addrs));
+                }
+                _ => {}
+            }
+        }
+        if let Some(nh) = self.nexthop {
+            self.rewrite_nexthop(&mut upd, nh)?;
+        }
+        Ok(upd)
+    }
+    fn remap_addrs(&self, addrs: BgpAddrs) -> BgpAddrs {
+        let (afi, safi) = addrs.get_afi_safi();
+        if self.target.check_addpath_send(afi, safi) {
+            addrs.with_pathid(self.pathid)
+        } else {
+            addrs.without_pathid()
+        }
+    }
+    /// Merges AS4_PATH for 4-byte target, or builds AS4_PATH with AS_TRANS for 2-byte one.
+    fn remap_aspath(&self, upd: &mut BgpUpdateMessage) -> Result<(), BgpError> {
+        let mut as4params = self.target.clone();
+        as4params.has_as32bit = true;
+        let as4pos = upd.attrs.iter().position(
+            |a| matches!(a, BgpAttrItem::Unknown(u) if u.params.typecode == BGP_ATTR_AS4PATH),
+        );
+        if self.target.has_as32bit {
+            if let Some(pos) = as4pos {
+                if let BgpAttrItem::Unknown(u) = upd.attrs.remove(pos) {
+                    let as4 = BgpASpath::decode_from(&as4params, &u.value)?;
+                    for attr in upd.attrs.iter_mut() {
+                        if let BgpAttrItem::ASPath(p) = attr {
+                            if as4.value.len() <= p.value.len() {
+                                p.value.truncate(p.value.len() - as4.value.len());
+                                p.value.extend(as4.value.iter().cloned());
+                            }
+                        }
+                    }
+                }
+            }
+            return Ok(());
+        }
+        if as4pos.is_some() {
+            return Ok(());
+        }
+        let mut as4 = None;
+        for attr in upd.attrs.iter_mut() {
+            if let BgpAttrItem::ASPath(p) = attr {
+                if p.value.iter().any(|a| !a.value.is_16bit()) {
+                    as4 = Some(p.clone());
+                    for a in p.value.iter_mut() {
+                        if !a.value.is_16bit() {
+                            a.value = AS_TRANS;
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(p) = as4 {
+            let mut buf = vec![0_u8; 2 + p.value.len() * 4];
+            let len = p.encode_to(&as4params, &mut buf)?;
+            let mut u = BgpAttrUnknown::new(BGP_ATTR_AS4PATH, 0xc0);
+            u.value = buf[0..len].to_vec();
+            upd.attrs.push(BgpAttrItem::Unknown(u));
+        }
+        Ok(())
+    }
+    /// Restores AGGREGATOR from AS4_AGGREGATOR for 4-byte target,
+    /// or builds AS4_AGGREGATOR with AS_TRANS in AGGREGATOR for 2-byte one.
+    fn remap_aggregator(&self, upd: &mut BgpUpdateMessage) -> Result<(), BgpError> {
+        let as4pos = upd.attrs.iter().position(
+            |a| matches!(a, BgpAttrItem::Unknown(u) if u.params.typecode == BGP_ATTR_AS4AGGREGATOR),
+        );
+        if self.target.has_as32bit {
+            if let Some(pos) = as4pos {
+                if let BgpAttrItem::Unknown(u) = upd.attrs.remove(pos) {
+                    let as4 = BgpAggregatorAS::decode_from(&self.target, &u.value)?;
+                    for attr in upd.attrs.iter_mut() {
+                        if let BgpAttrItem::AggregatorAS(a) = attr {
+                            if a.asn == AS_TRANS {
+                                a.asn = as4.asn;
+                            }
+                        }
+                    }
+                }
+            }
+            return Ok(());
+        }
+        if as4pos.is_some() {
+            return Ok(());
+        }
+        let mut as4 = None;
+        for attr in upd.attrs.iter_mut() {
+            if let BgpAttrItem::AggregatorAS(a) = attr {
+                if !a.asn.is_16bit() {
+                    as4 = Some(a.clone());
+                    a.asn = AS_TRANS;
+                }
+            }
+        }
+        if let Some(a) = as4 {
+            let mut u = BgpAttrUnknown::new(BGP_ATTR_AS4AGGREGATOR, 0xc0);
+            u.value.extend_from_slice(&a.asn.0.to_be_bytes());
+            u.value.extend_from_slice(&a.addr.octets());
+            upd.attrs.push(BgpAttrItem::Unknown(u));
+        }
+        Ok(())
+    }
+    fn rewrite_nexthop(
+        &self,
+        upd: &mut BgpUpdateMessage,
+        nh: std::net::IpAddr,
+    ) -> Result<(), BgpError> {
+        for attr in upd.attrs.iter_mut() {
+            match attr {
+                BgpAttrItem::NextHop(n) => n.value = nh,
+                BgpAttrItem::MPUpdates(mp) => mp.set_nexthop(nh)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bmp::bmputl::BmpMessagePeerHeader;
+
+    #[test]
+    fn test_bridge_convert() {
+        let mut upd = BgpUpdateMessage::new();
+        upd.updates = BgpAddrs::IPV4UP(vec![WithPathId::new(
+            5,
+            BgpAddrV4::new(std::net::Ipv4Addr::new(10, 0, 0, 0), 8),
+        )]);
+        upd.attrs.push(BgpAttrItem::ASPath(BgpASpath {
+            value: vec![BgpAS::new(65001), BgpAS::new(4200000000)],
+        }));
+        upd.attrs.push(BgpAttrItem::NextHop(BgpNextHop {
+            value: "10.0.0.1".parse().unwrap(),
+        }));
+        let rm = BmpMessageRouteMonitoring {
+            peer: BmpMessagePeerHeader {
+                peertype: 0,
+                flags: 0,
+                peerdistinguisher: BgpRD::new(0, 0),
+                peeraddress: "10.0.0.1".parse().unwrap(),
+                asnum: 65001,
+                routerid: std::net::Ipv4Addr::new(10, 0, 0, 1),
+                timestamp: 0,
+            },
+            update: upd,
+            raw: None,
+            errors: Vec::new(),
+        };
+        let mut target = BgpSessionParams::new(
+            Asn(64512),
+            180,
+            BgpTransportMode::IPv4,
+            std::net::Ipv4Addr::new(1, 1, 1, 1),
+            vec![BgpCapability::SafiIPv4u],
+        );
+        target.has_as32bit = false;
+        let mut bridge = BmpBgpBridge::new(target);
+        bridge.nexthop = Some("192.168.0.1".parse().unwrap());
+        let out = bridge.convert(&rm).unwrap();
+        assert!(matches!(out.updates, BgpAddrs::IPV4U(ref v) if v.len() == 1));
+        assert_eq!(
+            out.get_attr_aspath().unwrap().value,
+            vec![BgpAS::new(65001), BgpAS::new(23456)]
+        );
+        assert_eq!(
+            out.get_attr_nexthop().unwrap().value,
+            "192.168.0.1".parse::<std::net::IpAddr>().unwrap()
+        );
+        // back to 4-byte session restores path from AS4_PATH
+        let mut rm2 = rm;
+        rm2.update = out;
+        bridge.target.has_as32bit = true;
+        let back = bridge.convert(&rm2).unwrap();
+        assert_eq!(
+            back.get_attr_aspath().unwrap().value,
+            vec![BgpAS::new(65001), BgpAS::new(4200000000)]
+        );
+        assert!(!back
+            .attrs
+            .iter()
+            .any(|a| matches!(a, BgpAttrItem::Unknown(_))));
+    }
+    #[test]
+    fn test_bridge_aggregator() {
+        let aggr = BgpAggregatorAS {
+            asn: Asn(4200000001),
+            addr: std::net::Ipv4Addr::new(10, 0, 0, 9),
+        };
+        let mut upd = BgpUpdateMessage::new();
+        upd.attrs.push(BgpAttrItem::AggregatorAS(aggr.clone()));
+        let mut rm = BmpMessageRouteMonitoring {
+            peer: BmpMessagePeerHeader {
+                peertype: 0,
+                flags: 0,
+                peerdistinguisher: BgpRD::new(0, 0),
+                peeraddress: "10.0.0.1".parse().unwrap(),
+                asnum: 65001,
+                routerid: std::net::Ipv4Addr::new(10, 0, 0, 1),
+                timestamp: 0,
+            },
+            update: upd,
+            raw: None,
+            errors: Vec::new(),
+        };
+        let mut target = BgpSessionParams::new(
+            Asn(64512),
+            180,
+            BgpTransportMode::IPv4,
+            std::net::Ipv4Addr::new(1, 1, 1, 1),
+            vec![BgpCapability::SafiIPv4u],
+        );
+        target.has_as32bit = false;
+        let mut bridge = BmpBgpBridge::new(target);
+        let out = bridge.convert(&rm).unwrap();
+        assert!(out.attrs.iter().any(
+            |a| matches!(a, BgpAttrItem::AggregatorAS(a) if a.asn == AS_TRANS && a.addr == aggr.addr)
+        ));
+        assert!(out.attrs.iter().any(|a| matches!(a,
+            BgpAttrItem::Unknown(u) if u.params.typecode == 18
+                && u.value == [250, 86, 234, 1, 10, 0, 0, 9])));
+        // 4-byte target restores aggregator AS
+        rm.update = out;
+        bridge.target.has_as32bit = true;
+        let back = bridge.convert(&rm).unwrap();
+        assert_eq!(back.attrs, vec![BgpAttrItem::AggregatorAS(aggr)]);
+    }
+}
@@ -9,9 +9,11 @@
 //! BGP Monitoring Protocol (BMP) processing - <https://tools.ietf.org/html/rfc7854>
 
 mod bmputl;
+mod bridge;
 mod msginit;
 mod msgpeer;
 mod msgrmon;
+mod msgstat;
 mod msgterm;
 pub mod prelude;
 
@@ -20,6 +22,7 @@ use bmputl::*;
 use msginit::BmpMessageInitiation;
 use msgpeer::{BmpMessagePeerDown, BmpMessagePeerUp};
 use msgrmon::BmpMessageRouteMonitoring;
+use msgstat::BmpMessageStatistics;
 use msgterm::BmpMessageTermination;
 use std::collections::BTreeMap;
 
@@ -42,12 +45,35 @@ impl From<&BmpMessagePeerHeader> for BgpSessionKey {
         }
     }
 }
+/// Handling of Peer Up notification for already known session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BmpDuplicatePeerUp {
+    /// replace known session silently
+    #[default]
+    Replace,
+    /// keep known session and log warning
+    KeepAndWarn,
+    /// replace known session and return both as PeerUpDuplicate message
+    Report,
+}
 ///BMP Session
 #[derive(Default)]
 pub struct BMPSession {
     pub sessions: BTreeMap<BgpSessionKey, BmpMessagePeerUp>,
+    /// retain raw BGP update bytes in route monitoring messages
+    pub keep_raw: bool,
+    /// duplicate Peer Up handling policy
+    pub duplicate_peerup: BmpDuplicatePeerUp,
 }
 impl BMPSession {
+    /// Forgets session, returns its Peer Up notification if it was known.
+    pub fn remove_peer(&mut self, key: &BgpSessionKey) -> Option<BmpMessagePeerUp> {
+        self.sessions.remove(key)
+    }
+    /// Forgets all sessions, for example when monitored router restarts.
+    pub fn clear(&mut self) {
+        self.sessions.clear();
+    }
     pub fn decode_from(&mut self, buf: &[u8]) -> Result<BmpMessage, BgpError> {
         let msgtype = buf[0];
         match msgtype {
@@ -55,7 +81,9 @@ impl BMPSession {
                 let rm = self.decode_rm(&buf[1..])?;
                 Ok(BmpMessage::RouteMonitoring(rm))
             }
-            1 => Ok(BmpMessage::StatisticsReport),
+            1 => Ok(BmpMessage::StatisticsReport(
+                BmpMessageStatistics::decode_from(&buf[1..])?.0,
+            )),
             2 => {
                 let peerdown = BmpMessagePeerDown::decode_from(&buf[1..])?.0;
                 self.sessions.remove(&BgpSessionKey::from(&peerdown.peer));
@@ -63,9 +91,31 @@ impl BMPSession {
             }
             3 => {
                 let peerup = BmpMessagePeerUp::decode_from(&buf[1..])?.0;
-                self.sessions
-                    .insert(BgpSessionKey::from(&peerup.peer), peerup.clone());
-                Ok(BmpMessage::PeerUpNotification(peerup))
+                let key = BgpSessionKey::from(&peerup.peer);
+                match self.sessions.get(&key) {
+                    None => {
+                        self.sessions.insert(key, peerup.clone());
+                        Ok(BmpMessage::PeerUpNotification(peerup))
+                    }
+                    Some(old) => match self.duplicate_peerup {
+                        BmpDuplicatePeerUp::Replace => {
+                            self.sessions.insert(key, peerup.clone());
+                            Ok(BmpMessage::PeerUpNotification(peerup))
+                        }
+                        BmpDuplicatePeerUp::KeepAndWarn => {
+                            log::warn!(
+                                "Duplicate BMP Peer Up for {:?}, keeping known session",
+                                key
+                            );
+                            Ok(BmpMessage::PeerUpNotification(peerup))
+                        }
+                        BmpDuplicatePeerUp::Report => {
+                            let old = old.clone();
+                            self.sessions.insert(key, peerup.clone());
+                            Ok(BmpMessage::PeerUpDuplicate(old, peerup))
+                        }
+                    },
+                }
             }
             4 => Ok(BmpMessage::Initiation(
                 BmpMessageInitiation::decode_from(&buf[1..])?.0,
@@ -82,7 +132,6 @@ impl BMPSession {
             return Err(BgpError::InsufficientBufferSize);
         }
         let pm = BmpMessagePeerHeader::decode_from(buf)?;
-        let mut pos = pm.1;
         let sesskey = BgpSessionKey::from(&pm.0);
         let sesspars: BgpSessionParams = match self.sessions.get(&sesskey) {
             None => (&pm.0).into(),
@@ -94,32 +143,21 @@ impl BMPSession {
                 }
             }
         };
-        let msgt = sesspars.decode_message_head(&buf[pos..])?;
-        pos += 19;
-        if msgt.0 != BgpMessageType::Update {
-            return Err(BgpError::static_str(
-                "Invalid BGP message type for BmpMessageRouteMonitoring",
-            ));
-        }
-        let mut upd = BgpUpdateMessage::new();
-        upd.decode_from(&sesspars, &buf[pos..pos + msgt.1])?;
-        //pos += msgt.1;
-        Ok(BmpMessageRouteMonitoring {
-            peer: pm.0,
-            update: upd,
-        })
+        Ok(BmpMessageRouteMonitoring::decode_pdu(pm.0, &sesspars, &buf[pm.1..], self.keep_raw)?.0)
     }
 }
 /// BMP message
 #[derive(Debug)]
 pub enum BmpMessage {
     RouteMonitoring(BmpMessageRouteMonitoring), //0
-    StatisticsReport,                           //1
+    StatisticsReport(BmpMessageStatistics),     //1
     PeerDownNotification(BmpMessagePeerDown),   //2
     PeerUpNotification(BmpMessagePeerUp),       //3
     Initiation(BmpMessageInitiation),           //4
     Termination(BmpMessageTermination),         //5
     RouteMirroring,                             //6
+    /// Peer Up for already known session: previous and new notifications
+    PeerUpDuplicate(BmpMessagePeerUp, BmpMessagePeerUp),
 }
 
 /// BMP message header
@@ -164,7 +202,9 @@ impl BmpMessage {
             0 => Ok(BmpMessage::RouteMonitoring(
                 BmpMessageRouteMonitoring::decode_from(&buf[1..])?.0,
             )),
-            1 => Ok(BmpMessage::StatisticsReport),
+            1 => Ok(BmpMessage::StatisticsReport(
+                BmpMessageStatistics::decode_from(&buf[1..])?.0,
+            )),
             2 => Ok(BmpMessage::PeerDownNotification(
                 BmpMessagePeerDown::decode_from(&buf[1..])?.0,
             )),
@@ -192,15 +232,17 @@ impl BmpMessage {
                 curpos += 1;
                 curpos += rm.encode_to(&mut buf[1..])?;
             }
-            BmpMessage::StatisticsReport => {
-                unimplemented!()
+            BmpMessage::StatisticsReport(stats) => {
+                buf[0] = 1;
+                curpos += 1;
+                curpos += stats.encode_to(&mut buf[1..])?;
             }
             BmpMessage::PeerDownNotification(peerdown) => {
                 buf[0] = 2;
                 curpos += 1;
                 curpos += peerdown.encode_to(&mut buf[1..])?;
             }
-            BmpMessage::PeerUpNotification(peerup) => {
+            BmpMessage::PeerUpNotification(peerup) | BmpMessage::PeerUpDuplicate(_, peerup) => {
                 buf[0] = 3;
                 curpos += 1;
                 curpos += peerup.encode_to(&mut buf[1..])?;
@@ -222,3 +264,70 @@ impl BmpMessage {
         Ok(curpos)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::open::BgpOpenMessage;
+
+    fn mkpeerup(routerid: std::net::Ipv4Addr) -> Vec<u8> {
+        let mut open = BgpOpenMessage::new();
+        open.as_num = Asn(65001);
+        open.hold_time = 180;
+        open.router_id = routerid;
+        let peerup = BmpMessagePeerUp {
+            peer: BmpMessagePeerHeader {
+                peertype: 0,
+                flags: 0,
+                peerdistinguisher: BgpRD::new(0, 0),
+                peeraddress: "10.0.0.1".parse().unwrap(),
+                asnum: 65001,
+                routerid,
+                timestamp: 0,
+            },
+            localaddress: "10.0.0.2".parse().unwrap(),
+            localport: 179,
+            remoteport: 50000,
+            msg1: open.clone(),
+            msg2: open,
+        };
+        let mut buf = vec![0_u8; 4096];
+        let len = BmpMessage::PeerUpNotification(peerup)
+            .encode_to(&mut buf)
+            .unwrap();
+        buf.truncate(len);
+        buf
+    }
+
+    #[test]
+    fn test_duplicate_peerup() {
+        let first = mkpeerup(std::net::Ipv4Addr::new(10, 0, 0, 1));
+        let second = mkpeerup(std::net::Ipv4Addr::new(10, 0, 0, 9));
+        let key = BgpSessionKey::new(BgpRD::new(0, 0), "10.0.0.1".parse().unwrap());
+        let routerid = |s: &BMPSession| s.sessions[&key].peer.routerid;
+
+        let mut sess = BMPSession {
+            duplicate_peerup: BmpDuplicatePeerUp::KeepAndWarn,
+            ..Default::default()
+        };
+        sess.decode_from(&first).unwrap();
+        sess.decode_from(&second).unwrap();
+        assert_eq!(routerid(&sess), std::net::Ipv4Addr::new(10, 0, 0, 1));
+
+        sess.duplicate_peerup = BmpDuplicatePeerUp::Report;
+        match sess.decode_from(&second).unwrap() {
+            BmpMessage::PeerUpDuplicate(old, new) => {
+                assert_eq!(old.peer.routerid, std::net::Ipv4Addr::new(10, 0, 0, 1));
+                assert_eq!(new.peer.routerid, std::net::Ipv4Addr::new(10, 0, 0, 9));
+            }
+            m => panic!("unexpected {:?}", m),
+        }
+        assert_eq!(routerid(&sess), std::net::Ipv4Addr::new(10, 0, 0, 9));
+
+        assert!(sess.remove_peer(&key).is_some());
+        assert!(sess.remove_peer(&key).is_none());
+        sess.decode_from(&first).unwrap();
+        sess.clear();
+        assert!(sess.sessions.is_empty());
+    }
+}
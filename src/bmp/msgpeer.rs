@@ -58,18 +58,20 @@ impl BmpMessagePeerUp {
         let mut pos: usize = pm.1 + 20;
         let msgt = sesspars.decode_message_head(&buf[pos..])?;
         pos += 19;
-        if msgt.0 != BgpMessageType::Open {
+        if msgt.msgtype != BgpMessageType::Open {
             return Err(BgpError::static_str("Invalid BGP message type #1"));
         }
-        ret.msg1.decode_from(&sesspars, &buf[pos..pos + msgt.1])?;
-        pos += msgt.1;
+        ret.msg1
+            .decode_from(&sesspars, &buf[pos..pos + msgt.body_len])?;
+        pos += msgt.body_len;
         let msgt = sesspars.decode_message_head(&buf[pos..])?;
         pos += 19;
-        if msgt.0 != BgpMessageType::Open {
+        if msgt.msgtype != BgpMessageType::Open {
             return Err(BgpError::static_str("Invalid BGP message type #2"));
         }
-        ret.msg2.decode_from(&sesspars, &buf[pos..pos + msgt.1])?;
-        pos += msgt.1;
+        ret.msg2
+            .decode_from(&sesspars, &buf[pos..pos + msgt.body_len])?;
+        pos += msgt.body_len;
         Ok((ret, pos))
     }
     pub fn encode_to(&self, buf: &mut [u8]) -> Result<usize, BgpError> {
@@ -116,12 +118,12 @@ impl BmpMessagePeerDown {
                 let sesspars = BgpSessionParams::from(&pm.0);
                 let msgt = sesspars.decode_message_head(&buf[pos..])?;
                 pos += 19;
-                if msgt.0 != BgpMessageType::Notification {
+                if msgt.msgtype != BgpMessageType::Notification {
                     return Err(BgpError::static_str("Invalid BGP message type"));
                 }
                 let mut msg = BgpNotificationMessage::new();
-                msg.decode_from(&sesspars, &buf[pos..pos + msgt.1])?;
-                pos += msgt.1;
+                msg.decode_from(&sesspars, &buf[pos..pos + msgt.body_len])?;
+                pos += msgt.body_len;
                 BmpMessagePeerDownReason::AdministrativelyClosed(msg)
             }
             2 => {
@@ -136,12 +138,12 @@ impl BmpMessagePeerDown {
                 let sesspars = BgpSessionParams::from(&pm.0);
                 let msgt = sesspars.decode_message_head(&buf[pos..])?;
                 pos += 19;
-                if msgt.0 != BgpMessageType::Notification {
+                if msgt.msgtype != BgpMessageType::Notification {
                     return Err(BgpError::static_str("Invalid BGP message type"));
                 }
                 let mut msg = BgpNotificationMessage::new();
-                msg.decode_from(&sesspars, &buf[pos..pos + msgt.1])?;
-                pos += msgt.1;
+                msg.decode_from(&sesspars, &buf[pos..pos + msgt.body_len])?;
+                pos += msgt.body_len;
                 BmpMessagePeerDownReason::RemoteNotification(msg)
             }
             4 => BmpMessagePeerDownReason::Remote,
@@ -9,7 +9,7 @@
 //! BMP route monitoring message
 
 use crate::bmp::bmputl::*;
-use crate::message::update::BgpUpdateMessage;
+use crate::message::update::{BgpPartialError, BgpUpdateMessage};
 use crate::message::*;
 use crate::{BgpError, BgpMessage, BgpSessionParams};
 
@@ -20,34 +20,82 @@ pub struct BmpMessageRouteMonitoring {
     pub peer: BmpMessagePeerHeader,
     /// incapsulated BGP update message
     pub update: BgpUpdateMessage,
+    /// raw incapsulated BGP message with head, if retained
+    pub raw: Option<Vec<u8>>,
+    /// decode errors of retained message, update keeps successfully decoded parts
+    pub errors: Vec<BgpPartialError>,
 }
 
 impl BmpMessageRouteMonitoring {
     pub fn decode_from(buf: &[u8]) -> Result<(BmpMessageRouteMonitoring, usize), BgpError> {
+        Self::decode_opt(buf, false)
+    }
+    /// Decodes message retaining raw BGP update bytes.
+    pub fn decode_keep_raw(buf: &[u8]) -> Result<(BmpMessageRouteMonitoring, usize), BgpError> {
+        Self::decode_opt(buf, true)
+    }
+    fn decode_opt(
+        buf: &[u8],
+        keep_raw: bool,
+    ) -> Result<(BmpMessageRouteMonitoring, usize), BgpError> {
         if buf.len() < 62 {
             return Err(BgpError::InsufficientBufferSize);
         }
         let pm = BmpMessagePeerHeader::decode_from(buf)?;
-        let mut pos = pm.1;
-        let sesspars: &BgpSessionParams = &(&pm.0).into();
-        let msgt = sesspars.decode_message_head(&buf[pos..])?;
-        pos += 19;
-        if msgt.0 != BgpMessageType::Update {
+        let sesspars: BgpSessionParams = (&pm.0).into();
+        let (rm, len) = Self::decode_pdu(pm.0, &sesspars, &buf[pm.1..], keep_raw)?;
+        Ok((rm, pm.1 + len))
+    }
+    /// Decodes incapsulated BGP update with head.
+    /// When raw message is retained, it is kept with partially decoded update on decode errors,
+    /// so it can be decoded again with corrected session parameters.
+    pub(crate) fn decode_pdu(
+        peer: BmpMessagePeerHeader,
+        sesspars: &BgpSessionParams,
+        buf: &[u8],
+        keep_raw: bool,
+    ) -> Result<(BmpMessageRouteMonitoring, usize), BgpError> {
+        // monitored session may use extended messages, BMP message length bounds PDU anyway
+        let msgt = sesspars.decode_message_head_max(buf, BGP_MAX_EXT_MESSAGE_LEN)?;
+        if msgt.msgtype != BgpMessageType::Update {
             return Err(BgpError::static_str(
                 "Invalid BGP message type for BmpMessageRouteMonitoring",
             ));
         }
-        let mut upd = BgpUpdateMessage::new();
-        upd.decode_from(sesspars, &buf[pos..pos + msgt.1])?;
-        pos += msgt.1;
+        let pdu = crate::slice(buf, 0, msgt.total_len)?;
+        let body = &pdu[19..];
+        let (update, raw, errors) = if keep_raw {
+            let part = BgpUpdateMessage::decode_partial(sesspars, body);
+            (part.msg, Some(pdu.to_vec()), part.errors)
+        } else {
+            let mut upd = BgpUpdateMessage::new();
+            upd.decode_from(sesspars, body)?;
+            (upd, None, Vec::new())
+        };
         Ok((
             BmpMessageRouteMonitoring {
-                peer: pm.0,
-                update: upd,
+                peer,
+                update,
+                raw,
+                errors,
             },
-            pos,
+            msgt.total_len,
         ))
     }
+    /// Decodes retained raw BGP update again with given session parameters.
+    pub fn redecode(&self, params: &BgpSessionParams) -> Result<BgpUpdateMessage, BgpError> {
+        let raw = match &self.raw {
+            None => return Err(BgpError::static_str("Raw BGP update was not retained")),
+            Some(r) => r,
+        };
+        let msgt = params.decode_message_head(raw)?;
+        if msgt.msgtype != BgpMessageType::Update || raw.len() < msgt.total_len {
+            return Err(BgpError::static_str("Invalid retained BGP update"));
+        }
+        let mut upd = BgpUpdateMessage::new();
+        upd.decode_from(params, &raw[19..msgt.total_len])?;
+        Ok(upd)
+    }
     pub fn encode_to(&self, buf: &mut [u8]) -> Result<usize, BgpError> {
         let mut curpos: usize = 0;
         if buf.len() < 62 {
@@ -64,3 +112,112 @@ impl BmpMessageRouteMonitoring {
         Ok(curpos)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_route_monitoring_raw() {
+        let mut upd = BgpUpdateMessage::new();
+        upd.updates = BgpAddrs::IPV4U(vec![BgpAddrV4::new(
+            std::net::Ipv4Addr::new(10, 0, 0, 0),
+            8,
+        )]);
+        upd.attrs
+            .push(BgpAttrItem::NextHop("10.0.0.1".parse().unwrap()));
+        let rm = BmpMessageRouteMonitoring {
+            peer: BmpMessagePeerHeader {
+                peertype: 0,
+                flags: 0,
+                peerdistinguisher: BgpRD::new(0, 0),
+                peeraddress: "10.0.0.1".parse().unwrap(),
+                asnum: 65001,
+                routerid: std::net::Ipv4Addr::new(10, 0, 0, 1),
+                timestamp: 0,
+            },
+            update: upd,
+            raw: None,
+            errors: Vec::new(),
+        };
+        let mut buf = vec![0_u8; 4096];
+        let len = rm.encode_to(&mut buf).unwrap();
+        let (plain, _) = BmpMessageRouteMonitoring::decode_from(&buf[..len]).unwrap();
+        assert!(plain.raw.is_none());
+        let (dec, dlen) = BmpMessageRouteMonitoring::decode_keep_raw(&buf[..len]).unwrap();
+        assert_eq!(dlen, len);
+        assert_eq!(
+            dec.raw.as_ref().unwrap()[..],
+            buf[len - dec.raw.as_ref().unwrap().len()..len]
+        );
+        let sesspars: BgpSessionParams = (&dec.peer).into();
+        let again = dec.redecode(&sesspars).unwrap();
+        assert_eq!(again.updates, dec.update.updates);
+    }
+    #[test]
+    fn test_route_monitoring_keep_raw_on_error() {
+        let peer = BmpMessagePeerHeader {
+            peertype: 0,
+            flags: 0,
+            peerdistinguisher: BgpRD::new(0, 0),
+            peeraddress: "10.0.0.1".parse().unwrap(),
+            asnum: 65001,
+            routerid: std::net::Ipv4Addr::new(10, 0, 0, 1),
+            timestamp: 0,
+        };
+        // update sent on add-path session, BMP peer header does not tell it
+        let mut addpath: BgpSessionParams = (&peer).into();
+        addpath.caps = vec![BgpCapability::CapAddPath(vec![
+            BgpCapAddPath::new_from_cap(BgpCapability::SafiIPv4u, true, true).unwrap(),
+        ])];
+        addpath.check_caps();
+        let mut upd = BgpUpdateMessage::new();
+        upd.updates = BgpAddrs::IPV4UP(vec![WithPathId::new(
+            0x2100_0000,
+            BgpAddrV4::new(std::net::Ipv4Addr::new(10, 0, 0, 0), 8),
+        )]);
+        let mut buf = vec![0_u8; 4096];
+        let mut len = peer.encode_to(&mut buf).unwrap();
+        let msglen = upd.encode_to(&addpath, &mut buf[len + 19..]).unwrap();
+        len += addpath
+            .prepare_message_buf(&mut buf[len..], BgpMessageType::Update, msglen)
+            .unwrap();
+        assert!(BmpMessageRouteMonitoring::decode_from(&buf[..len]).is_err());
+        let (dec, dlen) = BmpMessageRouteMonitoring::decode_keep_raw(&buf[..len]).unwrap();
+        assert_eq!(dlen, len);
+        assert!(!dec.errors.is_empty());
+        let again = dec.redecode(&addpath).unwrap();
+        assert_eq!(again.updates, upd.updates);
+    }
+    #[test]
+    fn test_route_monitoring_extended() {
+        let mut upd = BgpUpdateMessage::new();
+        upd.updates = BgpAddrs::IPV4U(
+            (0..1200_u32)
+                .map(|i| BgpAddrV4::new(std::net::Ipv4Addr::from(0x0a00_0000 + (i << 8)), 24))
+                .collect(),
+        );
+        upd.attrs
+            .push(BgpAttrItem::NextHop("10.0.0.1".parse().unwrap()));
+        let rm = BmpMessageRouteMonitoring {
+            peer: BmpMessagePeerHeader {
+                peertype: 0,
+                flags: 0,
+                peerdistinguisher: BgpRD::new(0, 0),
+                peeraddress: "10.0.0.1".parse().unwrap(),
+                asnum: 65001,
+                routerid: std::net::Ipv4Addr::new(10, 0, 0, 1),
+                timestamp: 0,
+            },
+            update: upd,
+            raw: None,
+            errors: Vec::new(),
+        };
+        let mut buf = vec![0_u8; 8192];
+        let len = rm.encode_to(&mut buf).unwrap();
+        assert!(len > BGP_MAX_MESSAGE_LEN);
+        let (dec, _) = BmpMessageRouteMonitoring::decode_from(&buf[..len]).unwrap();
+        assert_eq!(dec.update.updates.len(), 1200);
+    }
+}
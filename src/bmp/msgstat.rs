@@ -0,0 +1,286 @@
+// Copyright 2021 Vladimir Melnikov.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! BMP statistics report message
+
+use crate::bmp::bmputl::*;
+use crate::bmp::BgpSessionKey;
+use crate::*;
+use std::collections::BTreeMap;
+
+/// BMP statistics value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BmpStatValue {
+    /// 32-bit counter
+    Counter(u32),
+    /// 64-bit gauge
+    Gauge(u64),
+    /// 64-bit gauge per afi/safi
+    AfiSafiGauge(u16, u8, u64),
+}
+
+impl BmpStatValue {
+    /// Numeric value
+    pub fn value(&self) -> u64 {
+        match self {
+            BmpStatValue::Counter(v) => *v as u64,
+            BmpStatValue::Gauge(v) => *v,
+            BmpStatValue::AfiSafiGauge(_, _, v) => *v,
+        }
+    }
+    /// afi/safi for per-family gauges
+    pub fn afi_safi(&self) -> Option<(u16, u8)> {
+        match self {
+            BmpStatValue::AfiSafiGauge(afi, safi, _) => Some((*afi, *safi)),
+            _ => None,
+        }
+    }
+}
+
+/// BMP statistics item
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BmpStatCounter {
+    /// statistics type code
+    pub stat_type: u16,
+    /// value
+    pub value: BmpStatValue,
+}
+
+/// BMP statistics report message
+#[derive(Debug, Clone)]
+pub struct BmpMessageStatistics {
+    /// peer header
+    pub peer: BmpMessagePeerHeader,
+    /// statistics counters
+    pub counters: Vec<BmpStatCounter>,
+}
+
+impl BmpMessageStatistics {
+    pub fn decode_from(buf: &[u8]) -> Result<(BmpMessageStatistics, usize), BgpError> {
+        let pm = BmpMessagePeerHeader::decode_from(buf)?;
+        let mut pos = pm.1;
+        if buf.len() < pos + 4 {
+            return Err(BgpError::InsufficientBufferSize);
+        }
+        let count = getn_u32(&buf[pos..]);
+        pos += 4;
+        let mut counters = Vec::new();
+        for _ in 0..count {
+            if buf.len() - pos < 4 {
+                return Err(BgpError::InsufficientBufferSize);
+            }
+            let stat_type = getn_u16(&buf[pos..]);
+            let statlen = getn_u16(&buf[pos + 2..]) as usize;
+            pos += 4;
+            if buf.len() - pos < statlen {
+                return Err(BgpError::InsufficientBufferSize);
+            }
+            // items of unknown size are skipped
+            let value = match statlen {
+                4 => Some(BmpStatValue::Counter(getn_u32(&buf[pos..]))),
+                8 => Some(BmpStatValue::Gauge(getn_u64(&buf[pos..]))),
+                11 => Some(BmpStatValue::AfiSafiGauge(
+                    getn_u16(&buf[pos..]),
+                    buf[pos + 2],
+                    getn_u64(&buf[pos + 3..]),
+                )),
+                _ => None,
+            };
+            pos += statlen;
+            if let Some(value) = value {
+                counters.push(BmpStatCounter { stat_type, value });
+            }
+        }
+        Ok((
+            BmpMessageStatistics {
+                peer: pm.0,
+                counters,
+            },
+            pos,
+        ))
+    }
+    pub fn encode_to(&self, buf: &mut [u8]) -> Result<usize, BgpError> {
+        let mut curpos = self.peer.encode_to(buf)?;
+        if buf.len() - curpos < 4 {
+            return Err(BgpError::InsufficientBufferSize);
+        }
+        setn_u32(self.counters.len() as u32, &mut buf[curpos..]);
+        curpos += 4;
+        for c in self.counters.iter() {
+            let statlen = match c.value {
+                BmpStatValue::Counter(_) => 4,
+                BmpStatValue::Gauge(_) => 8,
+                BmpStatValue::AfiSafiGauge(_, _, _) => 11,
+            };
+            if buf.len() - curpos < 4 + statlen {
+                return Err(BgpError::InsufficientBufferSize);
+            }
+            setn_u16(c.stat_type, &mut buf[curpos..]);
+            setn_u16(statlen as u16, &mut buf[curpos + 2..]);
+            curpos += 4;
+            match c.value {
+                BmpStatValue::Counter(v) => setn_u32(v, &mut buf[curpos..]),
+                BmpStatValue::Gauge(v) => setn_u64(v, &mut buf[curpos..]),
+                BmpStatValue::AfiSafiGauge(afi, safi, v) => {
+                    setn_u16(afi, &mut buf[curpos..]);
+                    buf[curpos + 2] = safi;
+                    setn_u64(v, &mut buf[curpos + 3..]);
+                }
+            }
+            curpos += statlen;
+        }
+        Ok(curpos)
+    }
+    /// Report timestamp in seconds
+    pub fn timestamp_secs(&self) -> f64 {
+        (self.peer.timestamp >> 32) as f64 + (self.peer.timestamp & 0xffffffff) as f64 / 1e6
+    }
+}
+
+/// Change of single statistics item between two reports
+#[derive(Debug, Clone, PartialEq)]
+pub struct BmpStatDelta {
+    /// statistics type code
+    pub stat_type: u16,
+    /// afi/safi for per-family gauges
+    pub afi_safi: Option<(u16, u8)>,
+    /// current value
+    pub value: u64,
+    /// difference from previous report
+    pub delta: i64,
+    /// difference per second, if reports timestamps differ
+    pub rate: Option<f64>,
+}
+
+/// Change of peer statistics between two reports
+#[derive(Debug, Clone, PartialEq)]
+pub struct BmpStatsDelta {
+    /// seconds between reports
+    pub interval: f64,
+    /// per-item changes, items absent in previous report are compared with zero
+    pub counters: Vec<BmpStatDelta>,
+}
+
+/// Keeps previous statistics report per peer and computes deltas
+#[derive(Debug, Default)]
+pub struct BmpStatsTracker {
+    prev: BTreeMap<BgpSessionKey, BmpMessageStatistics>,
+}
+
+impl BmpStatsTracker {
+    pub fn new() -> BmpStatsTracker {
+        BmpStatsTracker {
+            prev: BTreeMap::new(),
+        }
+    }
+    /// Stores report, returns delta from the previous one for this peer.
+    pub fn update(&mut self, stats: BmpMessageStatistics) -> Option<BmpStatsDelta> {
+        let key = BgpSessionKey::from(&stats.peer);
+        let ret = self.prev.get(&key).map(|prev| {
+            let interval = stats.timestamp_secs() - prev.timestamp_secs();
+            let counters = stats
+                .counters
+                .iter()
+                .map(|c| {
+                    let old = prev
+                        .counters
+                        .iter()
+                        .find(|p| {
+                            p.stat_type == c.stat_type && p.value.afi_safi() == c.value.afi_safi()
+                        })
+                        .map(|p| p.value.value())
+                        .unwrap_or(0);
+                    let mut delta = c.value.value() as i64 - old as i64;
+                    if delta < 0 && matches!(c.value, BmpStatValue::Counter(_)) {
+                        // 32-bit counter wrapped
+                        delta += 1 << 32;
+                    }
+                    BmpStatDelta {
+                        stat_type: c.stat_type,
+                        afi_safi: c.value.afi_safi(),
+                        value: c.value.value(),
+                        delta,
+                        rate: if interval > 0.0 {
+                            Some(delta as f64 / interval)
+                        } else {
+                            None
+                        },
+                    }
+                })
+                .collect();
+            BmpStatsDelta { interval, counters }
+        });
+        self.prev.insert(key, stats);
+        ret
+    }
+    /// Forgets peer previous report.
+    pub fn remove_peer(&mut self, peer: &BmpMessagePeerHeader) {
+        self.prev.remove(&BgpSessionKey::from(peer));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::afi::BgpRD;
+
+    fn mkstats(ts: u64, updates: u32, routes: u64) -> BmpMessageStatistics {
+        BmpMessageStatistics {
+            peer: BmpMessagePeerHeader {
+                peertype: 0,
+                flags: 0,
+                peerdistinguisher: BgpRD::new(0, 0),
+                peeraddress: "10.0.0.1".parse().unwrap(),
+                asnum: 65001,
+                routerid: std::net::Ipv4Addr::new(10, 0, 0, 1),
+                timestamp: ts << 32,
+            },
+            counters: vec![
+                BmpStatCounter {
+                    stat_type: 4,
+                    value: BmpStatValue::Counter(updates),
+                },
+                BmpStatCounter {
+                    stat_type: 9,
+                    value: BmpStatValue::AfiSafiGauge(1, 1, routes),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_stats_delta() {
+        let mut buf = vec![0_u8; 4096];
+        let s1 = mkstats(100, 10, 1000);
+        let len = s1.encode_to(&mut buf).unwrap();
+        let (d1, dlen) = BmpMessageStatistics::decode_from(&buf[..len]).unwrap();
+        assert_eq!(dlen, len);
+        assert_eq!(d1.counters, s1.counters);
+        let mut tracker = BmpStatsTracker::new();
+        assert!(tracker.update(d1).is_none());
+        let delta = tracker.update(mkstats(110, 30, 900)).unwrap();
+        assert_eq!(delta.interval, 10.0);
+        assert_eq!(delta.counters[0].delta, 20);
+        assert_eq!(delta.counters[0].rate, Some(2.0));
+        assert_eq!(delta.counters[1].afi_safi, Some((1, 1)));
+        assert_eq!(delta.counters[1].delta, -100);
+    }
+    #[test]
+    fn test_stats_skip_unknown() {
+        let mut buf = vec![0_u8; 4096];
+        let s1 = mkstats(100, 10, 1000);
+        let len = s1.encode_to(&mut buf).unwrap();
+        let cntpos = len - 27;
+        setn_u32(3, &mut buf[cntpos..]);
+        buf.copy_within(cntpos + 4..len, cntpos + 14);
+        buf[cntpos + 4..cntpos + 14].copy_from_slice(&[0, 100, 0, 6, 1, 2, 3, 4, 5, 6]);
+        let (d1, dlen) = BmpMessageStatistics::decode_from(&buf[..len + 10]).unwrap();
+        assert_eq!(dlen, len + 10);
+        assert_eq!(d1.counters, s1.counters);
+    }
+}
@@ -17,8 +17,10 @@
 //! ```
 
 pub use crate::bmp::bmputl::*;
+pub use crate::bmp::bridge::*;
 pub use crate::bmp::msginit::*;
 pub use crate::bmp::msgpeer::*;
 pub use crate::bmp::msgrmon::*;
+pub use crate::bmp::msgstat::*;
 pub use crate::bmp::msgterm::*;
 pub use crate::bmp::*;
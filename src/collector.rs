@@ -0,0 +1,206 @@
+// Copyright 2021 Vladimir Melnikov.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! This module contains multi-peer BGP collector registry
+
+use crate::message::notification::BgpNotificationMessage;
+use crate::message::*;
+use crate::*;
+use std::collections::BTreeMap;
+
+/// Collector per-peer state
+#[derive(Debug, Clone)]
+pub struct BgpCollectorPeer {
+    /// Configured local session parameters.
+    pub local: BgpSessionParams,
+    /// Session parameters, negotiated from local ones after peer OPEN received.
+    pub params: BgpSessionParams,
+    /// Peer OPEN message.
+    pub remote_open: Option<BgpOpenMessage>,
+    /// Last NOTIFICATION received from peer.
+    pub last_notification: Option<BgpNotificationMessage>,
+    /// Updates received.
+    pub updates: u64,
+    /// Keepalives received.
+    pub keepalives: u64,
+}
+
+impl BgpCollectorPeer {
+    pub fn new(params: BgpSessionParams) -> BgpCollectorPeer {
+        BgpCollectorPeer {
+            local: params.clone(),
+            params,
+            remote_open: None,
+            last_notification: None,
+            updates: 0,
+            keepalives: 0,
+        }
+    }
+    /// Returns true if peer OPEN was received and session was not closed by NOTIFICATION.
+    pub fn is_established(&self) -> bool {
+        self.remote_open.is_some() && self.last_notification.is_none()
+    }
+    /// Updates peer state with decoded message.
+    pub fn process(&mut self, msg: &BgpAnyMessage) {
+        match msg {
+            BgpAnyMessage::Open(open) => {
+                let mut params = self.local.clone();
+                params.match_caps(&open.caps);
+                if open.hold_time < params.hold_time {
+                    params.hold_time = open.hold_time;
+                }
+                self.params = params;
+                self.remote_open = Some(open.clone());
+                self.last_notification = None;
+            }
+            BgpAnyMessage::Update(_) => self.updates += 1,
+            BgpAnyMessage::Notification(n) => self.last_notification = Some(n.clone()),
+            BgpAnyMessage::Keepalive(_) => self.keepalives += 1,
+            BgpAnyMessage::Raw(_) => {}
+        }
+    }
+}
+
+/// Multi-peer BGP collector, sessions keyed by peer address
+#[derive(Debug, Default)]
+pub struct BgpCollector {
+    pub peers: BTreeMap<std::net::IpAddr, BgpCollectorPeer>,
+}
+
+impl BgpCollector {
+    pub fn new() -> BgpCollector {
+        BgpCollector {
+            peers: BTreeMap::new(),
+        }
+    }
+    /// Registers peer with local session params, replacing existing state.
+    pub fn add_peer(&mut self, addr: std::net::IpAddr, params: BgpSessionParams) {
+        self.peers.insert(addr, BgpCollectorPeer::new(params));
+    }
+    /// Removes peer.
+    pub fn remove_peer(&mut self, addr: &std::net::IpAddr) -> Option<BgpCollectorPeer> {
+        self.peers.remove(addr)
+    }
+    /// Returns peer state.
+    pub fn get(&self, addr: &std::net::IpAddr) -> Option<&BgpCollectorPeer> {
+        self.peers.get(addr)
+    }
+    /// Returns mutable peer state.
+    pub fn get_mut(&mut self, addr: &std::net::IpAddr) -> Option<&mut BgpCollectorPeer> {
+        self.peers.get_mut(addr)
+    }
+    /// Returns peer session params.
+    pub fn params(&self, addr: &std::net::IpAddr) -> Option<&BgpSessionParams> {
+        self.peers.get(addr).map(|p| &p.params)
+    }
+    /// Iterates over peers.
+    pub fn iter(&self) -> impl Iterator<Item = (&std::net::IpAddr, &BgpCollectorPeer)> {
+        self.peers.iter()
+    }
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+    /// Decodes whole message (with 19-byte head) received from peer and updates peer state.
+    pub fn decode_from(
+        &mut self,
+        addr: &std::net::IpAddr,
+        buf: &[u8],
+    ) -> Result<BgpAnyMessage, BgpError> {
+        let peer = match self.peers.get_mut(addr) {
+            Some(p) => p,
+            None => return Err(BgpError::from_string(format!("Unknown peer {}", addr))),
+        };
+        let msgt = peer.params.decode_message_head(buf)?;
+        let msg = BgpAnyMessage::decode_from(
+            &peer.params,
+            msgt.msgtype,
+            slice(buf, 19, 19 + msgt.body_len)?,
+        )?;
+        peer.process(&msg);
+        Ok(msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BgpMessage as _;
+
+    #[test]
+    fn test_collector_open() {
+        let local = BgpSessionParams::new(
+            Asn(64512),
+            180,
+            BgpTransportMode::IPv4,
+            std::net::Ipv4Addr::new(1, 1, 1, 1),
+            vec![
+                BgpCapability::SafiIPv4u,
+                BgpCapability::SafiIPv6u,
+                BgpCapability::CapASN32(Asn(64512)),
+            ],
+        );
+        let remote = BgpSessionParams::new(
+            Asn(64513),
+            90,
+            BgpTransportMode::IPv4,
+            std::net::Ipv4Addr::new(2, 2, 2, 2),
+            vec![
+                BgpCapability::SafiIPv4u,
+                BgpCapability::CapASN32(Asn(64513)),
+            ],
+        );
+        let addr: std::net::IpAddr = "10.0.0.2".parse().unwrap();
+        let mut coll = BgpCollector::new();
+        coll.add_peer(addr, local);
+        let mut buf = vec![0_u8; 4096];
+        let len = remote
+            .open_message()
+            .encode_to(&remote, &mut buf[19..])
+            .unwrap();
+        remote
+            .prepare_message_buf(&mut buf, BgpMessageType::Open, len)
+            .unwrap();
+        let msg = coll.decode_from(&addr, &buf[..19 + len]).unwrap();
+        assert_eq!(msg.msgtype(), BgpMessageType::Open);
+        let peer = coll.get(&addr).unwrap();
+        assert!(peer.is_established());
+        assert_eq!(peer.params.hold_time, 90);
+        assert!(!peer.params.check_capability(&BgpCapability::SafiIPv6u));
+        assert_eq!(coll.len(), 1);
+        assert!(coll
+            .decode_from(&"10.0.0.3".parse().unwrap(), &buf[..19 + len])
+            .is_err());
+    }
+    #[test]
+    fn test_collector_reopen() {
+        let local = BgpSessionParams::new(
+            Asn(64512),
+            180,
+            BgpTransportMode::IPv4,
+            std::net::Ipv4Addr::new(1, 1, 1, 1),
+            vec![BgpCapability::SafiIPv4u, BgpCapability::SafiIPv6u],
+        );
+        let mut peer = BgpCollectorPeer::new(local);
+        let mut open = BgpOpenMessage::new();
+        open.hold_time = 30;
+        open.caps = vec![BgpCapability::SafiIPv4u];
+        peer.process(&BgpAnyMessage::Open(open.clone()));
+        assert_eq!(peer.params.hold_time, 30);
+        assert!(!peer.params.check_capability(&BgpCapability::SafiIPv6u));
+        // session reset, peer comes back with more capabilities and larger hold time
+        open.hold_time = 90;
+        open.caps = vec![BgpCapability::SafiIPv4u, BgpCapability::SafiIPv6u];
+        peer.process(&BgpAnyMessage::Open(open));
+        assert_eq!(peer.params.hold_time, 90);
+        assert!(peer.params.check_capability(&BgpCapability::SafiIPv6u));
+        assert_eq!(peer.local.hold_time, 180);
+    }
+}
@@ -55,6 +55,31 @@ impl BgpError {
     pub fn too_many_data() -> BgpError {
         BgpError::TooManyData
     }
+    /// Wraps notification that should be sent to peer.
+    #[inline]
+    pub fn notification(n: crate::message::notification::BgpNotificationMessage) -> BgpError {
+        BgpError::Other(Box::new(n))
+    }
+    /// Returns notification that should be sent to peer, if error carries it.
+    pub fn get_notification(
+        &self,
+    ) -> Option<&crate::message::notification::BgpNotificationMessage> {
+        match self {
+            BgpError::Other(e) => e.downcast_ref(),
+            _ => None,
+        }
+    }
+    /// Returns error kind name, without message details.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BgpError::Static(_) => "Static",
+            BgpError::InsufficientBufferSize => "InsufficientBufferSize",
+            BgpError::ProtocolError => "ProtocolError",
+            BgpError::TooManyData => "TooManyData",
+            BgpError::DynStr(_) => "DynStr",
+            BgpError::Other(_) => "Other",
+        }
+    }
 }
 impl std::fmt::Display for BgpError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
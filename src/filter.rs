@@ -0,0 +1,523 @@
+// Copyright 2021 Vladimir Melnikov.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! This module contains route filter expressions
+//!
+//! Expression is parsed once into [BgpFilter] and evaluated per route:
+//!
+//! ```
+//! use zettabgp::filter::BgpFilter;
+//!
+//! let f: BgpFilter = "aspath ~ \"3356$\" && prefixlen <= 24 && community 65000:666"
+//!     .parse()
+//!     .unwrap();
+//! ```
+//!
+//! Conditions:
+//! * `aspath ~ "pattern"` - AS path pattern, whitespace or `_` separated AS numbers,
+//!   `.` matches any single AS, `.*` any AS sequence, `^` and `$` anchor path start and end
+//! * `originas`, `aslen`, `prefixlen`, `med`, `localpref` with `= != < <= > >=` and number
+//! * `origin = igp|egp|incomplete`
+//! * `community a:b`, `community a:b:c` for large community, or name from
+//!   [BgpCommunityNames]
+//!   passed to [BgpFilter::parse_with_names]
+//!
+//! Conditions are combined with `&&`, `||`, `!` and parentheses.
+
+use crate::afi::Nlri;
+use crate::message::attributes::BgpAttrList;
+use crate::prelude::*;
+
+/// Numeric comparison operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BgpFilterCmp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl BgpFilterCmp {
+    pub fn check(&self, value: u32, arg: u32) -> bool {
+        match self {
+            BgpFilterCmp::Eq => value == arg,
+            BgpFilterCmp::Ne => value != arg,
+            BgpFilterCmp::Lt => value < arg,
+            BgpFilterCmp::Le => value <= arg,
+            BgpFilterCmp::Gt => value > arg,
+            BgpFilterCmp::Ge => value >= arg,
+        }
+    }
+}
+
+/// AS path pattern element
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BgpAsPatternItem {
+    /// exact AS number
+    As(Asn),
+    /// any single AS
+    Any,
+    /// any AS sequence, possibly empty
+    AnySeq,
+}
+
+/// Compiled AS path pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BgpAsPathPattern {
+    pub items: Vec<BgpAsPatternItem>,
+}
+
+impl BgpAsPathPattern {
+    /// Returns true if AS path matches pattern, in O(pattern*path) time.
+    pub fn matches(&self, path: &[BgpAS]) -> bool {
+        // cur[j] is true when pattern items seen so far match first j path elements
+        let mut cur = vec![false; path.len() + 1];
+        cur[0] = true;
+        for item in self.items.iter() {
+            let mut next = vec![false; path.len() + 1];
+            match item {
+                BgpAsPatternItem::AnySeq => {
+                    let mut seen = false;
+                    for j in 0..=path.len() {
+                        seen |= cur[j];
+                        next[j] = seen;
+                    }
+                }
+                BgpAsPatternItem::Any => {
+                    next[1..].copy_from_slice(&cur[..path.len()]);
+                }
+                BgpAsPatternItem::As(n) => {
+                    for j in 0..path.len() {
+                        next[j + 1] = cur[j] && path[j].value == *n;
+                    }
+                }
+            }
+            cur = next;
+        }
+        cur[path.len()]
+    }
+    /// Appends item, merging consecutive any-sequence items.
+    fn push(&mut self, item: BgpAsPatternItem) {
+        if item == BgpAsPatternItem::AnySeq && self.items.last() == Some(&BgpAsPatternItem::AnySeq)
+        {
+            return;
+        }
+        self.items.push(item);
+    }
+}
+
+impl std::str::FromStr for BgpAsPathPattern {
+    type Err = BgpError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut s = s.trim();
+        let mut ret = BgpAsPathPattern { items: Vec::new() };
+        match s.strip_prefix('^') {
+            Some(r) => s = r,
+            None => ret.push(BgpAsPatternItem::AnySeq),
+        }
+        let anchored_end = match s.strip_suffix('$') {
+            Some(r) => {
+                s = r;
+                true
+            }
+            None => false,
+        };
+        for w in s.split(|c: char| c.is_whitespace() || c == '_') {
+            match w {
+                "" => {}
+                "." => ret.push(BgpAsPatternItem::Any),
+                ".*" | "*" => ret.push(BgpAsPatternItem::AnySeq),
+                n => ret.push(BgpAsPatternItem::As(n.parse().map_err(|_| {
+                    BgpError::from_string(format!("Invalid AS path pattern item: {}", n))
+                })?)),
+            }
+        }
+        if !anchored_end {
+            ret.push(BgpAsPatternItem::AnySeq);
+        }
+        Ok(ret)
+    }
+}
+
+/// Filter expression AST
+#[derive(Debug, Clone, PartialEq)]
+pub enum BgpFilter {
+    And(Box<BgpFilter>, Box<BgpFilter>),
+    Or(Box<BgpFilter>, Box<BgpFilter>),
+    Not(Box<BgpFilter>),
+    AsPath(BgpAsPathPattern),
+    OriginAs(BgpFilterCmp, Asn),
+    AsPathLen(BgpFilterCmp, u32),
+    PrefixLen(BgpFilterCmp, u32),
+    Med(BgpFilterCmp, u32),
+    LocalPref(BgpFilterCmp, u32),
+    Origin(BgpAttrOrigin),
+    Community(BgpCommunity),
+    LargeCommunity(BgpLargeCommunity),
+}
+
+impl BgpFilter {
+    /// Parses filter expression, community names are resolved with registry.
+    pub fn parse_with_names(s: &str, names: &BgpCommunityNames) -> Result<BgpFilter, BgpError> {
+        parse_filter(s, Some(names))
+    }
+    /// Evaluates filter for route given by NLRI and its path attributes.
+    /// Numeric conditions on absent attributes are false.
+    pub fn matches(&self, nlri: &dyn Nlri, attrs: &[BgpAttrItem]) -> bool {
+        match self {
+            BgpFilter::And(a, b) => a.matches(nlri, attrs) && b.matches(nlri, attrs),
+            BgpFilter::Or(a, b) => a.matches(nlri, attrs) || b.matches(nlri, attrs),
+            BgpFilter::Not(a) => !a.matches(nlri, attrs),
+            BgpFilter::AsPath(p) => attrs
+                .get_attr_aspath()
+                .map(|a| p.matches(&a.value))
+                .unwrap_or(false),
+            BgpFilter::OriginAs(c, n) => attrs
+                .get_attr_aspath()
+                .and_then(|a| a.value.last())
+                .map(|a| c.check(a.value.value(), n.value()))
+                .unwrap_or(false),
+            BgpFilter::AsPathLen(c, n) => {
+                let len = attrs.get_attr_aspath().map(|a| a.value.len()).unwrap_or(0);
+                c.check(len as u32, *n)
+            }
+            BgpFilter::PrefixLen(c, n) => nlri
+                .prefixlen()
+                .map(|l| c.check(l as u32, *n))
+                .unwrap_or(false),
+            BgpFilter::Med(c, n) => attrs
+                .get_attr_med()
+                .map(|a| c.check(a.value, *n))
+                .unwrap_or(false),
+            BgpFilter::LocalPref(c, n) => attrs
+                .get_attr_localpref()
+                .map(|a| c.check(a.value, *n))
+                .unwrap_or(false),
+            BgpFilter::Origin(o) => attrs
+                .get_attr_origin()
+                .map(|a| a.value == *o)
+                .unwrap_or(false),
+            BgpFilter::Community(v) => attrs
+                .get_attr_communitylist()
+                .map(|a| a.value.contains(v))
+                .unwrap_or(false),
+            BgpFilter::LargeCommunity(v) => attrs
+                .get_attr_largecommunitylist()
+                .map(|a| a.value.contains(v))
+                .unwrap_or(false),
+        }
+    }
+    /// Returns announced NLRI of update message matching filter.
+    pub fn filter_update(&self, upd: &BgpUpdateMessage) -> Vec<BgpNlriItem> {
+        let mut ret = Vec::new();
+        let mp = upd.attrs.iter().filter_map(|a| match a {
+            BgpAttrItem::MPUpdates(u) => Some(&u.addrs),
+            _ => None,
+        });
+        for addrs in std::iter::once(&upd.updates).chain(mp) {
+            ret.extend(
+                addrs
+                    .nlri_items()
+                    .into_iter()
+                    .filter(|n| self.matches(n, &upd.attrs)),
+            );
+        }
+        ret
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Op(&'static str),
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, BgpError> {
+    const OPS: [&str; 13] = [
+        "&&", "||", "==", "!=", "<=", ">=", "!", "(", ")", "~", "=", "<", ">",
+    ];
+    let mut ret = Vec::new();
+    let mut rest = s.trim_start();
+    while !rest.is_empty() {
+        if let Some(r) = rest.strip_prefix('"') {
+            let end = r
+                .find('"')
+                .ok_or_else(|| BgpError::static_str("Unterminated string in filter"))?;
+            ret.push(Token::Str(r[..end].to_string()));
+            rest = &r[end + 1..];
+        } else if let Some(op) = OPS.iter().find(|op| rest.starts_with(**op)) {
+            ret.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        } else {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || "\"&|=!<>()~".contains(c))
+                .unwrap_or(rest.len());
+            if end == 0 {
+                return Err(BgpError::from_string(format!(
+                    "Unexpected filter input: {}",
+                    rest
+                )));
+            }
+            ret.push(Token::Word(rest[..end].to_string()));
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(ret)
+}
+
+/// Maximum nesting depth of '(' and '!' in filter expression.
+const MAX_FILTER_DEPTH: usize = 64;
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+    names: Option<&'a BgpCommunityNames>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+    fn next(&mut self) -> Result<Token, BgpError> {
+        let t = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| BgpError::static_str("Unexpected end of filter"))?;
+        self.pos += 1;
+        Ok(t)
+    }
+    fn eat(&mut self, op: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Op(o)) if *o == op) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+    fn word(&mut self) -> Result<String, BgpError> {
+        match self.next()? {
+            Token::Word(w) => Ok(w),
+            t => Err(BgpError::from_string(format!(
+                "Expected value in filter, got {:?}",
+                t
+            ))),
+        }
+    }
+    fn number(&mut self) -> Result<u32, BgpError> {
+        let w = self.word()?;
+        w.parse()
+            .map_err(|_| BgpError::from_string(format!("Invalid number in filter: {}", w)))
+    }
+    fn cmp(&mut self) -> Result<BgpFilterCmp, BgpError> {
+        match self.next()? {
+            Token::Op("=") | Token::Op("==") => Ok(BgpFilterCmp::Eq),
+            Token::Op("!=") => Ok(BgpFilterCmp::Ne),
+            Token::Op("<") => Ok(BgpFilterCmp::Lt),
+            Token::Op("<=") => Ok(BgpFilterCmp::Le),
+            Token::Op(">") => Ok(BgpFilterCmp::Gt),
+            Token::Op(">=") => Ok(BgpFilterCmp::Ge),
+            t => Err(BgpError::from_string(format!(
+                "Expected comparison in filter, got {:?}",
+                t
+            ))),
+        }
+    }
+    fn or(&mut self) -> Result<BgpFilter, BgpError> {
+        let mut l = self.and()?;
+        while self.eat("||") {
+            l = BgpFilter::Or(Box::new(l), Box::new(self.and()?));
+        }
+        Ok(l)
+    }
+    fn and(&mut self) -> Result<BgpFilter, BgpError> {
+        let mut l = self.unary()?;
+        while self.eat("&&") {
+            l = BgpFilter::And(Box::new(l), Box::new(self.unary()?));
+        }
+        Ok(l)
+    }
+    fn nested<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, BgpError>,
+    ) -> Result<T, BgpError> {
+        if self.depth >= MAX_FILTER_DEPTH {
+            return Err(BgpError::static_str("Filter nesting is too deep"));
+        }
+        self.depth += 1;
+        let ret = f(self);
+        self.depth -= 1;
+        ret
+    }
+    fn unary(&mut self) -> Result<BgpFilter, BgpError> {
+        if self.eat("!") {
+            return self.nested(|p| Ok(BgpFilter::Not(Box::new(p.unary()?))));
+        }
+        if self.eat("(") {
+            let e = self.nested(|p| p.or())?;
+            if !self.eat(")") {
+                return Err(BgpError::static_str("Expected ')' in filter"));
+            }
+            return Ok(e);
+        }
+        let key = self.word()?;
+        match key.as_str() {
+            "aspath" => {
+                if self.next()? != Token::Op("~") {
+                    return Err(BgpError::static_str("Expected '~' after aspath"));
+                }
+                match self.next()? {
+                    Token::Str(s) | Token::Word(s) => Ok(BgpFilter::AsPath(s.parse()?)),
+                    t => Err(BgpError::from_string(format!(
+                        "Expected AS path pattern, got {:?}",
+                        t
+                    ))),
+                }
+            }
+            "originas" => Ok(BgpFilter::OriginAs(self.cmp()?, Asn(self.number()?))),
+            "aslen" => Ok(BgpFilter::AsPathLen(self.cmp()?, self.number()?)),
+            "prefixlen" => Ok(BgpFilter::PrefixLen(self.cmp()?, self.number()?)),
+            "med" => Ok(BgpFilter::Med(self.cmp()?, self.number()?)),
+            "localpref" => Ok(BgpFilter::LocalPref(self.cmp()?, self.number()?)),
+            "origin" => {
+                if self.cmp()? != BgpFilterCmp::Eq {
+                    return Err(BgpError::static_str("Only '=' is allowed for origin"));
+                }
+                Ok(BgpFilter::Origin(self.word()?.parse()?))
+            }
+            "community" => {
+                let w = self.word()?;
+                if let Some(names) = self.names {
+                    if let Some(c) = names.community(&w) {
+                        return Ok(BgpFilter::Community(c));
+                    }
+                    if let Some(c) = names.large_community(&w) {
+                        return Ok(BgpFilter::LargeCommunity(c));
+                    }
+                }
+                if w.split(':').count() != 3 {
+                    if let Ok(c) = w.parse() {
+                        return Ok(BgpFilter::Community(c));
+                    }
+                }
+                Ok(BgpFilter::LargeCommunity(w.parse().map_err(|_| {
+                    BgpError::from_string(format!("Invalid community in filter: {}", w))
+                })?))
+            }
+            _ => Err(BgpError::from_string(format!(
+                "Unknown filter condition: {}",
+                key
+            ))),
+        }
+    }
+}
+
+impl std::str::FromStr for BgpFilter {
+    type Err = BgpError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_filter(s, None)
+    }
+}
+
+fn parse_filter(s: &str, names: Option<&BgpCommunityNames>) -> Result<BgpFilter, BgpError> {
+    let mut p = Parser {
+        tokens: tokenize(s)?,
+        pos: 0,
+        depth: 0,
+        names,
+    };
+    let ret = p.or()?;
+    match p.peek() {
+        None => Ok(ret),
+        Some(t) => Err(BgpError::from_string(format!(
+            "Unexpected filter token {:?}",
+            t
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter() {
+        let f: BgpFilter = "aspath ~ \"3356$\" && prefixlen <= 24 && community 65000:666"
+            .parse()
+            .unwrap();
+        let mut comms = BgpCommunityList::new();
+        comms.value.insert("65000:666".parse().unwrap());
+        let mut attrs = vec![
+            BgpAttrItem::ASPath(BgpASpath {
+                value: vec![BgpAS::new(65001), BgpAS::new(174), BgpAS::new(3356)],
+            }),
+            BgpAttrItem::CommunityList(comms),
+        ];
+        let upd = BgpUpdateMessage {
+            updates: BgpAddrs::IPV4U(vec![
+                BgpAddrV4::new("10.0.0.0".parse().unwrap(), 24),
+                BgpAddrV4::new("10.1.0.0".parse().unwrap(), 25),
+            ]),
+            withdraws: BgpAddrs::None,
+            attrs: attrs.clone(),
+        };
+        let matched = upd.updates.nlri_items();
+        assert!(f.matches(&matched[0], &attrs));
+        assert!(!f.matches(&matched[1], &attrs));
+        assert_eq!(f.filter_update(&upd).len(), 1);
+        attrs.pop();
+        assert!(!f.matches(&matched[0], &attrs));
+        let f: BgpFilter = "!(originas = 174 || aspath ~ \"^65001 . 3356$\") && aslen > 2"
+            .parse()
+            .unwrap();
+        assert!(!f.matches(&matched[0], &attrs));
+        let f: BgpFilter = "aspath ~ \"^65001_.*_3356\"".parse().unwrap();
+        assert!(f.matches(&matched[0], &attrs));
+        assert!("prefixlen <=".parse::<BgpFilter>().is_err());
+        assert!("aspath ~ \"unterminated".parse::<BgpFilter>().is_err());
+        assert!("med > 10 foo".parse::<BgpFilter>().is_err());
+        let nested = format!("{}med > 10{}", "(".repeat(32), ")".repeat(32));
+        assert!(nested.parse::<BgpFilter>().is_ok());
+        let deep = format!("{}med > 10", "!(".repeat(100_000));
+        assert!(deep.parse::<BgpFilter>().is_err());
+    }
+    #[test]
+    fn test_aspath_pattern_pathological() {
+        let p: BgpAsPathPattern = ". * .* .* .* .* .* .* .* .* .* 1".parse().unwrap();
+        assert_eq!(
+            p.items,
+            vec![
+                BgpAsPatternItem::AnySeq,
+                BgpAsPatternItem::Any,
+                BgpAsPatternItem::AnySeq,
+                BgpAsPatternItem::As(Asn(1)),
+                BgpAsPatternItem::AnySeq,
+            ]
+        );
+        let path: Vec<BgpAS> = (0..30).map(|n| BgpAS::new(65000 + n)).collect();
+        assert!(!p.matches(&path));
+        // unmerged pattern, as may be constructed by hand, is not exponential either
+        let hand = BgpAsPathPattern {
+            items: std::iter::repeat_n(BgpAsPatternItem::AnySeq, 20)
+                .chain(std::iter::once(BgpAsPatternItem::As(Asn(1))))
+                .collect(),
+        };
+        assert!(!hand.matches(&path));
+        let mut path = path;
+        path.push(BgpAS::new(1));
+        assert!(hand.matches(&path));
+        assert!(p.matches(&path));
+    }
+}
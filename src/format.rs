@@ -0,0 +1,368 @@
+// Copyright 2021 Vladimir Melnikov.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! This module contains configurable text rendering of NLRI, attributes and messages
+//!
+//! Display implementations keep their fixed format, [FormatWith::format_with]
+//! renders the same structures according to [FormatOpts].
+
+use crate::afi::mdt::{BgpMdtV4, BgpMdtV6};
+use crate::prelude::*;
+use std::sync::Arc;
+
+/// Route distinguisher rendering style
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BgpRdStyle {
+    /// administrator:assigned, e.g. "65000:100" or "10.0.0.1:5"
+    #[default]
+    Plain,
+    /// type:administrator:assigned, e.g. "0:65000:100"
+    Typed,
+    /// raw 8 bytes in hex
+    Hex,
+}
+
+/// Formatting options
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatOpts {
+    /// show non-zero path identifiers
+    pub show_pathid: bool,
+    /// route distinguisher style
+    pub rd_style: BgpRdStyle,
+    /// show 4-byte AS numbers in asdot notation (RFC 5396)
+    pub asdot: bool,
+    /// show MPLS labels
+    pub show_labels: bool,
+    /// verbose output: zero RD and path-id are shown, messages are rendered one item per line
+    pub verbose: bool,
+    /// community names shown instead of numbers
+    pub community_names: Option<Arc<BgpCommunityNames>>,
+}
+
+impl Default for FormatOpts {
+    fn default() -> Self {
+        FormatOpts {
+            show_pathid: true,
+            rd_style: BgpRdStyle::Plain,
+            asdot: false,
+            show_labels: true,
+            verbose: false,
+            community_names: None,
+        }
+    }
+}
+
+impl FormatOpts {
+    /// Prefixes only, without labels and path identifiers.
+    pub fn compact() -> FormatOpts {
+        FormatOpts {
+            show_pathid: false,
+            show_labels: false,
+            ..Default::default()
+        }
+    }
+    /// Everything, one item per line.
+    pub fn verbose() -> FormatOpts {
+        FormatOpts {
+            rd_style: BgpRdStyle::Typed,
+            verbose: true,
+            ..Default::default()
+        }
+    }
+    /// Formats AS number according to asdot option.
+    pub fn asn<A: Into<Asn>>(&self, asn: A) -> String {
+        let asn = asn.into();
+        if self.asdot {
+            asn.to_asdot()
+        } else {
+            asn.to_string()
+        }
+    }
+}
+
+/// Rendering with formatting options
+pub trait FormatWith {
+    fn format_with(&self, opts: &FormatOpts) -> String;
+}
+
+impl FormatWith for BgpRD {
+    fn format_with(&self, opts: &FormatOpts) -> String {
+        let rdtype = self.rdh >> 16;
+        let admin = match rdtype {
+            0 => opts.asn(self.rdh & 0xffff),
+            1 => {
+                std::net::Ipv4Addr::from(((self.rdh & 0xffff) << 16) | (self.rdl >> 16)).to_string()
+            }
+            _ => opts.asn(((self.rdh & 0xffff) << 16) | (self.rdl >> 16)),
+        };
+        let assigned = if rdtype == 0 {
+            self.rdl
+        } else {
+            self.rdl & 0xffff
+        };
+        match opts.rd_style {
+            BgpRdStyle::Plain => format!("{}:{}", admin, assigned),
+            BgpRdStyle::Typed => format!("{}:{}:{}", rdtype, admin, assigned),
+            BgpRdStyle::Hex => format!("{:08x}{:08x}", self.rdh, self.rdl),
+        }
+    }
+}
+
+impl FormatWith for BgpAddrV4 {
+    fn format_with(&self, _opts: &FormatOpts) -> String {
+        self.to_string()
+    }
+}
+
+impl FormatWith for BgpAddrV6 {
+    fn format_with(&self, _opts: &FormatOpts) -> String {
+        self.to_string()
+    }
+}
+
+impl FormatWith for BgpMdtV4 {
+    fn format_with(&self, _opts: &FormatOpts) -> String {
+        self.to_string()
+    }
+}
+
+impl FormatWith for BgpMdtV6 {
+    fn format_with(&self, _opts: &FormatOpts) -> String {
+        self.to_string()
+    }
+}
+
+impl<T: BgpItem<T> + FormatWith> FormatWith for Labeled<T> {
+    fn format_with(&self, opts: &FormatOpts) -> String {
+        if opts.show_labels && !self.labels.labels.is_empty() {
+            format!("<l:{}> {}", self.labels, self.prefix.format_with(opts))
+        } else {
+            self.prefix.format_with(opts)
+        }
+    }
+}
+
+impl<T: BgpItem<T> + FormatWith> FormatWith for WithRd<T> {
+    fn format_with(&self, opts: &FormatOpts) -> String {
+        if self.rd.is_zero() && !opts.verbose {
+            self.prefix.format_with(opts)
+        } else {
+            format!(
+                "<rd:{}> {}",
+                self.rd.format_with(opts),
+                self.prefix.format_with(opts)
+            )
+        }
+    }
+}
+
+impl<T: Clone + PartialEq + Eq + PartialOrd + FormatWith> FormatWith for WithPathId<T> {
+    fn format_with(&self, opts: &FormatOpts) -> String {
+        if opts.show_pathid && (self.pathid != 0 || opts.verbose) {
+            format!("{} path-id {}", self.nlri.format_with(opts), self.pathid)
+        } else {
+            self.nlri.format_with(opts)
+        }
+    }
+}
+
+fn join_with<T: FormatWith>(v: &[T], opts: &FormatOpts) -> String {
+    v.iter()
+        .map(|x| x.format_with(opts))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn join_display<T: std::fmt::Display>(v: &[T]) -> String {
+    v.iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl FormatWith for BgpAddrs {
+    fn format_with(&self, opts: &FormatOpts) -> String {
+        match self {
+            BgpAddrs::None => String::new(),
+            BgpAddrs::IPV4U(v) => join_with(v, opts),
+            BgpAddrs::IPV4M(v) => join_with(v, opts),
+            BgpAddrs::IPV4LU(v) => join_with(v, opts),
+            BgpAddrs::VPNV4U(v) => join_with(v, opts),
+            BgpAddrs::VPNV4M(v) => join_with(v, opts),
+            BgpAddrs::IPV6U(v) => join_with(v, opts),
+            BgpAddrs::IPV6M(v) => join_with(v, opts),
+            BgpAddrs::IPV6LU(v) => join_with(v, opts),
+            BgpAddrs::VPNV6U(v) => join_with(v, opts),
+            BgpAddrs::VPNV6M(v) => join_with(v, opts),
+            BgpAddrs::L2VPLS(v) => join_display(v),
+            BgpAddrs::MVPN(v) => join_display(v),
+            BgpAddrs::EVPN(v) => join_display(v),
+            BgpAddrs::FS4U(v) => join_display(v),
+            BgpAddrs::FS6U(v) => join_display(v),
+            BgpAddrs::FSV4U(v) => join_display(v),
+            BgpAddrs::IPV4UP(v) => join_with(v, opts),
+            BgpAddrs::IPV4MP(v) => join_with(v, opts),
+            BgpAddrs::IPV4LUP(v) => join_with(v, opts),
+            BgpAddrs::VPNV4UP(v) => join_with(v, opts),
+            BgpAddrs::VPNV4MP(v) => join_with(v, opts),
+            BgpAddrs::IPV6UP(v) => join_with(v, opts),
+            BgpAddrs::IPV6MP(v) => join_with(v, opts),
+            BgpAddrs::IPV6LUP(v) => join_with(v, opts),
+            BgpAddrs::VPNV6UP(v) => join_with(v, opts),
+            BgpAddrs::VPNV6MP(v) => join_with(v, opts),
+            BgpAddrs::IPV4MDT(v) => join_with(v, opts),
+            BgpAddrs::IPV4MDTP(v) => join_with(v, opts),
+            BgpAddrs::IPV6MDT(v) => join_with(v, opts),
+            BgpAddrs::IPV6MDTP(v) => join_with(v, opts),
+        }
+    }
+}
+
+impl FormatWith for BgpNlriItem {
+    fn format_with(&self, opts: &FormatOpts) -> String {
+        self.addrs().format_with(opts)
+    }
+}
+
+impl FormatWith for BgpASpath {
+    fn format_with(&self, opts: &FormatOpts) -> String {
+        self.value
+            .iter()
+            .map(|a| opts.asn(a.value))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl FormatWith for BgpAttrItem {
+    fn format_with(&self, opts: &FormatOpts) -> String {
+        match self {
+            BgpAttrItem::Origin(a) => a.to_string(),
+            BgpAttrItem::ASPath(a) => format!("as-path {}", a.format_with(opts)),
+            BgpAttrItem::NextHop(a) => format!("next-hop {}", a.value),
+            BgpAttrItem::MED(a) => format!("med {}", a.value),
+            BgpAttrItem::LocalPref(a) => format!("local-pref {}", a.value),
+            BgpAttrItem::AtomicAggregate(_) => "atomic-aggregate".to_string(),
+            BgpAttrItem::AggregatorAS(a) => format!("aggregator {} {}", opts.asn(a.asn), a.addr),
+            BgpAttrItem::CommunityList(a) => format!(
+                "community {}",
+                a.value
+                    .iter()
+                    .map(|c| match &opts.community_names {
+                        Some(n) => n.display(c),
+                        None => c.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            BgpAttrItem::LargeCommunityList(a) => format!(
+                "large-community {}",
+                a.value
+                    .iter()
+                    .map(|c| match &opts.community_names {
+                        Some(n) => n.display_large(c),
+                        None => c.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            BgpAttrItem::ExtCommunityList(a) => format!(
+                "ext-community {}",
+                a.value
+                    .iter()
+                    .map(|c| c.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            BgpAttrItem::MPUpdates(a) => format!(
+                "mp-reach next-hop {} {}",
+                a.nexthop,
+                a.addrs.format_with(opts)
+            ),
+            BgpAttrItem::MPWithdraws(a) => format!("mp-unreach {}", a.addrs.format_with(opts)),
+            BgpAttrItem::OriginatorID(a) => a.to_string(),
+            BgpAttrItem::ClusterList(a) => a.to_string(),
+            BgpAttrItem::PMSITunnel(a) => a.to_string(),
+            BgpAttrItem::AttrSet(a) => a.to_string(),
+            BgpAttrItem::Connector(a) => a.to_string(),
+            BgpAttrItem::Unknown(a) => a.to_string(),
+        }
+    }
+}
+
+impl FormatWith for BgpUpdateMessage {
+    fn format_with(&self, opts: &FormatOpts) -> String {
+        let mut items = Vec::new();
+        if !self.withdraws.is_empty() {
+            items.push(format!("withdraw {}", self.withdraws.format_with(opts)));
+        }
+        items.extend(self.attrs.iter().map(|a| a.format_with(opts)));
+        if !self.updates.is_empty() {
+            items.push(format!("announce {}", self.updates.format_with(opts)));
+        }
+        if opts.verbose {
+            items.join("\n")
+        } else {
+            items.join("; ")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_with() {
+        let mut msg = BgpUpdateMessage::new();
+        msg.attrs = vec![
+            BgpAttrItem::ASPath(BgpASpath {
+                value: vec![BgpAS::new(65001), BgpAS::new(4200000001)],
+            }),
+            BgpAttrItem::MED(BgpMED::new(10)),
+            BgpAttrItem::MPUpdates(BgpMPUpdates {
+                nexthop: BgpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 1)),
+                link_local: None,
+                addrs: BgpAddrs::VPNV4UP(vec![WithPathId::new(
+                    3,
+                    Labeled::new(
+                        MplsLabels::fromvec(vec![100]),
+                        WithRd::new(
+                            BgpRD::new(65000, 5),
+                            BgpAddrV4::new(std::net::Ipv4Addr::new(10, 1, 0, 0), 16),
+                        ),
+                    ),
+                )]),
+            }),
+        ];
+        assert_eq!(
+            msg.format_with(&FormatOpts::default()),
+            "as-path 65001 4200000001; med 10; mp-reach next-hop 10.0.0.1 <l:100> <rd:65000:5> 10.1.0.0/16 path-id 3"
+        );
+        let opts = FormatOpts {
+            asdot: true,
+            ..FormatOpts::compact()
+        };
+        assert_eq!(
+            msg.format_with(&opts),
+            "as-path 65001 64086.59905; med 10; mp-reach next-hop 10.0.0.1 <rd:65000:5> 10.1.0.0/16"
+        );
+        assert_eq!(msg.format_with(&FormatOpts::verbose()).lines().count(), 3);
+        let rd = BgpRD::new(0x0002fde8, 0x00010007);
+        let typed = FormatOpts {
+            rd_style: BgpRdStyle::Typed,
+            ..Default::default()
+        };
+        assert_eq!(rd.format_with(&typed), "2:4259840001:7");
+        let hex = FormatOpts {
+            rd_style: BgpRdStyle::Hex,
+            ..Default::default()
+        };
+        assert_eq!(rd.format_with(&hex), "0002fde800010007");
+    }
+}
@@ -67,7 +67,7 @@
 //!  Ok(sck) => sck,
 //!  Err(e) => {eprintln!("Unable to connect to BGP neighbor: {}",e);return;}
 //! };
-//! let params=BgpSessionParams::new(64512,180,BgpTransportMode::IPv4,std::net::Ipv4Addr::new(1,1,1,1),vec![BgpCapability::SafiIPv4u].into_iter().collect());
+//! let params=BgpSessionParams::new(Asn(64512),180,BgpTransportMode::IPv4,std::net::Ipv4Addr::new(1,1,1,1),vec![BgpCapability::SafiIPv4u].into_iter().collect());
 //! let mut buf = [0 as u8; 32768];
 //! let mut open_my = params.open_message();
 //! let open_sz = open_my.encode_to(&params, &mut buf[19..]).unwrap();
@@ -75,30 +75,45 @@
 //! socket.write_all(&buf[0..tosend]).unwrap();//send my open message
 //! socket.read_exact(&mut buf[0..19]).unwrap();//read response message head
 //! let messagehead=params.decode_message_head(&buf).unwrap();//decode message head
-//! if messagehead.0 == BgpMessageType::Open {
-//!   socket.read_exact(&mut buf[0..messagehead.1]).unwrap();//read message body
+//! if messagehead.msgtype == BgpMessageType::Open {
+//!   socket.read_exact(&mut buf[0..messagehead.body_len]).unwrap();//read message body
 //!   let mut bom = BgpOpenMessage::new();
-//!   bom.decode_from(&params, &buf[0..messagehead.1]).unwrap();//decode received message body
+//!   bom.decode_from(&params, &buf[0..messagehead.body_len]).unwrap();//decode received message body
 //!   eprintln!("BGP Open message received: {:?}", bom);
 //! }
 //! ```
 //!
+#![forbid(unsafe_code)]
+
 #[cfg(feature = "serialization")]
 extern crate serde;
+#[cfg(feature = "serialization")]
+use serde::{Deserialize, Serialize};
 
 pub mod afi;
+pub mod asn;
 pub mod bmp;
+pub mod collector;
 pub mod error;
+pub mod filter;
+pub mod format;
+pub mod llgr;
 pub mod message;
+pub mod metrics;
 pub mod prelude;
+pub mod routeleak;
+pub mod routeserver;
 pub mod util;
 
+use asn::*;
 use error::*;
 use message::open::*;
+use metrics::*;
 use util::*;
 
 /// BGP session transport - ipv4 or ipv6.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
 pub enum BgpTransportMode {
     IPv4,
     IPv6,
@@ -114,6 +129,31 @@ impl From<std::net::IpAddr> for BgpTransportMode {
     }
 }
 
+/// BGP message header marker validation mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub enum BgpMarkerCheck {
+    /// Marker must be all-ones.
+    Strict,
+    /// Marker may be all-ones or all-zeros.
+    Relaxed,
+    /// Marker is not checked.
+    Skip,
+}
+
+impl BgpMarkerCheck {
+    /// Checks marker bytes.
+    pub fn check(&self, marker: &[u8]) -> bool {
+        match self {
+            BgpMarkerCheck::Strict => marker.iter().all(|q| *q == 255),
+            BgpMarkerCheck::Relaxed => {
+                marker.iter().all(|q| *q == 255) || marker.iter().all(|q| *q == 0)
+            }
+            BgpMarkerCheck::Skip => true,
+        }
+    }
+}
+
 /// This trait represens BGP protocol message.
 pub trait BgpMessage {
     /// Decode from buffer.
@@ -132,6 +172,7 @@ pub trait BgpAddrItem<T: std::marker::Sized> {
 
 /// BGP capability GR
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
 pub struct BgpCapGR {
     pub afi: u16,
     pub safi: u8,
@@ -196,6 +237,7 @@ impl BgpCapGR {
 }
 /// BGP capability LLGR
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
 pub struct BgpCapLLGR {
     pub afi: u16,
     pub safi: u8,
@@ -247,6 +289,7 @@ impl BgpCapLLGR {
 }
 /// BGP capability AddPath.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
 pub struct BgpCapAddPath {
     pub afi: u16,
     pub safi: u8,
@@ -275,6 +318,12 @@ impl BgpCapAddPath {
             receive,
         })
     }
+    /// Builds AddPath entries for every address family capability in the list.
+    pub fn for_caps(caps: &[BgpCapability], send: bool, receive: bool) -> Vec<BgpCapAddPath> {
+        caps.iter()
+            .filter_map(|c| BgpCapAddPath::new_from_cap(c.clone(), send, receive).ok())
+            .collect()
+    }
     pub fn encode_to(&self, buf: &mut [u8]) -> Result<(), BgpError> {
         if buf.len() < 4 {
             return Err(BgpError::insufficient_buffer_size());
@@ -296,9 +345,145 @@ impl BgpCapAddPath {
         })
     }
 }
+/// Removes capability from the list, AddPath entries are removed one by one.
+fn remove_capability_from(caps: &mut Vec<BgpCapability>, cp: &BgpCapability) {
+    match cp {
+        BgpCapability::CapASN32(_) => caps.retain(|x| !matches!(x, BgpCapability::CapASN32(_))),
+        BgpCapability::CapAddPath(vc) => {
+            match caps
+                .iter_mut()
+                .find(|x| matches!(x, BgpCapability::CapAddPath(_)))
+            {
+                None => return,
+                Some(ref mut q) => {
+                    if let BgpCapability::CapAddPath(ref mut cvc) = q {
+                        for cp in vc.iter() {
+                            cvc.retain(|x| *x != *cp)
+                        }
+                    };
+                }
+            };
+            caps.retain(|x| match x {
+                BgpCapability::CapAddPath(vc) => !vc.is_empty(),
+                _ => true,
+            })
+        }
+        n => caps.retain(|x| *x != *n),
+    }
+}
+/// Adds capability to the list without duplicates.
+/// Capability replaces one with the same code, except multiprotocol ones which are kept per family,
+/// AddPath entries are merged into one block.
+fn add_capability_to(caps: &mut Vec<BgpCapability>, cp: BgpCapability) {
+    match cp {
+        BgpCapability::CapAddPath(vc) => {
+            match caps
+                .iter_mut()
+                .find(|x| matches!(x, BgpCapability::CapAddPath(_)))
+            {
+                Some(BgpCapability::CapAddPath(cvc)) => {
+                    for ap in vc.into_iter() {
+                        cvc.retain(|x| x.afi != ap.afi || x.safi != ap.safi);
+                        cvc.push(ap);
+                    }
+                }
+                _ => caps.push(BgpCapability::CapAddPath(vc)),
+            }
+        }
+        n if n.code() == 1 => {
+            if !caps.contains(&n) {
+                caps.push(n);
+            }
+        }
+        n => {
+            caps.retain(|x| x.code() != n.code());
+            caps.push(n);
+        }
+    }
+}
+fn find_addpath_in(caps: &[BgpCapability], afi: u16, safi: u8) -> Option<&BgpCapAddPath> {
+    for cap in caps.iter() {
+        if let BgpCapability::CapAddPath(mcap) = cap {
+            if let Some(r) = mcap.iter().find(|ap| ap.afi == afi && ap.safi == safi) {
+                return Some(r);
+            }
+        }
+    }
+    None
+}
+/// Address family - afi and safi pair
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct BgpAfiSafi {
+    pub afi: u16,
+    pub safi: u8,
+}
+impl BgpAfiSafi {
+    pub const IPV4U: BgpAfiSafi = BgpAfiSafi::new(1, 1);
+    pub const IPV6U: BgpAfiSafi = BgpAfiSafi::new(2, 1);
+
+    pub const fn new(afi: u16, safi: u8) -> BgpAfiSafi {
+        BgpAfiSafi { afi, safi }
+    }
+}
+impl From<(u16, u8)> for BgpAfiSafi {
+    fn from(v: (u16, u8)) -> Self {
+        BgpAfiSafi::new(v.0, v.1)
+    }
+}
+impl From<BgpAfiSafi> for (u16, u8) {
+    fn from(v: BgpAfiSafi) -> Self {
+        (v.afi, v.safi)
+    }
+}
+/// Address family implied by capability set, with AddPath state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct BgpNegotiatedFamily {
+    pub family: BgpAfiSafi,
+    /// AddPath send is active
+    pub addpath_send: bool,
+    /// AddPath receive is active
+    pub addpath_receive: bool,
+}
+/// Address families from multiprotocol capabilities, IPv4 unicast if there are none (RFC 4760).
+pub(crate) fn families_in(caps: &[BgpCapability]) -> std::vec::IntoIter<BgpNegotiatedFamily> {
+    let mut ret: Vec<BgpNegotiatedFamily> = Vec::new();
+    for cap in caps.iter() {
+        let (afi, safi) = match cap {
+            BgpCapability::SafiIPv4fu => (1, 133),
+            BgpCapability::SafiVPNv4fu => (1, 134),
+            BgpCapability::SafiIPv6fu => (2, 133),
+            c => match afisafi_from_cap(c.clone()) {
+                Ok(r) => r,
+                Err(_) => continue,
+            },
+        };
+        let family = BgpAfiSafi::new(afi, safi);
+        if ret.iter().any(|f| f.family == family) {
+            continue;
+        }
+        let ap = find_addpath_in(caps, afi, safi);
+        ret.push(BgpNegotiatedFamily {
+            family,
+            addpath_send: ap.map(|a| a.send).unwrap_or(false),
+            addpath_receive: ap.map(|a| a.receive).unwrap_or(false),
+        });
+    }
+    if ret.is_empty() {
+        let ap = find_addpath_in(caps, 1, 1);
+        ret.push(BgpNegotiatedFamily {
+            family: BgpAfiSafi::IPV4U,
+            addpath_send: ap.map(|a| a.send).unwrap_or(false),
+            addpath_receive: ap.map(|a| a.receive).unwrap_or(false),
+        });
+    }
+    ret.into_iter()
+}
 // capability codes https://www.iana.org/assignments/capability-codes/capability-codes.xhtml
 /// BGP capability for OPEN message.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
 pub enum BgpCapability {
     /// BGP capability ipv4 unicast.
     SafiIPv4u,
@@ -341,7 +526,7 @@ pub enum BgpCapability {
         afis: Vec<BgpCapGR>,
     },
     /// BGP capability 32-bit AS numbers.
-    CapASN32(u32),
+    CapASN32(Asn),
     /// BGP capability route-refresh.
     CapRR,
     /// BGP Capability AddPath
@@ -354,9 +539,61 @@ pub enum BgpCapability {
     CapFQDN(String, String),
     /// BGP Capability BFD Strict-Mode (draft-ietf-idr-bgp-bfd-strict-mode)
     CapBFD,
+    /// BGP Capability Extended Message (RFC8654)
+    CapExtendedMessage,
 }
 
 impl BgpCapability {
+    /// Capability code.
+    pub fn code(&self) -> u8 {
+        match self {
+            BgpCapability::CapGR { .. } => 64,
+            BgpCapability::CapASN32(_) => 65,
+            BgpCapability::CapRR => 2,
+            BgpCapability::CapAddPath(_) => 69,
+            BgpCapability::CapEnhancedRR => 70,
+            BgpCapability::CapLLGR(_) => 71,
+            BgpCapability::CapFQDN(_, _) => 73,
+            BgpCapability::CapBFD => 74,
+            BgpCapability::CapExtendedMessage => 6,
+            _ => 1,
+        }
+    }
+    /// Graceful Restart capability for given address families.
+    pub fn gr_for(families: &[BgpAfiSafi], restart_time: u16, fstate: bool) -> BgpCapability {
+        BgpCapability::CapGR {
+            restart_time,
+            restart_state: false,
+            afis: families
+                .iter()
+                .map(|f| BgpCapGR {
+                    afi: f.afi,
+                    safi: f.safi,
+                    forwarding_state: fstate,
+                })
+                .collect(),
+        }
+    }
+    /// Graceful Restart time, if this is GR capability.
+    pub fn restart_time(&self) -> Option<u16> {
+        match self {
+            BgpCapability::CapGR { restart_time, .. } => Some(*restart_time),
+            _ => None,
+        }
+    }
+    /// Returns true if this is GR capability preserving forwarding state for address family.
+    pub fn preserves(&self, family: BgpAfiSafi) -> bool {
+        match self {
+            BgpCapability::CapGR { afis, .. } => afis
+                .iter()
+                .any(|x| x.afi == family.afi && x.safi == family.safi && x.forwarding_state),
+            _ => false,
+        }
+    }
+    /// AddPath capability for every address family capability in the list.
+    pub fn addpath_for_all(caps: &[BgpCapability], send: bool, receive: bool) -> BgpCapability {
+        BgpCapability::CapAddPath(BgpCapAddPath::for_caps(caps, send, receive))
+    }
     /// Bytes needed to encode capability in OPEN message.
     fn bytes_len(&self) -> usize {
         match self {
@@ -387,6 +624,7 @@ impl BgpCapability {
                 4 + hostname.as_bytes().len() + domainname.as_bytes().len()
             }
             BgpCapability::CapBFD => 2,
+            BgpCapability::CapExtendedMessage => 2,
         }
     }
     /// Store capability code into the given buffer.
@@ -467,6 +705,7 @@ impl BgpCapability {
                 }
             }
             BgpCapability::CapASN32(as_num) => {
+                let as_num = as_num.value();
                 buf.clone_from_slice(&[
                     65,
                     4,
@@ -513,6 +752,9 @@ impl BgpCapability {
             BgpCapability::CapBFD => {
                 buf.clone_from_slice(&[74, 0]);
             }
+            BgpCapability::CapExtendedMessage => {
+                buf.clone_from_slice(&[6, 0]);
+            }
         };
         Ok(())
     }
@@ -551,6 +793,12 @@ impl BgpCapability {
                 }
                 BgpCapability::CapRR
             }
+            6 => {
+                if !data.is_empty() {
+                    return Err(BgpError::static_str("Invalid capability"));
+                }
+                BgpCapability::CapExtendedMessage
+            }
             64 => {
                 if data.len() < 2 || (data.len() - 2) % 4 != 0 {
                     return Err(BgpError::static_str("Invalid GR capability"));
@@ -573,7 +821,7 @@ impl BgpCapability {
                 if data.len() != 4 {
                     return Err(BgpError::static_str("Invalid capability"));
                 }
-                BgpCapability::CapASN32(getn_u32(data))
+                BgpCapability::CapASN32(Asn(getn_u32(data)))
             }
             69 => {
                 if data.len() & 3 != 0 {
@@ -668,9 +916,10 @@ impl BgpCapability {
 
 /// BGP session parameters - AS, hold time, capabilities etc.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
 pub struct BgpSessionParams {
     /// Autonomous system number.
-    pub as_num: u32,
+    pub as_num: Asn,
     /// Hold time in seconds.
     pub hold_time: u16,
     /// IP transport mode.
@@ -683,11 +932,24 @@ pub struct BgpSessionParams {
     pub caps: Vec<BgpCapability>,
     /// Try to detect pathid
     pub fuzzy_pathid: bool,
+    /// Optional decode metrics registry, not serialized.
+    #[cfg_attr(feature = "serialization", serde(skip))]
+    pub metrics: Option<std::sync::Arc<BgpDecodeMetrics>>,
+    /// Validate peer BGP identifier in OPEN message.
+    pub check_router_id: bool,
+    /// Message header marker validation mode.
+    pub marker_check: BgpMarkerCheck,
+    /// eBGP peer AS number that must be the leftmost one in received AS_PATH.
+    pub check_first_as: Option<Asn>,
+    /// Sort NLRI and path attributes on encode, for byte-stable output.
+    pub sort_nlri: bool,
+    /// Maximum accepted message length, raised by Extended Message capability.
+    pub max_message_len: usize,
 }
 
 impl BgpSessionParams {
     pub fn new(
-        asnum: u32,
+        asnum: Asn,
         holdtime: u16,
         peermode: BgpTransportMode,
         routerid: std::net::Ipv4Addr,
@@ -701,6 +963,26 @@ impl BgpSessionParams {
             router_id: routerid,
             caps: cps,
             fuzzy_pathid: true,
+            metrics: None,
+            check_router_id: false,
+            marker_check: BgpMarkerCheck::Strict,
+            check_first_as: None,
+            sort_nlri: false,
+            max_message_len: message::BGP_MAX_MESSAGE_LEN,
+        }
+    }
+    /// Counts decode result in metrics registry, if any.
+    #[inline]
+    pub(crate) fn record_decode<T>(&self, cat: BgpDecodeCategory, r: &Result<T, BgpError>) {
+        if let Some(m) = &self.metrics {
+            m.record(cat, r);
+        }
+    }
+    /// Counts tolerated item in metrics registry, if any.
+    #[inline]
+    pub(crate) fn record_ignored(&self, cat: BgpDecodeCategory) {
+        if let Some(m) = &self.metrics {
+            m.record_ignored(cat);
         }
     }
     /// Constructs BGP OPEN message from params.
@@ -712,13 +994,17 @@ impl BgpSessionParams {
         bom.hold_time = self.hold_time;
         bom
     }
-    /// Check capability set and validates has_as32bit flag.
+    /// Check capability set and validates has_as32bit flag and maximum message length.
     pub fn check_caps(&mut self) {
         self.has_as32bit = false;
+        self.max_message_len = message::BGP_MAX_MESSAGE_LEN;
         for cap in self.caps.iter() {
+            if *cap == BgpCapability::CapExtendedMessage {
+                self.max_message_len = message::BGP_MAX_EXT_MESSAGE_LEN;
+            }
             if let BgpCapability::CapASN32(n) = cap {
                 self.has_as32bit = true;
-                if self.as_num != 0 && self.as_num != 23456 && self.as_num != *n {
+                if self.as_num != Asn(0) && self.as_num != AS_TRANS && self.as_num != *n {
                     log::trace!(
                         "Capability 32-bit AS mismatch AS number: {:?}!={:?}",
                         self.as_num,
@@ -744,6 +1030,7 @@ impl BgpSessionParams {
     /// Match capability set
     pub fn match_caps(&mut self, rcaps: &[BgpCapability]) {
         self.has_as32bit = false;
+        let families: Vec<BgpAfiSafi> = families_in(&self.caps).map(|f| f.family).collect();
         let nv = self
             .caps
             .iter()
@@ -758,6 +1045,26 @@ impl BgpSessionParams {
                         None
                     }
                 }
+                BgpCapability::CapGR { .. } => match rcaps
+                    .iter()
+                    .find(|q| matches!(q, BgpCapability::CapGR { .. }))
+                {
+                    // peer's restart parameters, limited to families of this session
+                    Some(BgpCapability::CapGR {
+                        restart_time,
+                        restart_state,
+                        afis,
+                    }) => Some(BgpCapability::CapGR {
+                        restart_time: *restart_time,
+                        restart_state: *restart_state,
+                        afis: afis
+                            .iter()
+                            .filter(|a| families.contains(&BgpAfiSafi::new(a.afi, a.safi)))
+                            .cloned()
+                            .collect(),
+                    }),
+                    _ => None,
+                },
                 BgpCapability::CapAddPath(cap) => {
                     match rcaps
                         .iter()
@@ -781,16 +1088,56 @@ impl BgpSessionParams {
         self.caps = nv;
         self.check_caps();
     }
+    /// Returns own capabilities that would be dropped by match_caps with remote set.
+    pub fn unmatched_caps(&self, rcaps: &[BgpCapability]) -> Vec<BgpCapability> {
+        let mut matched = self.clone();
+        matched.match_caps(rcaps);
+        self.caps
+            .iter()
+            .filter_map(|x| match x {
+                BgpCapability::CapASN32(_) => {
+                    if matched.has_as32bit {
+                        None
+                    } else {
+                        Some(x.clone())
+                    }
+                }
+                BgpCapability::CapAddPath(cap) => {
+                    let missing: Vec<BgpCapAddPath> = cap
+                        .iter()
+                        .filter(|ap| matched.find_addpath(ap.afi, ap.safi).is_none())
+                        .cloned()
+                        .collect();
+                    if missing.is_empty() {
+                        None
+                    } else {
+                        Some(BgpCapability::CapAddPath(missing))
+                    }
+                }
+                _ => {
+                    if matched.check_capability(x) {
+                        None
+                    } else {
+                        Some(x.clone())
+                    }
+                }
+            })
+            .collect()
+    }
+    /// Returns peer's Graceful Restart capability, if it was negotiated by match_caps.
+    /// Forwarding state flags are kept only for families of this session.
+    pub fn gr_negotiated(&self) -> Option<&BgpCapability> {
+        self.caps
+            .iter()
+            .find(|x| matches!(x, BgpCapability::CapGR { .. }))
+    }
+    /// Address families of this session with AddPath state.
+    pub fn negotiated_families(&self) -> impl Iterator<Item = BgpNegotiatedFamily> {
+        families_in(&self.caps)
+    }
     /// Search for specified addpath capability.
     pub fn find_addpath(&self, afi: u16, safi: u8) -> Option<&BgpCapAddPath> {
-        for cap in self.caps.iter() {
-            if let BgpCapability::CapAddPath(mcap) = cap {
-                if let Some(r) = mcap.iter().find(|ap| ap.afi == afi && ap.safi == safi) {
-                    return Some(r);
-                }
-            }
-        }
-        None
+        find_addpath_in(&self.caps, afi, safi)
     }
     /// Search for specified addpath send capability.
     pub fn check_addpath_send(&self, afi: u16, safi: u8) -> bool {
@@ -812,60 +1159,57 @@ impl BgpSessionParams {
     }
     /// Remove capability
     pub fn remove_capability(&mut self, cp: &BgpCapability) {
-        match cp {
-            BgpCapability::CapASN32(_) => self
-                .caps
-                .retain(|x| !matches!(x, BgpCapability::CapASN32(_))),
-            BgpCapability::CapAddPath(vc) => {
-                match self
-                    .caps
-                    .iter_mut()
-                    .find(|x| matches!(x, BgpCapability::CapAddPath(_)))
-                {
-                    None => return,
-                    Some(ref mut q) => {
-                        if let BgpCapability::CapAddPath(ref mut cvc) = q {
-                            for cp in vc.iter() {
-                                cvc.retain(|x| *x != *cp)
-                            }
-                        };
-                    }
-                };
-                self.caps.retain(|x| match x {
-                    BgpCapability::CapAddPath(vc) => !vc.is_empty(),
-                    _ => true,
-                })
-            }
-            n => self.caps.retain(|x| *x != *n),
-        }
+        remove_capability_from(&mut self.caps, cp)
     }
     pub fn remove_capability_addpath(&mut self) {
         self.caps
             .retain(|x| !matches!(x, BgpCapability::CapAddPath(_)));
     }
     /// Decode message head from buffer. Returns following message kind and length.
-    pub fn decode_message_head(
+    pub fn decode_message_head(&self, buf: &[u8]) -> Result<message::BgpMessageHead, BgpError> {
+        self.decode_message_head_max(buf, self.max_message_len)
+    }
+    /// Decodes message head, accepting messages up to max_len bytes long.
+    pub fn decode_message_head_max(
         &self,
         buf: &[u8],
-    ) -> Result<(message::BgpMessageType, usize), BgpError> {
+        max_len: usize,
+    ) -> Result<message::BgpMessageHead, BgpError> {
         if buf.len() < 19 {
             return Err(BgpError::static_str("Invalid message header size!"));
         }
-        for q in buf[0..16].iter() {
-            if (*q) != 255 {
-                return Err(BgpError::static_str(
-                    "Invalid header content, MD5 is not supported!",
-                ));
-            }
+        if !self.marker_check.check(&buf[0..16]) {
+            return Err(BgpError::from_string(format!(
+                "Invalid message header marker {:02x?}",
+                &buf[0..16]
+            )));
+        }
+        let messagetype = message::BgpMessageType::decode_from(buf[18]);
+        if messagetype.is_err() {
+            self.record_decode(BgpDecodeCategory::Message(buf[18]), &messagetype);
         }
-        let messagetype = message::BgpMessageType::decode_from(buf[18])?;
-        Ok((messagetype, (getn_u16(&buf[16..18]) - 19) as usize))
+        let messagetype = messagetype?;
+        let total_len = getn_u16(&buf[16..18]) as usize;
+        let head = message::BgpMessageHead {
+            msgtype: messagetype,
+            total_len,
+            body_len: total_len.saturating_sub(19),
+        };
+        head.validate()?;
+        head.check_max_len(max_len)?;
+        Ok(head)
+    }
+    /// Returns message header marker bytes.
+    pub fn decode_message_marker(buf: &[u8]) -> Result<[u8; 16], BgpError> {
+        let mut ret = [0_u8; 16];
+        ret.copy_from_slice(slice(buf, 0, 16)?);
+        Ok(ret)
     }
     /// Receive message head from buffer. Returns following message kind and length.
     pub fn recv_message_head(
         &mut self,
         rdsrc: &mut impl std::io::Read,
-    ) -> Result<(message::BgpMessageType, usize), BgpError> {
+    ) -> Result<message::BgpMessageHead, BgpError> {
         let mut buf = [0_u8; 19];
         rdsrc.read_exact(&mut buf)?;
         self.decode_message_head(&buf)
@@ -908,6 +1252,15 @@ impl BgpSessionParams {
             Err(e) => Err(e.into()),
         }
     }
+    /// Writes message batch into the target with vectored writes.
+    pub fn send_message_batch(
+        &self,
+        wrdst: &mut impl std::io::Write,
+        batch: &mut message::batch::BgpMessageBatch,
+    ) -> Result<(), BgpError> {
+        batch.write_to(wrdst)?;
+        Ok(())
+    }
 }
 impl From<&BgpOpenMessage> for BgpSessionParams {
     fn from(bom: &BgpOpenMessage) -> BgpSessionParams {
@@ -919,11 +1272,114 @@ impl From<&BgpOpenMessage> for BgpSessionParams {
             router_id: bom.router_id,
             caps: bom.caps.clone(),
             fuzzy_pathid: false,
+            metrics: None,
+            check_router_id: false,
+            marker_check: BgpMarkerCheck::Strict,
+            check_first_as: None,
+            sort_nlri: false,
+            max_message_len: message::BGP_MAX_MESSAGE_LEN,
         };
         ret.check_caps();
         ret
     }
 }
+
+/// Per-peer overrides applied by BgpPeerTemplate.
+#[derive(Debug, Clone, Default)]
+pub struct BgpPeerOverrides {
+    /// Autonomous system number.
+    pub as_num: Option<Asn>,
+    /// IP transport mode.
+    pub peer_mode: Option<BgpTransportMode>,
+    /// Addpath capability set, empty vector removes addpath.
+    pub addpath: Option<Vec<BgpCapAddPath>>,
+}
+
+/// Peer-group template - shared capabilities and timers for many sessions.
+#[derive(Debug, Clone)]
+pub struct BgpPeerTemplate {
+    /// Autonomous system number.
+    pub as_num: Asn,
+    /// Hold time in seconds.
+    pub hold_time: u16,
+    /// IP transport mode.
+    pub peer_mode: BgpTransportMode,
+    /// Router ID.
+    pub router_id: std::net::Ipv4Addr,
+    /// Capability set.
+    pub caps: Vec<BgpCapability>,
+    /// Try to detect pathid
+    pub fuzzy_pathid: bool,
+    /// Optional decode metrics registry, shared by all sessions.
+    pub metrics: Option<std::sync::Arc<BgpDecodeMetrics>>,
+}
+
+impl BgpPeerTemplate {
+    pub fn new(
+        asnum: Asn,
+        holdtime: u16,
+        routerid: std::net::Ipv4Addr,
+        cps: Vec<BgpCapability>,
+    ) -> BgpPeerTemplate {
+        BgpPeerTemplate {
+            as_num: asnum,
+            hold_time: holdtime,
+            peer_mode: BgpTransportMode::IPv4,
+            router_id: routerid,
+            caps: cps,
+            fuzzy_pathid: true,
+            metrics: None,
+        }
+    }
+    /// Stamps out session params as is.
+    pub fn params(&self) -> BgpSessionParams {
+        let mut ret = BgpSessionParams::new(
+            self.as_num,
+            self.hold_time,
+            self.peer_mode,
+            self.router_id,
+            self.caps.clone(),
+        );
+        ret.fuzzy_pathid = self.fuzzy_pathid;
+        ret.metrics = self.metrics.clone();
+        ret
+    }
+    /// Stamps out session params with per-peer overrides.
+    pub fn params_with(&self, ovr: &BgpPeerOverrides) -> BgpSessionParams {
+        let mut ret = self.params();
+        if let Some(asn) = ovr.as_num {
+            ret.as_num = asn;
+            for cap in ret.caps.iter_mut() {
+                if let BgpCapability::CapASN32(n) = cap {
+                    *n = asn;
+                }
+            }
+        }
+        if let Some(mode) = ovr.peer_mode {
+            ret.peer_mode = mode;
+        }
+        if let Some(addpath) = &ovr.addpath {
+            ret.remove_capability_addpath();
+            if !addpath.is_empty() {
+                ret.caps.push(BgpCapability::CapAddPath(addpath.clone()));
+            }
+        }
+        ret
+    }
+}
+impl From<&BgpSessionParams> for BgpPeerTemplate {
+    fn from(params: &BgpSessionParams) -> BgpPeerTemplate {
+        BgpPeerTemplate {
+            as_num: params.as_num,
+            hold_time: params.hold_time,
+            peer_mode: params.peer_mode,
+            router_id: params.router_id,
+            caps: params.caps.clone(),
+            fuzzy_pathid: params.fuzzy_pathid,
+            metrics: params.metrics.clone(),
+        }
+    }
+}
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -931,7 +1387,7 @@ mod tests {
     #[test]
     fn test_capabilities_remove() {
         let mut params = BgpSessionParams::new(
-            64512,
+            Asn(64512),
             180,
             BgpTransportMode::IPv4,
             std::net::Ipv4Addr::new(1, 1, 1, 1),
@@ -975,4 +1431,213 @@ mod tests {
         }]));
         assert_eq!(params.caps.len(), 0);
     }
+    #[test]
+    fn test_addpath_for_all() {
+        let caps = vec![
+            BgpCapability::SafiIPv4u,
+            BgpCapability::CapRR,
+            BgpCapability::SafiIPv6u,
+        ];
+        assert_eq!(
+            BgpCapability::addpath_for_all(&caps, true, false),
+            BgpCapability::CapAddPath(vec![
+                BgpCapAddPath {
+                    afi: 1,
+                    safi: 1,
+                    send: true,
+                    receive: false
+                },
+                BgpCapAddPath {
+                    afi: 2,
+                    safi: 1,
+                    send: true,
+                    receive: false
+                },
+            ])
+        );
+    }
+    #[test]
+    fn test_gr_negotiated() {
+        let gr = BgpCapability::gr_for(&[BgpAfiSafi::IPV4U, BgpAfiSafi::IPV6U], 120, true);
+        assert_eq!(gr.restart_time(), Some(120));
+        assert!(gr.preserves(BgpAfiSafi::IPV6U));
+        assert!(!gr.preserves(BgpAfiSafi::new(1, 128)));
+        let mut params = BgpSessionParams::new(
+            Asn(64512),
+            180,
+            BgpTransportMode::IPv4,
+            std::net::Ipv4Addr::new(1, 1, 1, 1),
+            vec![BgpCapability::SafiIPv4u, gr],
+        );
+        let mut peer_gr = BgpCapability::gr_for(&[BgpAfiSafi::IPV4U, BgpAfiSafi::IPV6U], 90, true);
+        if let BgpCapability::CapGR { restart_state, .. } = &mut peer_gr {
+            *restart_state = true;
+        }
+        params.match_caps(&[BgpCapability::SafiIPv4u, peer_gr]);
+        let negotiated = params.gr_negotiated().unwrap();
+        assert_eq!(negotiated.restart_time(), Some(90));
+        assert!(matches!(
+            negotiated,
+            BgpCapability::CapGR {
+                restart_state: true,
+                ..
+            }
+        ));
+        assert!(negotiated.preserves(BgpAfiSafi::IPV4U));
+        assert!(!negotiated.preserves(BgpAfiSafi::IPV6U));
+        params.match_caps(&[BgpCapability::SafiIPv4u]);
+        assert_eq!(params.gr_negotiated(), None);
+    }
+    #[test]
+    fn test_peer_template() {
+        let tmpl = BgpPeerTemplate::new(
+            Asn(64512),
+            90,
+            std::net::Ipv4Addr::new(1, 1, 1, 1),
+            vec![
+                BgpCapability::SafiIPv4u,
+                BgpCapability::CapASN32(Asn(64512)),
+            ],
+        );
+        let params = tmpl.params_with(&BgpPeerOverrides {
+            as_num: Some(Asn(4200000000)),
+            peer_mode: Some(BgpTransportMode::IPv6),
+            addpath: Some(vec![BgpCapAddPath {
+                afi: 1,
+                safi: 1,
+                send: false,
+                receive: true,
+            }]),
+        });
+        assert_eq!(params.as_num, Asn(4200000000));
+        assert_eq!(params.hold_time, 90);
+        assert_eq!(params.peer_mode, BgpTransportMode::IPv6);
+        assert!(params.check_capability(&BgpCapability::CapASN32(Asn(4200000000))));
+        assert!(params.check_addpath_receive(1, 1));
+        assert_eq!(tmpl.params().caps, tmpl.caps);
+    }
+    #[test]
+    #[cfg(feature = "serialization")]
+    fn test_session_params_serde() {
+        let mut params = BgpSessionParams::new(
+            Asn(65001),
+            90,
+            BgpTransportMode::IPv6,
+            "10.0.0.1".parse().unwrap(),
+            vec![
+                BgpCapability::SafiIPv4u,
+                BgpCapability::SafiIPv6u,
+                BgpCapability::CapASN32(Asn(65001)),
+                BgpCapability::gr_for(&[BgpAfiSafi::IPV4U, BgpAfiSafi::IPV6U], 120, true),
+                BgpCapability::CapAddPath(vec![BgpCapAddPath::new_from_cap(
+                    BgpCapability::SafiIPv4u,
+                    true,
+                    true,
+                )
+                .unwrap()]),
+            ],
+        );
+        params.marker_check = BgpMarkerCheck::Relaxed;
+        params.metrics = Some(Default::default());
+        let json = serde_json::to_string(&params).unwrap();
+        let restored: BgpSessionParams = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.as_num, params.as_num);
+        assert_eq!(restored.hold_time, params.hold_time);
+        assert_eq!(restored.peer_mode, params.peer_mode);
+        assert_eq!(restored.router_id, params.router_id);
+        assert_eq!(restored.caps, params.caps);
+        assert_eq!(restored.marker_check, params.marker_check);
+        assert!(restored.metrics.is_none());
+        assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+    }
+    #[test]
+    fn test_negotiated_families() {
+        let mut params = BgpSessionParams::new(
+            Asn(65001),
+            90,
+            BgpTransportMode::IPv4,
+            "10.0.0.1".parse().unwrap(),
+            vec![BgpCapability::CapRR],
+        );
+        assert_eq!(
+            params.negotiated_families().collect::<Vec<_>>(),
+            vec![BgpNegotiatedFamily {
+                family: BgpAfiSafi::IPV4U,
+                addpath_send: false,
+                addpath_receive: false
+            }]
+        );
+        params.caps = vec![
+            BgpCapability::SafiIPv4u,
+            BgpCapability::SafiVPNv4u,
+            BgpCapability::SafiIPv6fu,
+            BgpCapability::CapAddPath(vec![BgpCapAddPath::new_from_cap(
+                BgpCapability::SafiVPNv4u,
+                false,
+                true,
+            )
+            .unwrap()]),
+        ];
+        let fams: Vec<BgpNegotiatedFamily> = params.negotiated_families().collect();
+        assert_eq!(fams.len(), 3);
+        assert!(!fams[0].addpath_receive);
+        assert_eq!(
+            (fams[1].family, fams[1].addpath_receive),
+            (BgpAfiSafi::new(1, 128), true)
+        );
+        assert_eq!(fams[2].family, (2, 133).into());
+        let open = params.open_message();
+        assert_eq!(open.negotiated_families().collect::<Vec<_>>(), fams);
+    }
+    #[test]
+    fn test_extended_message() {
+        let mut params = BgpSessionParams::new(
+            Asn(64512),
+            180,
+            BgpTransportMode::IPv4,
+            std::net::Ipv4Addr::new(1, 1, 1, 1),
+            vec![BgpCapability::SafiIPv4u, BgpCapability::CapExtendedMessage],
+        );
+        let mut buf = [0_u8; 19];
+        buf[0..16].copy_from_slice(&[255_u8; 16]);
+        setn_u16(5000, &mut buf[16..18]);
+        buf[18] = 2;
+        let mut plain = params.clone();
+        plain.match_caps(&[BgpCapability::SafiIPv4u]);
+        assert_eq!(plain.max_message_len, 4096);
+        let err = plain.decode_message_head(&buf).unwrap_err();
+        assert_eq!(err.get_notification().map(|n| n.data_u16()), Some(5000));
+        params.match_caps(&[BgpCapability::SafiIPv4u, BgpCapability::CapExtendedMessage]);
+        assert_eq!(params.max_message_len, 65535);
+        assert_eq!(params.decode_message_head(&buf).unwrap().body_len, 4981);
+        let mut cbuf = [0_u8; 2];
+        BgpCapability::CapExtendedMessage
+            .fill_buffer(&mut cbuf)
+            .unwrap();
+        assert_eq!(
+            BgpCapability::from_buffer(&cbuf).unwrap(),
+            (Ok(BgpCapability::CapExtendedMessage), 2)
+        );
+    }
+    #[test]
+    fn test_marker_check() {
+        let mut params = BgpSessionParams::new(
+            Asn(64512),
+            180,
+            BgpTransportMode::IPv4,
+            std::net::Ipv4Addr::new(1, 1, 1, 1),
+            Vec::new(),
+        );
+        let mut buf = [0_u8; 19];
+        buf[17] = 19;
+        buf[18] = 4;
+        assert!(params.decode_message_head(&buf).is_err());
+        params.marker_check = BgpMarkerCheck::Relaxed;
+        assert!(params.decode_message_head(&buf).is_ok());
+        buf[0] = 1;
+        assert!(params.decode_message_head(&buf).is_err());
+        params.marker_check = BgpMarkerCheck::Skip;
+        assert!(params.decode_message_head(&buf).is_ok());
+        assert_eq!(BgpSessionParams::decode_message_marker(&buf).unwrap()[0], 1);
+    }
 }
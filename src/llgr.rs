@@ -0,0 +1,140 @@
+// Copyright 2021 Vladimir Melnikov.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! This module contains Long-Lived Graceful Restart stale timers tracking
+//!
+//! Routes retained after session loss should be marked with
+//! [LLGR_STALE](crate::message::attributes::community::LLGR_STALE) community,
+//! routes with [NO_LLGR](crate::message::attributes::community::NO_LLGR) should not be retained.
+
+use crate::{BgpCapLLGR, BgpCapability, BgpSessionParams};
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{Duration, Instant};
+
+/// Per-family LLGR stale timers and retained routes.
+#[derive(Debug, Clone)]
+pub struct BgpLlgrTracker<R: Ord + Clone> {
+    /// Negotiated LLGR families
+    pub caps: Vec<BgpCapLLGR>,
+    deadlines: BTreeMap<(u16, u8), Instant>,
+    routes: BTreeMap<(u16, u8), BTreeSet<R>>,
+}
+
+impl<R: Ord + Clone> BgpLlgrTracker<R> {
+    pub fn new(caps: Vec<BgpCapLLGR>) -> BgpLlgrTracker<R> {
+        BgpLlgrTracker {
+            caps,
+            deadlines: BTreeMap::new(),
+            routes: BTreeMap::new(),
+        }
+    }
+    /// Creates tracker from negotiated session capabilities.
+    pub fn from_params(params: &BgpSessionParams) -> BgpLlgrTracker<R> {
+        let mut caps = Vec::new();
+        for cap in params.caps.iter() {
+            if let BgpCapability::CapLLGR(v) = cap {
+                caps.extend(v.iter().cloned());
+            }
+        }
+        BgpLlgrTracker::new(caps)
+    }
+    /// Starts stale timers for all families with non-zero stale time.
+    pub fn session_down(&mut self, now: Instant) {
+        for cap in self.caps.iter() {
+            if cap.stale_time > 0 {
+                self.deadlines.insert(
+                    (cap.afi, cap.safi),
+                    now + Duration::from_secs(cap.stale_time as u64),
+                );
+            }
+        }
+    }
+    /// Retains route as stale. Returns false if family is not within stale time.
+    pub fn retain(&mut self, afi: u16, safi: u8, route: R) -> bool {
+        if !self.deadlines.contains_key(&(afi, safi)) {
+            return false;
+        }
+        self.routes.entry((afi, safi)).or_default().insert(route);
+        true
+    }
+    /// Drops stale mark from route refreshed by restarted session.
+    pub fn refresh(&mut self, afi: u16, safi: u8, route: &R) -> bool {
+        match self.routes.get_mut(&(afi, safi)) {
+            None => false,
+            Some(v) => v.remove(route),
+        }
+    }
+    /// Returns true if family stale timer is running at given moment.
+    pub fn is_stale(&self, afi: u16, safi: u8, now: Instant) -> bool {
+        match self.deadlines.get(&(afi, safi)) {
+            None => false,
+            Some(d) => *d > now,
+        }
+    }
+    /// Families still within stale time.
+    pub fn stale_families(&self, now: Instant) -> Vec<(u16, u8)> {
+        self.deadlines
+            .iter()
+            .filter(|(_, d)| **d > now)
+            .map(|(k, _)| *k)
+            .collect()
+    }
+    /// Earliest timer expiration, if any.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.deadlines.values().min().cloned()
+    }
+    /// Stops stale timer for family (End-of-RIB received), returns routes not refreshed.
+    pub fn end_of_rib(&mut self, afi: u16, safi: u8) -> Vec<R> {
+        self.deadlines.remove(&(afi, safi));
+        self.routes
+            .remove(&(afi, safi))
+            .map(|v| v.into_iter().collect())
+            .unwrap_or_default()
+    }
+    /// Removes expired timers and returns routes to flush per family.
+    pub fn expire(&mut self, now: Instant) -> Vec<((u16, u8), Vec<R>)> {
+        let expired: Vec<(u16, u8)> = self
+            .deadlines
+            .iter()
+            .filter(|(_, d)| **d <= now)
+            .map(|(k, _)| *k)
+            .collect();
+        expired
+            .into_iter()
+            .map(|k| (k, self.end_of_rib(k.0, k.1)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_llgr_tracker() {
+        let mut tr = BgpLlgrTracker::<u32>::new(vec![
+            BgpCapLLGR::new_from_cap(BgpCapability::SafiIPv4u, 0, 10).unwrap(),
+            BgpCapLLGR::new_from_cap(BgpCapability::SafiIPv6u, 0, 100).unwrap(),
+        ]);
+        let t0 = Instant::now();
+        assert!(!tr.retain(1, 1, 1));
+        tr.session_down(t0);
+        assert!(tr.retain(1, 1, 1));
+        assert!(tr.retain(1, 1, 2));
+        assert!(tr.retain(2, 1, 3));
+        assert!(tr.refresh(1, 1, &2));
+        assert_eq!(tr.next_deadline(), Some(t0 + Duration::from_secs(10)));
+        let t1 = t0 + Duration::from_secs(20);
+        assert_eq!(tr.stale_families(t1), vec![(2, 1)]);
+        assert_eq!(tr.expire(t1), vec![((1, 1), vec![1])]);
+        assert!(!tr.is_stale(1, 1, t1));
+        assert!(tr.is_stale(2, 1, t1));
+        assert_eq!(tr.end_of_rib(2, 1), vec![3]);
+        assert_eq!(tr.next_deadline(), None);
+    }
+}
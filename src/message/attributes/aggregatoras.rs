@@ -18,7 +18,7 @@ use serde::{Deserialize, Serialize};
 #[derive(Serialize, Deserialize)]
 pub struct BgpAggregatorAS {
     /// Autonomous system number
-    pub asn: u32,
+    pub asn: Asn,
     /// Aggregation router ID
     pub addr: std::net::Ipv4Addr,
 }
@@ -26,12 +26,12 @@ impl BgpAggregatorAS {
     pub fn decode_from(_peer: &BgpSessionParams, buf: &[u8]) -> Result<BgpAggregatorAS, BgpError> {
         if buf.len() == 8 {
             Ok(BgpAggregatorAS {
-                asn: getn_u32(buf),
+                asn: Asn(getn_u32(buf)),
                 addr: decode_addrv4_from(&buf[4..8])?,
             })
         } else if buf.len() == 6 {
             Ok(BgpAggregatorAS {
-                asn: getn_u16(buf) as u32,
+                asn: Asn::from(getn_u16(buf)),
                 addr: decode_addrv4_from(&buf[2..6])?,
             })
         } else {
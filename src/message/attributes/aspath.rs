@@ -20,7 +20,7 @@ use std::hash::{Hash, Hasher};
 #[derive(Serialize, Deserialize)]
 #[serde(transparent)]
 pub struct BgpAS {
-    pub value: u32,
+    pub value: Asn,
 }
 /// BGP as-path path attribute
 #[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
@@ -33,18 +33,27 @@ pub struct BgpASpath {
 
 impl BgpAS {
     pub fn new(v: u32) -> BgpAS {
-        BgpAS { value: v }
+        BgpAS { value: Asn(v) }
     }
     pub fn tonumb(&self) -> u32 {
-        if (self.value & 0xffff) == 0 {
-            self.value >> 16
+        if (self.value.0 & 0xffff) == 0 {
+            self.value.0 >> 16
         } else {
-            self.value
+            self.value.0
         }
     }
+    /// AS number, normalized like tonumb.
+    pub fn asn(&self) -> Asn {
+        Asn(self.tonumb())
+    }
 }
 impl From<u32> for BgpAS {
     fn from(v: u32) -> Self {
+        BgpAS { value: Asn(v) }
+    }
+}
+impl From<Asn> for BgpAS {
+    fn from(v: Asn) -> Self {
         BgpAS { value: v }
     }
 }
@@ -71,8 +80,8 @@ impl Hash for BgpAS {
 }
 impl std::fmt::Display for BgpAS {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        if (self.value & 0xffff) == 0 {
-            write!(f, "{}", self.value >> 16)
+        if (self.value.0 & 0xffff) == 0 {
+            write!(f, "{}", self.value.0 >> 16)
         } else {
             write!(f, "{}", self.value)
         }
@@ -82,11 +91,29 @@ impl BgpASpath {
     pub fn new() -> BgpASpath {
         BgpASpath { value: Vec::new() }
     }
-    pub fn from<T: std::convert::Into<BgpAS>, I: IntoIterator<Item=T>>(sv: I) -> BgpASpath {
+    pub fn from<T: std::convert::Into<BgpAS>, I: IntoIterator<Item = T>>(sv: I) -> BgpASpath {
         BgpASpath {
             value: sv.into_iter().map(|q| q.into()).collect(),
         }
     }
+    /// Leftmost AS number - the one of neighbor advertised the route.
+    pub fn first_as(&self) -> Option<Asn> {
+        self.value.first().map(|a| a.asn())
+    }
+    /// Checks that leftmost AS is eBGP peer AS (RFC 4271 section 6.3).
+    /// 4-byte peer AS is seen as AS_TRANS on 2-byte session (RFC 6793).
+    /// Fails with Malformed AS_PATH notification.
+    pub fn check_first_as(&self, peer: &BgpSessionParams, peer_as: Asn) -> Result<(), BgpError> {
+        match self.value.first() {
+            Some(a) if *a == BgpAS::from(peer_as) => Ok(()),
+            Some(a) if !peer.has_as32bit && peer_as.0 > 65535 && *a == BgpAS::from(AS_TRANS) => {
+                Ok(())
+            }
+            _ => Err(BgpError::notification(
+                crate::message::notification::BgpNotificationMessage::from_code(3, 11),
+            )),
+        }
+    }
     pub fn decode_from(peer: &BgpSessionParams, buf: &[u8]) -> Result<BgpASpath, BgpError> {
         if buf.len() < 2 {
             return Ok(BgpASpath { value: Vec::new() });
@@ -156,10 +183,10 @@ impl BgpAttr for BgpASpath {
         }
         for i in &self.value {
             if peer.has_as32bit {
-                setn_u32(i.value, &mut buf[pos..(pos + 4)]);
+                setn_u32(i.value.0, &mut buf[pos..(pos + 4)]);
                 pos += 4;
             } else {
-                setn_u16(i.value as u16, &mut buf[pos..(pos + 2)]);
+                setn_u16(i.value.0 as u16, &mut buf[pos..(pos + 2)]);
                 pos += 2;
             }
         }
@@ -18,11 +18,14 @@ use serde::{Deserialize, Serialize};
 #[derive(Serialize, Deserialize)]
 pub struct BgpAttrSet {
     /// Originating autonomous system number
-    pub asn: u32,
+    pub asn: Asn,
     /// Carried path attributes
     pub attrs: Vec<BgpAttrItem>,
 }
 impl BgpAttrSet {
+    pub fn new(asn: Asn, attrs: Vec<BgpAttrItem>) -> BgpAttrSet {
+        BgpAttrSet { asn, attrs }
+    }
     pub fn decode_from(peer: &BgpSessionParams, buf: &[u8]) -> Result<BgpAttrSet, BgpError> {
         if !peer.has_as32bit {
             return Err(BgpError::static_str(
@@ -57,7 +60,7 @@ impl BgpAttrSet {
             curpos += attrlen;
         }
         Ok(BgpAttrSet {
-            asn: getn_u32(buf),
+            asn: Asn(getn_u32(buf)),
             attrs,
         })
     }
@@ -82,7 +85,73 @@ impl BgpAttr for BgpAttrSet {
             flags: 224,
         }
     }
-    fn encode_to(&self, _peer: &BgpSessionParams, _buf: &mut [u8]) -> Result<usize, BgpError> {
-        unimplemented!()
+    fn encode_to(&self, peer: &BgpSessionParams, buf: &mut [u8]) -> Result<usize, BgpError> {
+        if !peer.has_as32bit {
+            return Err(BgpError::static_str(
+                "Invalid BgpAttrSet without 32-bit AS support",
+            ));
+        }
+        if buf.len() < 4 {
+            return Err(BgpError::insufficient_buffer_size());
+        }
+        setn_u32(self.asn.value(), buf);
+        let mut curpos = 4;
+        for attr in self.attrs.iter() {
+            curpos += attr.encode_to(peer, &mut buf[curpos..])?;
+        }
+        Ok(curpos)
+    }
+}
+impl BgpAttrList for BgpAttrSet {
+    fn attr_items(&self) -> &[BgpAttrItem] {
+        &self.attrs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_attrset() {
+        let params = BgpSessionParams::new(
+            Asn(65001),
+            30,
+            BgpTransportMode::IPv4,
+            "10.0.0.1".parse().unwrap(),
+            vec![],
+        );
+        let set = BgpAttrSet::new(
+            Asn(65100),
+            vec![
+                BgpAttrItem::Origin(BgpOrigin::new(BgpAttrOrigin::Igp)),
+                BgpAttrItem::ASPath(BgpASpath::from(vec![65200])),
+                BgpAttrItem::LocalPref(BgpLocalpref::new(200)),
+            ],
+        );
+        let mut upd = BgpUpdateMessage::new();
+        upd.updates = BgpAddrs::IPV4U(vec![BgpAddrV4::new("10.1.0.0".parse().unwrap(), 16)]);
+        upd.attrs = vec![
+            BgpAttrItem::NextHop(BgpNextHop::from(std::net::Ipv4Addr::new(10, 0, 0, 1))),
+            BgpAttrItem::AttrSet(set.clone()),
+        ];
+        let mut buf = vec![0_u8; 4096];
+        let len = upd.encode_to(&params, &mut buf).unwrap();
+        let mut dec = BgpUpdateMessage::new();
+        dec.decode_from(&params, &buf[..len]).unwrap();
+        match dec
+            .attrs
+            .iter()
+            .find(|a| matches!(a, BgpAttrItem::AttrSet(_)))
+        {
+            Some(BgpAttrItem::AttrSet(s)) => {
+                assert_eq!(*s, set);
+                assert_eq!(s.get_attr_localpref().unwrap().value, 200);
+                assert_eq!(s.get_attr_aspath().unwrap().first_as(), Some(Asn(65200)));
+                assert!(s.get_attr_med().is_none());
+            }
+            a => panic!("unexpected {:?}", a),
+        }
     }
 }
@@ -14,13 +14,17 @@ use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
 /// no-export well-known community
-pub const NO_EXPORT: BgpCommunity = BgpCommunity{ value:0xffffff01 };
+pub const NO_EXPORT: BgpCommunity = BgpCommunity { value: 0xffffff01 };
 /// no-advertise well-known community
-pub const NO_ADVERTISE: BgpCommunity = BgpCommunity{ value:0xffffff02 };
+pub const NO_ADVERTISE: BgpCommunity = BgpCommunity { value: 0xffffff02 };
 /// no-export-subconfed well-known community
-pub const NO_EXPORT_SUBCONFED: BgpCommunity = BgpCommunity{ value:0xffffff03 };
+pub const NO_EXPORT_SUBCONFED: BgpCommunity = BgpCommunity { value: 0xffffff03 };
 /// no-peer well-known community
-pub const NOPEER: BgpCommunity = BgpCommunity{ value:0xffffff04 };
+pub const NOPEER: BgpCommunity = BgpCommunity { value: 0xffffff04 };
+/// LLGR_STALE well-known community - route is retained after session loss (RFC 9494)
+pub const LLGR_STALE: BgpCommunity = BgpCommunity { value: 0xffff0006 };
+/// NO_LLGR well-known community - route must not be retained as LLGR stale (RFC 9494)
+pub const NO_LLGR: BgpCommunity = BgpCommunity { value: 0xffff0007 };
 
 /// BGP community - element for BgpCommunityList path attribute
 #[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
@@ -93,6 +97,22 @@ impl std::fmt::Display for BgpLargeCommunity {
         write!(f, "{}:{}:{}", self.ga, self.ldp1, self.ldp2)
     }
 }
+impl FromStr for BgpLargeCommunity {
+    type Err = BgpError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.trim().split(':').collect();
+        let err = || BgpError::from_string(format!("Invalid large community: {}", s));
+        if parts.len() != 3 {
+            return Err(err());
+        }
+        Ok(BgpLargeCommunity {
+            ga: parts[0].parse().map_err(|_| err())?,
+            ldp1: parts[1].parse().map_err(|_| err())?,
+            ldp2: parts[2].parse().map_err(|_| err())?,
+        })
+    }
+}
 impl BgpLargeCommunityList {
     pub fn new() -> BgpLargeCommunityList {
         BgpLargeCommunityList {
@@ -142,14 +162,18 @@ impl Default for BgpLargeCommunityList {
     }
 }
 impl BgpCommunity {
-    const NO_EXPORT_STR0:&str="no_export";
-    const NO_EXPORT_STR1:&str="noexport";
-    const NO_EXPORT_STR2:&str="no-export";
-    const NO_ADVERTISE_STR0:&str="no_advertise";
-    const NO_ADVERTISE_STR1:&str="no-advertise";
-    const NO_EXPORT_SUBCONFED_STR:&str="no_export_subconfed";
-    const NOPEER_STR0:&str="nopeer";
-    const NOPEER_STR1:&str="no-peer";
+    const NO_EXPORT_STR0: &str = "no_export";
+    const NO_EXPORT_STR1: &str = "noexport";
+    const NO_EXPORT_STR2: &str = "no-export";
+    const NO_ADVERTISE_STR0: &str = "no_advertise";
+    const NO_ADVERTISE_STR1: &str = "no-advertise";
+    const NO_EXPORT_SUBCONFED_STR: &str = "no_export_subconfed";
+    const NOPEER_STR0: &str = "nopeer";
+    const NOPEER_STR1: &str = "no-peer";
+    const LLGR_STALE_STR0: &str = "llgr_stale";
+    const LLGR_STALE_STR1: &str = "llgr-stale";
+    const NO_LLGR_STR0: &str = "no_llgr";
+    const NO_LLGR_STR1: &str = "no-llgr";
     pub fn new(v: u32) -> BgpCommunity {
         BgpCommunity { value: v }
     }
@@ -188,12 +212,14 @@ impl std::fmt::Display for BgpCommunity {
             &NO_ADVERTISE => f.write_str(Self::NO_ADVERTISE_STR0),
             &NO_EXPORT_SUBCONFED => f.write_str(Self::NO_EXPORT_SUBCONFED_STR),
             &NOPEER => f.write_str(Self::NOPEER_STR0),
+            &LLGR_STALE => f.write_str(Self::LLGR_STALE_STR0),
+            &NO_LLGR => f.write_str(Self::NO_LLGR_STR0),
             _ => write!(
                 f,
                 "{}:{}",
                 (self.value >> 16) as u16,
                 (self.value & 0xffff) as u16
-                )
+            ),
         }
     }
 }
@@ -202,10 +228,14 @@ impl FromStr for BgpCommunity {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            Self::NO_EXPORT_STR0 | Self::NO_EXPORT_STR1 | Self::NO_EXPORT_STR2 => return Ok(NO_EXPORT.clone()),
+            Self::NO_EXPORT_STR0 | Self::NO_EXPORT_STR1 | Self::NO_EXPORT_STR2 => {
+                return Ok(NO_EXPORT.clone())
+            }
             Self::NO_ADVERTISE_STR0 | Self::NO_ADVERTISE_STR1 => return Ok(NO_ADVERTISE.clone()),
             Self::NO_EXPORT_SUBCONFED_STR => return Ok(NO_EXPORT_SUBCONFED.clone()),
             Self::NOPEER_STR0 | Self::NOPEER_STR1 => return Ok(NOPEER.clone()),
+            Self::LLGR_STALE_STR0 | Self::LLGR_STALE_STR1 => return Ok(LLGR_STALE.clone()),
+            Self::NO_LLGR_STR0 | Self::NO_LLGR_STR1 => return Ok(NO_LLGR.clone()),
             _ => {}
         };
         let parts: Vec<&str> = s.trim().split(':').collect();
@@ -303,20 +333,22 @@ mod tests {
 
     #[test]
     fn test_community_parse() {
-        assert_eq!(
-            "no_export".parse::<BgpCommunity>(),
-            Ok(NO_EXPORT.clone())
-        );
+        assert_eq!("no_export".parse::<BgpCommunity>(), Ok(NO_EXPORT.clone()));
         assert_eq!(
             "23:45".parse::<BgpCommunity>(),
-            Ok(BgpCommunity{ value: 0x0017002d })
+            Ok(BgpCommunity { value: 0x0017002d })
         );
     }
     #[test]
     fn test_community_format() {
         assert_eq!(
-            format!("{}",BgpCommunity{ value:0xffffff01 }),
+            format!("{}", BgpCommunity { value: 0xffffff01 }),
             "no_export".to_string()
         );
     }
+    #[test]
+    fn test_community_llgr() {
+        assert_eq!("llgr-stale".parse::<BgpCommunity>(), Ok(LLGR_STALE));
+        assert_eq!(format!("{}", NO_LLGR), "no_llgr".to_string());
+    }
 }
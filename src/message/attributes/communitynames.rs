@@ -0,0 +1,211 @@
+// Copyright 2021 Vladimir Melnikov.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Symbolic names for communities and large communities
+//!
+//! Registry is passed explicitly - [BgpCommunityNames::display] and [BgpCommunityNames::parse],
+//! [FormatOpts](crate::format::FormatOpts) or filter parser, so "acme:blackhole" can be
+//! printed and parsed instead of numbers. Display and FromStr of communities are not affected.
+
+use crate::message::attributes::community::{BgpCommunity, BgpLargeCommunity};
+use crate::BgpError;
+use std::collections::BTreeMap;
+
+/// Community names registry
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BgpCommunityNames {
+    names: BTreeMap<BgpCommunity, String>,
+    values: BTreeMap<String, BgpCommunity>,
+    large_names: BTreeMap<BgpLargeCommunity, String>,
+    large_values: BTreeMap<String, BgpLargeCommunity>,
+}
+
+impl BgpCommunityNames {
+    pub fn new() -> BgpCommunityNames {
+        Default::default()
+    }
+    /// Creates registry from (name, value) pairs, value is "a:b" community or "a:b:c" large community.
+    pub fn from_pairs<I, N, V>(pairs: I) -> Result<BgpCommunityNames, BgpError>
+    where
+        I: IntoIterator<Item = (N, V)>,
+        N: Into<String>,
+        V: AsRef<str>,
+    {
+        let mut ret = BgpCommunityNames::new();
+        for (name, value) in pairs {
+            let value = value.as_ref();
+            if value.split(':').count() == 3 {
+                ret.insert_large(name, value.parse()?);
+            } else {
+                let c = value.parse::<BgpCommunity>().map_err(|_| {
+                    BgpError::from_string(format!("Invalid community value: {}", value))
+                })?;
+                ret.insert(name, c);
+            }
+        }
+        Ok(ret)
+    }
+    /// Adds community name, replacing previous name for the same value
+    /// and previous value for the same name.
+    pub fn insert<N: Into<String>>(&mut self, name: N, value: BgpCommunity) {
+        let name = name.into();
+        if let Some(old) = self.names.insert(value.clone(), name.clone()) {
+            self.values.remove(&old);
+        }
+        if let Some(old) = self.values.insert(name, value.clone()) {
+            if old != value {
+                self.names.remove(&old);
+            }
+        }
+    }
+    /// Adds large community name, replacing previous name for the same value
+    /// and previous value for the same name.
+    pub fn insert_large<N: Into<String>>(&mut self, name: N, value: BgpLargeCommunity) {
+        let name = name.into();
+        if let Some(old) = self.large_names.insert(value.clone(), name.clone()) {
+            self.large_values.remove(&old);
+        }
+        if let Some(old) = self.large_values.insert(name, value.clone()) {
+            if old != value {
+                self.large_names.remove(&old);
+            }
+        }
+    }
+    /// Name of community
+    pub fn name(&self, value: &BgpCommunity) -> Option<&str> {
+        self.names.get(value).map(|s| s.as_str())
+    }
+    /// Name of large community
+    pub fn large_name(&self, value: &BgpLargeCommunity) -> Option<&str> {
+        self.large_names.get(value).map(|s| s.as_str())
+    }
+    /// Community by name
+    pub fn community(&self, name: &str) -> Option<BgpCommunity> {
+        self.values.get(name).cloned()
+    }
+    /// Large community by name
+    pub fn large_community(&self, name: &str) -> Option<BgpLargeCommunity> {
+        self.large_values.get(name).cloned()
+    }
+    pub fn len(&self) -> usize {
+        self.names.len() + self.large_names.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.large_names.is_empty()
+    }
+    /// Community name, or numeric form if it has no name.
+    pub fn display(&self, value: &BgpCommunity) -> String {
+        match self.name(value) {
+            Some(name) => name.to_string(),
+            None => value.to_string(),
+        }
+    }
+    /// Large community name, or numeric form if it has no name.
+    pub fn display_large(&self, value: &BgpLargeCommunity) -> String {
+        match self.large_name(value) {
+            Some(name) => name.to_string(),
+            None => value.to_string(),
+        }
+    }
+    /// Parses community name or any form accepted by BgpCommunity FromStr.
+    pub fn parse(&self, s: &str) -> Result<BgpCommunity, BgpError> {
+        if let Some(c) = self.community(s.trim()) {
+            return Ok(c);
+        }
+        s.parse()
+            .map_err(|_| BgpError::from_string(format!("Invalid community: {}", s)))
+    }
+    /// Parses large community name or "a:b:c".
+    pub fn parse_large(&self, s: &str) -> Result<BgpLargeCommunity, BgpError> {
+        match self.large_community(s.trim()) {
+            Some(c) => Ok(c),
+            None => s.parse(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::BgpFilter;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_community_names() {
+        let names = BgpCommunityNames::from_pairs(vec![
+            ("acme:blackhole", "64999:9999"),
+            ("acme:customer", "4200000999:1:2"),
+        ])
+        .unwrap();
+        assert_eq!(names.len(), 2);
+        assert_eq!(
+            names.name(&BgpCommunity::from(64999, 9999)),
+            Some("acme:blackhole")
+        );
+        assert!(BgpCommunityNames::from_pairs(vec![("bad", "x:y")]).is_err());
+        assert_eq!(
+            names.display(&BgpCommunity::from(64999, 9999)),
+            "acme:blackhole"
+        );
+        assert_eq!(names.display(&BgpCommunity::from(64999, 1)), "64999:1");
+        assert_eq!(
+            names.parse("acme:blackhole").unwrap(),
+            BgpCommunity::from(64999, 9999)
+        );
+        assert_eq!(
+            names.parse("65000:1").unwrap(),
+            BgpCommunity::from(65000, 1)
+        );
+        let large = names.parse_large("acme:customer").unwrap();
+        assert_eq!(names.display_large(&large), "acme:customer");
+        // plain Display and FromStr do not depend on registry
+        assert_eq!(large.to_string(), "4200000999:1:2");
+        assert_eq!(BgpCommunity::from(64999, 9999).to_string(), "64999:9999");
+        assert!("acme:blackhole".parse::<BgpCommunity>().is_err());
+
+        let mut comms = BgpCommunityList::new();
+        comms.value.insert(BgpCommunity::from(64999, 9999));
+        let attr = BgpAttrItem::CommunityList(comms);
+        let opts = FormatOpts {
+            community_names: Some(std::sync::Arc::new(names.clone())),
+            ..Default::default()
+        };
+        assert_eq!(attr.format_with(&opts), "community acme:blackhole");
+        assert_eq!(
+            attr.format_with(&FormatOpts::default()),
+            "community 64999:9999"
+        );
+        assert_eq!(
+            BgpFilter::parse_with_names("community acme:blackhole", &names).unwrap(),
+            BgpFilter::Community(BgpCommunity::from(64999, 9999))
+        );
+        assert!("community acme:blackhole".parse::<BgpFilter>().is_err());
+    }
+    #[test]
+    fn test_community_names_values() {
+        assert!(BgpCommunityNames::from_pairs(vec![("x", "1:2:3:4")]).is_err());
+        assert!(BgpCommunityNames::from_pairs(vec![("x", "1:x:3")]).is_err());
+        let names = BgpCommunityNames::from_pairs(vec![("drop", "no-export")]).unwrap();
+        assert_eq!(names.community("drop"), Some(NO_EXPORT.clone()));
+
+        let mut names = BgpCommunityNames::new();
+        names.insert("a", BgpCommunity::from(1, 1));
+        names.insert("a", BgpCommunity::from(1, 2));
+        assert_eq!(names.len(), 1);
+        assert_eq!(names.name(&BgpCommunity::from(1, 1)), None);
+        assert_eq!(names.display(&BgpCommunity::from(1, 1)), "1:1");
+        assert_eq!(names.community("a"), Some(BgpCommunity::from(1, 2)));
+        let l1: BgpLargeCommunity = "1:1:1".parse().unwrap();
+        let l2: BgpLargeCommunity = "1:1:2".parse().unwrap();
+        names.insert_large("b", l1.clone());
+        names.insert_large("b", l2.clone());
+        assert_eq!(names.len(), 2);
+        assert_eq!(names.large_name(&l1), None);
+        assert_eq!(names.large_name(&l2), Some("b"));
+    }
+}
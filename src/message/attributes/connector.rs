@@ -6,26 +6,77 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-//! BGP "connector" path attribute
+//! BGP "connector" path attribute (RFC 6037)
+//!
+//! Carried with VPN-IPv4 routes, identifies PE originated route by RD and address,
+//! which is also the tunnel endpoint for draft-rosen multicast VPN default MDT.
 
+use crate::afi::{BgpAddrV4, BgpMdtV4, BgpRD, WithRd};
 use crate::message::attributes::*;
 #[cfg(feature = "serialization")]
 use serde::{Deserialize, Serialize};
 use std::net::Ipv4Addr;
 
-/// BGP local preference path attribute
+/// BGP connector path attribute
 #[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
 #[cfg(feature = "serialization")]
 #[derive(Serialize, Deserialize)]
 pub struct BgpConnector {
+    /// high-order part of route distinguisher
     pub asn: u32,
+    /// low-order part of route distinguisher
     pub addr: Ipv4Addr,
+    /// originating PE address, tunnel endpoint
     pub orig: Ipv4Addr,
 }
 impl BgpConnector {
     pub fn new(asn: u32, addr: Ipv4Addr, orig: Ipv4Addr) -> BgpConnector {
         BgpConnector { asn, addr, orig }
     }
+    /// Constructs connector from route distinguisher and originating PE address.
+    pub fn from_rd(rd: &BgpRD, orig: Ipv4Addr) -> BgpConnector {
+        BgpConnector {
+            asn: rd.rdh,
+            addr: Ipv4Addr::from(rd.rdl),
+            orig,
+        }
+    }
+    /// Route distinguisher
+    pub fn rd(&self) -> BgpRD {
+        BgpRD::new(self.asn, u32::from(self.addr))
+    }
+    /// Checks that originating PE address is usable unicast tunnel endpoint.
+    /// Decoding does not call it, so received attribute is kept as is.
+    pub fn validate(&self) -> Result<(), BgpError> {
+        if self.orig.is_unspecified() || self.orig.is_multicast() || self.orig.is_broadcast() {
+            return Err(BgpError::from_string(format!(
+                "Invalid Connector address {}",
+                self.orig
+            )));
+        }
+        Ok(())
+    }
+    /// Constructs connector from MDT SAFI route.
+    pub fn from_mdt(mdt: &WithRd<BgpMdtV4>) -> BgpConnector {
+        BgpConnector::from_rd(&mdt.rd, mdt.prefix.addr.addr)
+    }
+    /// Checks that MDT SAFI route was originated by the same PE.
+    pub fn matches_mdt(&self, mdt: &WithRd<BgpMdtV4>) -> bool {
+        mdt.rd == self.rd() && mdt.prefix.addr.prefixlen == 32 && mdt.prefix.addr.addr == self.orig
+    }
+    /// MDT SAFI route mapping this PE to default MDT group of VPN.
+    pub fn default_mdt(&self, group: Ipv4Addr) -> Result<WithRd<BgpMdtV4>, BgpError> {
+        if !group.is_multicast() {
+            return Err(BgpError::from_string(format!(
+                "Invalid default MDT group {}",
+                group
+            )));
+        }
+        Ok(WithRd::new(
+            self.rd(),
+            BgpMdtV4::new(BgpAddrV4::new(self.orig, 32), group),
+        ))
+    }
     pub fn decode_from(buf: &[u8]) -> Result<BgpConnector, BgpError> {
         if buf.len() >= 14 {
             if getn_u16(buf) != 1 {
@@ -52,11 +103,7 @@ impl std::fmt::Debug for BgpConnector {
 }
 impl std::fmt::Display for BgpConnector {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(
-            f,
-            "BgpConnector({:?}:{:?} @{:?})",
-            self.asn, self.addr, self.orig
-        )
+        write!(f, "BgpConnector({} endpoint {})", self.rd(), self.orig)
     }
 }
 impl BgpAttr for BgpConnector {
@@ -74,7 +121,33 @@ impl BgpAttr for BgpConnector {
             encode_addrv4_to(&self.orig, &mut buf[10..14])?;
             Ok(14)
         } else {
-            Err(BgpError::static_str("Invalid localpref length"))
+            Err(BgpError::static_str("Invalid Connector length"))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connector_mdt() {
+        let rd: BgpRD = "65000:10".parse().unwrap();
+        let pe = Ipv4Addr::new(10, 0, 0, 1);
+        let conn = BgpConnector::from_rd(&rd, pe);
+        assert_eq!(conn.rd(), rd);
+        assert_eq!(conn.to_string(), "BgpConnector(65000:10 endpoint 10.0.0.1)");
+        let mdt = conn.default_mdt(Ipv4Addr::new(239, 1, 1, 1)).unwrap();
+        assert!(conn.matches_mdt(&mdt));
+        assert_eq!(BgpConnector::from_mdt(&mdt), conn);
+        assert!(conn.default_mdt(Ipv4Addr::new(10, 1, 1, 1)).is_err());
+        let mut buf = [0_u8; 14];
+        let params = BgpSessionParams::new(Asn(65000), 180, BgpTransportMode::IPv4, pe, vec![]);
+        conn.encode_to(&params, &mut buf).unwrap();
+        assert_eq!(BgpConnector::decode_from(&buf).unwrap(), conn);
+        assert!(conn.validate().is_ok());
+        buf[10] = 224;
+        let bad = BgpConnector::decode_from(&buf).unwrap();
+        assert!(bad.validate().is_err());
+    }
+}
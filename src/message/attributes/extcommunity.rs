@@ -42,6 +42,10 @@ impl BgpExtCommunity {
             b: (octs[2] as u32) << 24 | (octs[3] as u32) << 16 | (val as u32),
         }
     }
+    /// Transitive across AS boundaries, per high-order type bit (RFC 4360).
+    pub fn is_transitive(&self) -> bool {
+        (self.ctype & 0x40) == 0
+    }
     pub fn decode_from(buf: &[u8]) -> Result<BgpExtCommunity, BgpError> {
         match buf.len() {
             8 => Ok(BgpExtCommunity {
@@ -187,6 +191,12 @@ impl BgpExtCommunityList {
         }
         BgpExtCommunityList { value: vs }
     }
+    /// Removes non-transitive communities, returns number of removed ones.
+    pub fn strip_non_transitive(&mut self) -> usize {
+        let len = self.value.len();
+        self.value.retain(|c| c.is_transitive());
+        len - self.value.len()
+    }
     pub fn decode_from(buf: &[u8]) -> Result<BgpExtCommunityList, BgpError> {
         let mut v = std::collections::BTreeSet::new();
         let mut pos: usize = 0;
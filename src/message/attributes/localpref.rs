@@ -11,6 +11,10 @@
 use crate::message::attributes::*;
 #[cfg(feature = "serialization")]
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// Local preference assumed for paths without LOCAL_PREF attribute
+pub const BGP_DEFAULT_LOCALPREF: u32 = 100;
 
 /// BGP local preference path attribute
 #[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
@@ -24,6 +28,15 @@ impl BgpLocalpref {
     pub fn new(v: u32) -> BgpLocalpref {
         BgpLocalpref { value: v }
     }
+    /// Effective local preference of path, default if attribute is absent.
+    pub fn effective(lp: Option<&BgpLocalpref>, default: u32) -> u32 {
+        lp.map(|x| x.value).unwrap_or(default)
+    }
+    /// Compares local preference of two paths.
+    /// Returns Less when the first path is preferred (higher local preference).
+    pub fn compare(a: Option<&BgpLocalpref>, b: Option<&BgpLocalpref>, default: u32) -> Ordering {
+        BgpLocalpref::effective(b, default).cmp(&BgpLocalpref::effective(a, default))
+    }
     pub fn decode_from(buf: &[u8]) -> Result<BgpLocalpref, BgpError> {
         if buf.len() >= 4 {
             Ok(BgpLocalpref {
@@ -62,3 +75,22 @@ impl BgpAttr for BgpLocalpref {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_localpref_compare() {
+        let high = BgpLocalpref::new(200);
+        assert_eq!(
+            BgpLocalpref::compare(Some(&high), None, BGP_DEFAULT_LOCALPREF),
+            Ordering::Less
+        );
+        assert_eq!(
+            BgpLocalpref::compare(None, Some(&BgpLocalpref::new(100)), BGP_DEFAULT_LOCALPREF),
+            Ordering::Equal
+        );
+        assert_eq!(BgpLocalpref::effective(None, 50), 50);
+    }
+}
@@ -11,6 +11,7 @@
 use crate::message::attributes::*;
 #[cfg(feature = "serialization")]
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 
 /// BGP MED (multi-exit discriminator) path attribute
 #[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
@@ -34,6 +35,49 @@ impl BgpMED {
         }
     }
 }
+/// Treatment of paths without MED attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BgpMissingMed {
+    /// missing MED is 0, the best possible (RFC 4271)
+    #[default]
+    Zero,
+    /// missing MED is the worst possible
+    Worst,
+}
+
+/// MED comparison options for best path selection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BgpMedCompare {
+    /// missing MED treatment
+    pub missing: BgpMissingMed,
+    /// compare MED for paths from different neighbor AS too
+    pub always_compare: bool,
+}
+impl BgpMedCompare {
+    /// Effective MED value of path.
+    pub fn effective(&self, med: Option<&BgpMED>) -> u32 {
+        match (med, self.missing) {
+            (Some(m), _) => m.value,
+            (None, BgpMissingMed::Zero) => 0,
+            (None, BgpMissingMed::Worst) => u32::MAX,
+        }
+    }
+    /// Compares MED of two paths with given neighbor AS (leftmost in AS path).
+    /// Returns Less when the first path is preferred,
+    /// None when paths are not comparable by MED.
+    pub fn compare(
+        &self,
+        a: Option<&BgpMED>,
+        a_as: Option<Asn>,
+        b: Option<&BgpMED>,
+        b_as: Option<Asn>,
+    ) -> Option<Ordering> {
+        if !self.always_compare && a_as != b_as {
+            return None;
+        }
+        Some(self.effective(a).cmp(&self.effective(b)))
+    }
+}
 impl std::fmt::Debug for BgpMED {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("BgpMED")
@@ -62,3 +106,30 @@ impl BgpAttr for BgpMED {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_med_compare() {
+        let low = BgpMED::new(10);
+        let cmp = BgpMedCompare::default();
+        assert_eq!(
+            cmp.compare(None, Some(Asn(1)), Some(&low), Some(Asn(1))),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            cmp.compare(Some(&low), Some(Asn(1)), None, Some(Asn(2))),
+            None
+        );
+        let cmp = BgpMedCompare {
+            missing: BgpMissingMed::Worst,
+            always_compare: true,
+        };
+        assert_eq!(
+            cmp.compare(None, Some(Asn(1)), Some(&low), Some(Asn(2))),
+            Some(Ordering::Greater)
+        );
+    }
+}
@@ -14,6 +14,7 @@ pub mod atomicaggregate;
 pub mod attrset;
 pub mod clusterlist;
 pub mod community;
+pub mod communitynames;
 pub mod connector;
 pub mod extcommunity;
 pub mod localpref;
@@ -58,6 +59,88 @@ pub trait BgpAttr: std::fmt::Display + std::fmt::Debug {
     fn attr(&self) -> BgpAttrParams;
 }
 
+/// Typed access to a list of path attributes
+pub trait BgpAttrList {
+    /// path attributes
+    fn attr_items(&self) -> &[BgpAttrItem];
+    /// returns origin attribute.
+    fn get_attr_origin(&self) -> Option<&BgpOrigin> {
+        self.attr_items().iter().find_map(|i| match i {
+            BgpAttrItem::Origin(n) => Some(n),
+            _ => None,
+        })
+    }
+    /// returns aspath attribute.
+    fn get_attr_aspath(&self) -> Option<&BgpASpath> {
+        self.attr_items().iter().find_map(|i| match i {
+            BgpAttrItem::ASPath(n) => Some(n),
+            _ => None,
+        })
+    }
+    /// returns next hop attribute.
+    fn get_attr_nexthop(&self) -> Option<&BgpNextHop> {
+        self.attr_items().iter().find_map(|i| match i {
+            BgpAttrItem::NextHop(n) => Some(n),
+            _ => None,
+        })
+    }
+    /// returns MED attribute.
+    fn get_attr_med(&self) -> Option<&BgpMED> {
+        self.attr_items().iter().find_map(|i| match i {
+            BgpAttrItem::MED(n) => Some(n),
+            _ => None,
+        })
+    }
+    /// returns local preference attribute.
+    fn get_attr_localpref(&self) -> Option<&BgpLocalpref> {
+        self.attr_items().iter().find_map(|i| match i {
+            BgpAttrItem::LocalPref(n) => Some(n),
+            _ => None,
+        })
+    }
+    /// returns community list attribute.
+    fn get_attr_communitylist(&self) -> Option<&BgpCommunityList> {
+        self.attr_items().iter().find_map(|i| match i {
+            BgpAttrItem::CommunityList(n) => Some(n),
+            _ => None,
+        })
+    }
+    /// returns large community list attribute.
+    fn get_attr_largecommunitylist(&self) -> Option<&BgpLargeCommunityList> {
+        self.attr_items().iter().find_map(|i| match i {
+            BgpAttrItem::LargeCommunityList(n) => Some(n),
+            _ => None,
+        })
+    }
+    /// returns extended community list attribute.
+    fn get_attr_extcommunitylist(&self) -> Option<&BgpExtCommunityList> {
+        self.attr_items().iter().find_map(|i| match i {
+            BgpAttrItem::ExtCommunityList(n) => Some(n),
+            _ => None,
+        })
+    }
+}
+impl BgpAttrList for [BgpAttrItem] {
+    fn attr_items(&self) -> &[BgpAttrItem] {
+        self
+    }
+}
+
+/// Prepares path attributes for advertisement to eBGP peer:
+/// removes LOCAL_PREF, ORIGINATOR_ID, CLUSTER_LIST and non-transitive extended communities.
+pub fn ebgp_egress(attrs: &mut Vec<BgpAttrItem>) {
+    attrs.retain_mut(|a| match a {
+        BgpAttrItem::LocalPref(_) | BgpAttrItem::OriginatorID(_) | BgpAttrItem::ClusterList(_) => {
+            false
+        }
+        BgpAttrItem::ExtCommunityList(l) => {
+            l.strip_non_transitive();
+            !l.value.is_empty()
+        }
+        _ => true,
+    });
+}
+
 /// BGP path attribute
 #[derive(Clone, Debug, Hash, PartialOrd, Ord, PartialEq, Eq)]
 #[cfg(feature = "serialization")]
@@ -90,6 +173,22 @@ impl BgpAttrItem {
         flags: u8,
         attrlen: usize,
         buf: &[u8],
+    ) -> Result<BgpAttrItem, BgpError> {
+        let r = BgpAttrItem::decode_item(peer, typecode, flags, attrlen, buf);
+        match r {
+            Ok(BgpAttrItem::Unknown(_)) => {
+                peer.record_ignored(BgpDecodeCategory::Attribute(typecode))
+            }
+            _ => peer.record_decode(BgpDecodeCategory::Attribute(typecode), &r),
+        }
+        r
+    }
+    fn decode_item(
+        peer: &BgpSessionParams,
+        typecode: u8,
+        flags: u8,
+        attrlen: usize,
+        buf: &[u8],
     ) -> Result<BgpAttrItem, BgpError> {
         match typecode {
             1 => Ok(BgpAttrItem::Origin(BgpOrigin::decode_from(buf)?)),
@@ -154,33 +253,57 @@ impl BgpAttrItem {
             }
         }
     }
+    /// Encodes attribute with head. Extended Length flag is selected by actual payload size.
     fn encode_bgpattr(
         attr: &impl BgpAttr,
         peer: &BgpSessionParams,
         buf: &mut [u8],
     ) -> Result<usize, BgpError> {
+        if buf.len() < 3 {
+            return Err(BgpError::insufficient_buffer_size());
+        }
         let attrparams = attr.attr();
-        buf[0] = attrparams.flags;
         buf[1] = attrparams.typecode;
-        let mut curpos: usize = 2;
-        if (attrparams.flags & 16) > 0 {
-            curpos += 2;
-        } else {
-            curpos += 1;
+        let attrlen = attr.encode_to(peer, &mut buf[3..])?;
+        if attrlen > 65535 {
+            return Err(BgpError::static_str("Invalid path attribute length"));
         }
-        let attrlen = attr.encode_to(peer, &mut buf[curpos..])?;
-        if (attrparams.flags & 16) > 0 {
-            if attrlen > 65535 {
-                return Err(BgpError::static_str("Invalid path attribute length"));
+        if attrlen > 255 {
+            if buf.len() < 4 + attrlen {
+                return Err(BgpError::insufficient_buffer_size());
             }
+            buf.copy_within(3..3 + attrlen, 4);
+            buf[0] = attrparams.flags | 16;
             setn_u16(attrlen as u16, &mut buf[2..4]);
+            Ok(4 + attrlen)
         } else {
-            if attrlen > 255 {
-                return Err(BgpError::static_str("Invalid path attribute length"));
-            }
+            buf[0] = attrparams.flags & !16;
             buf[2] = attrlen as u8;
+            Ok(3 + attrlen)
+        }
+    }
+    /// Path attribute type code and flags.
+    pub fn attr(&self) -> BgpAttrParams {
+        match self {
+            BgpAttrItem::Origin(pa) => pa.attr(),
+            BgpAttrItem::ASPath(pa) => pa.attr(),
+            BgpAttrItem::NextHop(pa) => pa.attr(),
+            BgpAttrItem::MED(pa) => pa.attr(),
+            BgpAttrItem::LocalPref(pa) => pa.attr(),
+            BgpAttrItem::AtomicAggregate(pa) => pa.attr(),
+            BgpAttrItem::AggregatorAS(pa) => pa.attr(),
+            BgpAttrItem::CommunityList(pa) => pa.attr(),
+            BgpAttrItem::OriginatorID(pa) => pa.attr(),
+            BgpAttrItem::ClusterList(pa) => pa.attr(),
+            BgpAttrItem::MPUpdates(pa) => pa.attr(),
+            BgpAttrItem::MPWithdraws(pa) => pa.attr(),
+            BgpAttrItem::ExtCommunityList(pa) => pa.attr(),
+            BgpAttrItem::LargeCommunityList(pa) => pa.attr(),
+            BgpAttrItem::PMSITunnel(pa) => pa.attr(),
+            BgpAttrItem::AttrSet(pa) => pa.attr(),
+            BgpAttrItem::Connector(pa) => pa.attr(),
+            BgpAttrItem::Unknown(pa) => pa.attr(),
         }
-        Ok(curpos + attrlen)
     }
     pub fn encode_to(&self, peer: &BgpSessionParams, buf: &mut [u8]) -> Result<usize, BgpError> {
         match self {
@@ -205,3 +328,67 @@ impl BgpAttrItem {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    fn test_attr_extended_length() {
+        let params = BgpSessionParams::new(
+            Asn(64512),
+            180,
+            BgpTransportMode::IPv4,
+            std::net::Ipv4Addr::new(1, 1, 1, 1),
+            vec![BgpCapability::SafiIPv4u],
+        );
+        let mut buf = vec![0_u8; 4096];
+        let short =
+            BgpAttrItem::CommunityList(BgpCommunityList::from_vec(vec![BgpCommunity::new(1)]));
+        assert_eq!(short.encode_to(&params, &mut buf).unwrap(), 7);
+        assert_eq!(buf[0..4], [128 | 64, 8, 4, 0]);
+        let mut exact = [0_u8; 7];
+        assert_eq!(short.encode_to(&params, &mut exact).unwrap(), 7);
+        assert_eq!(exact[..], buf[0..7]);
+        let long = BgpAttrItem::CommunityList(BgpCommunityList::from_vec(
+            (0..100).map(BgpCommunity::new).collect(),
+        ));
+        assert_eq!(long.encode_to(&params, &mut buf).unwrap(), 404);
+        assert_eq!(buf[0..4], [128 | 64 | 16, 8, 1, 144]);
+        let attr = BgpAttrItem::decode_from(&params, 8, buf[0], 400, &buf[4..404]).unwrap();
+        assert_eq!(attr, long);
+        assert!(long.encode_to(&params, &mut buf[..403]).is_err());
+    }
+
+    #[test]
+    fn test_ebgp_egress() {
+        let rt = BgpExtCommunity::rt_asn(65000, 1);
+        let local = BgpExtCommunity {
+            ctype: 0x40,
+            subtype: 4,
+            a: 0,
+            b: 1,
+        };
+        assert!(rt.is_transitive());
+        assert!(!local.is_transitive());
+        let mut attrs = vec![
+            BgpAttrItem::LocalPref(BgpLocalpref::new(200)),
+            BgpAttrItem::ExtCommunityList(BgpExtCommunityList::from_vec(vec![
+                rt.clone(),
+                local.clone(),
+            ])),
+        ];
+        ebgp_egress(&mut attrs);
+        assert_eq!(
+            attrs,
+            vec![BgpAttrItem::ExtCommunityList(
+                BgpExtCommunityList::from_vec(vec![rt])
+            )]
+        );
+        let mut attrs = vec![BgpAttrItem::ExtCommunityList(
+            BgpExtCommunityList::from_vec(vec![local]),
+        )];
+        ebgp_egress(&mut attrs);
+        assert!(attrs.is_empty());
+    }
+}
@@ -18,14 +18,34 @@ use serde::{Deserialize, Serialize};
 pub struct BgpMPUpdates {
     /// next hop for this updates
     pub nexthop: BgpAddr,
+    /// ipv6 link-local next hop, carried after global one (32-byte nexthop)
+    #[cfg_attr(feature = "serialization", serde(default))]
+    pub link_local: Option<std::net::Ipv6Addr>,
     /// NLRI
     pub addrs: BgpAddrs,
 }
+/// Decodes plain nexthop by its length: none, ipv4 or ipv6 (global, optionally with link-local).
+fn decode_nexthop(buf: &[u8]) -> Result<(BgpAddr, Option<std::net::Ipv6Addr>), BgpError> {
+    match buf.len() {
+        0 => Ok((BgpAddr::None, None)),
+        4 => Ok((BgpAddr::V4(decode_addrv4_from(buf)?), None)),
+        16 => Ok((BgpAddr::V6(decode_addrv6_from(buf)?), None)),
+        32 => Ok((
+            BgpAddr::V6(decode_addrv6_from(buf)?),
+            Some(decode_addrv6_from(&buf[16..])?),
+        )),
+        n => Err(BgpError::from_string(format!(
+            "Invalid nexthop length {:?}",
+            n
+        ))),
+    }
+}
 impl BgpMPUpdates {
     /// Creates update for VPNv4 unicast
     pub fn s4vpnv4u(nhop: BgpIPv4RD, nlri: Vec<Labeled<WithRd<BgpAddrV4>>>) -> BgpMPUpdates {
         BgpMPUpdates {
             nexthop: BgpAddr::V4RD(nhop),
+            link_local: None,
             addrs: BgpAddrs::VPNV4U(nlri),
         }
     }
@@ -33,6 +53,7 @@ impl BgpMPUpdates {
     pub fn s4vpnv4m(nhop: BgpIPv4RD, nlri: Vec<Labeled<WithRd<BgpAddrV4>>>) -> BgpMPUpdates {
         BgpMPUpdates {
             nexthop: BgpAddr::V4RD(nhop),
+            link_local: None,
             addrs: BgpAddrs::VPNV4M(nlri),
         }
     }
@@ -40,6 +61,7 @@ impl BgpMPUpdates {
     pub fn s4ip4lu(nhop: std::net::Ipv4Addr, nlri: Vec<Labeled<BgpAddrV4>>) -> BgpMPUpdates {
         BgpMPUpdates {
             nexthop: BgpAddr::V4(nhop),
+            link_local: None,
             addrs: BgpAddrs::IPV4LU(nlri),
         }
     }
@@ -47,6 +69,7 @@ impl BgpMPUpdates {
     pub fn s4ip6lu(nhop: std::net::Ipv4Addr, nlri: Vec<Labeled<BgpAddrV6>>) -> BgpMPUpdates {
         BgpMPUpdates {
             nexthop: BgpAddr::V4(nhop),
+            link_local: None,
             addrs: BgpAddrs::IPV6LU(nlri),
         }
     }
@@ -54,6 +77,7 @@ impl BgpMPUpdates {
     pub fn s4vpnv6u(nhop: BgpIPv4RD, nlri: Vec<Labeled<WithRd<BgpAddrV6>>>) -> BgpMPUpdates {
         BgpMPUpdates {
             nexthop: BgpAddr::V4RD(nhop),
+            link_local: None,
             addrs: BgpAddrs::VPNV6U(nlri),
         }
     }
@@ -61,14 +85,122 @@ impl BgpMPUpdates {
     pub fn s4vpnv6m(nhop: BgpIPv4RD, nlri: Vec<Labeled<WithRd<BgpAddrV6>>>) -> BgpMPUpdates {
         BgpMPUpdates {
             nexthop: BgpAddr::V4RD(nhop),
+            link_local: None,
             addrs: BgpAddrs::VPNV6M(nlri),
         }
     }
+    /// Creates update for IPv6 unicast with ipv6 nexthop
+    pub fn s6ip6u(nhop: std::net::Ipv6Addr, nlri: Vec<BgpAddrV6>) -> BgpMPUpdates {
+        BgpMPUpdates {
+            nexthop: BgpAddr::V6(nhop),
+            link_local: None,
+            addrs: BgpAddrs::IPV6U(nlri),
+        }
+    }
+    /// Creates update for IPv4 MVPN
+    pub fn s4mvpn(nhop: std::net::Ipv4Addr, nlri: Vec<BgpMVPN>) -> BgpMPUpdates {
+        BgpMPUpdates {
+            nexthop: BgpAddr::V4(nhop),
+            link_local: None,
+            addrs: BgpAddrs::MVPN(nlri),
+        }
+    }
+    /// Creates update for EVPN with ipv4 nexthop
+    pub fn s4evpn(nhop: std::net::Ipv4Addr, nlri: Vec<BgpEVPN>) -> BgpMPUpdates {
+        BgpMPUpdates {
+            nexthop: BgpAddr::V4(nhop),
+            link_local: None,
+            addrs: BgpAddrs::EVPN(nlri),
+        }
+    }
+    /// Creates update for EVPN with ipv6 nexthop
+    pub fn s6evpn(nhop: std::net::Ipv6Addr, nlri: Vec<BgpEVPN>) -> BgpMPUpdates {
+        BgpMPUpdates {
+            nexthop: BgpAddr::V6(nhop),
+            link_local: None,
+            addrs: BgpAddrs::EVPN(nlri),
+        }
+    }
+    /// Creates update for IPv4 flowspec, without nexthop
+    pub fn fs4u(nlri: Vec<BgpFlowSpec<BgpAddrV4>>) -> BgpMPUpdates {
+        BgpMPUpdates {
+            nexthop: BgpAddr::None,
+            link_local: None,
+            addrs: BgpAddrs::FS4U(nlri),
+        }
+    }
+    /// Creates update for IPv6 flowspec, without nexthop
+    pub fn fs6u(nlri: Vec<BgpFlowSpec<FS6>>) -> BgpMPUpdates {
+        BgpMPUpdates {
+            nexthop: BgpAddr::None,
+            link_local: None,
+            addrs: BgpAddrs::FS6U(nlri),
+        }
+    }
+    /// Creates update for VPNv4 flowspec, without nexthop
+    pub fn fsv4u(nlri: Vec<BgpFlowSpec<FSV4U>>) -> BgpMPUpdates {
+        BgpMPUpdates {
+            nexthop: BgpAddr::None,
+            link_local: None,
+            addrs: BgpAddrs::FSV4U(nlri),
+        }
+    }
+    /// Sets plain nexthop (RD-prefixed with zero RD for VPN families), drops link-local one.
+    /// Address family is validated against NLRI afi/safi;
+    /// ipv4 nexthop for ipv6 labeled unicast is encoded as IPv4-mapped ipv6 address.
+    pub fn set_nexthop(&mut self, nh: std::net::IpAddr) -> Result<(), BgpError> {
+        let (afi, safi) = self.addrs.get_afi_safi();
+        if matches!(safi, 128 | 129) || (safi == 134 && afi == 1) {
+            return self.set_nexthop_rd(BgpRD::new(0, 0), nh);
+        }
+        self.nexthop = match (afi, safi, nh) {
+            (0, _, _) => return Err(BgpError::static_str("No NLRI to set nexthop for")),
+            (2, 4, std::net::IpAddr::V4(a)) => BgpAddr::V6(a.to_ipv6_mapped()),
+            (2, _, std::net::IpAddr::V4(_)) => {
+                return Err(BgpError::static_str("ipv4 nexthop for ipv6 NLRI"))
+            }
+            (_, _, std::net::IpAddr::V4(a)) => BgpAddr::V4(a),
+            (_, _, std::net::IpAddr::V6(a)) => BgpAddr::V6(a),
+        };
+        self.link_local = None;
+        Ok(())
+    }
+    /// Sets ipv6 nexthop with link-local address (32-byte form).
+    pub fn set_nexthop_ll(
+        &mut self,
+        nh: std::net::Ipv6Addr,
+        link_local: std::net::Ipv6Addr,
+    ) -> Result<(), BgpError> {
+        self.set_nexthop(std::net::IpAddr::V6(nh))?;
+        if let BgpAddr::V6(_) = self.nexthop {
+            self.link_local = Some(link_local);
+            Ok(())
+        } else {
+            Err(BgpError::static_str("Link-local nexthop for VPN NLRI"))
+        }
+    }
+    /// Sets RD-prefixed nexthop, valid for VPN families only.
+    pub fn set_nexthop_rd(&mut self, rd: BgpRD, nh: std::net::IpAddr) -> Result<(), BgpError> {
+        let (afi, safi) = self.addrs.get_afi_safi();
+        if !matches!(safi, 128 | 129 | 134) {
+            return Err(BgpError::from_string(format!(
+                "RD nexthop for non-VPN afi/safi {}/{}",
+                afi, safi
+            )));
+        }
+        self.nexthop = match nh {
+            std::net::IpAddr::V4(a) => BgpAddr::V4RD(BgpIPv4RD::new(rd, a)),
+            std::net::IpAddr::V6(a) => BgpAddr::V6RD(BgpIPv6RD { rd, addr: a }),
+        };
+        self.link_local = None;
+        Ok(())
+    }
     pub fn decode_from(peer: &BgpSessionParams, buf: &[u8]) -> Result<BgpMPUpdates, BgpError> {
         let afi = getn_u16(buf);
         let safi = buf[2];
         let mut curpos: usize = 4;
         let nh: BgpAddr;
+        let mut link_local = None;
         let nhlen = buf[3] as usize;
         match afi {
             1 => {
@@ -76,9 +208,13 @@ impl BgpMPUpdates {
                 match safi {
                     1 | 2 | 4 | 5 | 66 | 133 => {
                         //unicast|multicast|labeled unicast|mvpn|mdt|flow
-                        nh = BgpAddr::V4(decode_addrv4_from(&buf[curpos..(curpos + nhlen)])?);
+                        (nh, link_local) = decode_nexthop(slice(buf, curpos, curpos + nhlen)?)?;
                         curpos += nhlen;
                     }
+                    134 if nhlen == 0 => {
+                        //flow without nexthop
+                        nh = BgpAddr::None;
+                    }
                     128 | 129 | 134 => {
                         //vpnv4u|vpnv4m|flow
                         let r = BgpIPv4RD::decode_from(peer.peer_mode, &buf[curpos..])?;
@@ -97,9 +233,9 @@ impl BgpMPUpdates {
             2 => {
                 //ipv6
                 match safi {
-                    1 | 2 | 4 | 66 => {
-                        //unicast|multicast|labeled unicast|mdt
-                        nh = BgpAddr::V6(decode_addrv6_from(&buf[curpos..(curpos + nhlen)])?);
+                    1 | 2 | 4 | 66 | 133 => {
+                        //unicast|multicast|labeled unicast|mdt|flow
+                        (nh, link_local) = decode_nexthop(slice(buf, curpos, curpos + nhlen)?)?;
                         curpos += nhlen;
                     }
                     128 | 129 => {
@@ -121,7 +257,7 @@ impl BgpMPUpdates {
                 match safi {
                     65 | 70 => {
                         //vpls + evpn
-                        nh = BgpAddr::V4(decode_addrv4_from(&buf[curpos..])?);
+                        (nh, link_local) = decode_nexthop(slice(buf, curpos, curpos + nhlen)?)?;
                         curpos += nhlen;
                     }
                     n => {
@@ -143,6 +279,7 @@ impl BgpMPUpdates {
         let ap = BgpAddrs::decode_from(peer, afi, safi, &buf[curpos..])?;
         Ok(BgpMPUpdates {
             nexthop: nh,
+            link_local,
             addrs: ap.0,
         })
     }
@@ -152,6 +289,7 @@ impl std::fmt::Debug for BgpMPUpdates {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("BgpMPUpdates")
             .field("nexthop", &self.nexthop)
+            .field("link_local", &self.link_local)
             .field("addrs", &self.addrs)
             .finish()
     }
@@ -176,7 +314,13 @@ impl BgpAttr for BgpMPUpdates {
         let nhl = match &self.nexthop {
             BgpAddr::None => 0,
             BgpAddr::V4(a) => encode_addrv4_to(a, &mut buf[curpos..])?,
-            BgpAddr::V6(a) => encode_addrv6_to(a, &mut buf[curpos..])?,
+            BgpAddr::V6(a) => match self.link_local {
+                None => encode_addrv6_to(a, &mut buf[curpos..])?,
+                Some(ll) => {
+                    encode_addrv6_to(a, &mut buf[curpos..])?
+                        + encode_addrv6_to(&ll, &mut buf[curpos + 16..])?
+                }
+            },
             BgpAddr::V4RD(a) => a.encode_to(peer.peer_mode, &mut buf[curpos..])?,
             BgpAddr::V6RD(a) => a.encode_to(peer.peer_mode, &mut buf[curpos..])?,
             _ => return Err(BgpError::static_str("Invalid nexthop kind")),
@@ -236,3 +380,72 @@ impl BgpAttr for BgpMPWithdraws {
         Ok(curpos)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mp_constructors() {
+        let params = BgpSessionParams::new(
+            Asn(64512),
+            180,
+            BgpTransportMode::IPv6,
+            std::net::Ipv4Addr::new(1, 1, 1, 1),
+            vec![BgpCapability::SafiIPv6u],
+        );
+        let mut buf = vec![0_u8; 4096];
+        let upd = BgpMPUpdates::s6ip6u(
+            "2001:db8::1".parse().unwrap(),
+            vec![BgpAddrV6::new("2001:db8:1::".parse().unwrap(), 48)],
+        );
+        let len = upd.encode_to(&params, &mut buf).unwrap();
+        assert_eq!(buf[3], 16);
+        assert_eq!(
+            BgpMPUpdates::decode_from(&params, &buf[..len]).unwrap(),
+            upd
+        );
+        let upd = BgpMPUpdates::fs4u(vec![]);
+        let len = upd.encode_to(&params, &mut buf).unwrap();
+        assert_eq!(buf[0..5], [0, 1, 133, 0, 0]);
+        assert_eq!(
+            BgpMPUpdates::decode_from(&params, &buf[..len]).unwrap(),
+            upd
+        );
+    }
+    #[test]
+    fn test_mp_set_nexthop() {
+        let params = BgpSessionParams::new(
+            Asn(64512),
+            180,
+            BgpTransportMode::IPv6,
+            std::net::Ipv4Addr::new(1, 1, 1, 1),
+            vec![BgpCapability::SafiIPv6u],
+        );
+        let mut upd = BgpMPUpdates::s6ip6u(
+            "2001:db8::1".parse().unwrap(),
+            vec![BgpAddrV6::new("2001:db8:1::".parse().unwrap(), 48)],
+        );
+        assert!(upd.set_nexthop("10.0.0.1".parse().unwrap()).is_err());
+        upd.set_nexthop_ll("2001:db8::2".parse().unwrap(), "fe80::2".parse().unwrap())
+            .unwrap();
+        let mut buf = vec![0_u8; 4096];
+        let len = upd.encode_to(&params, &mut buf).unwrap();
+        assert_eq!(buf[3], 32);
+        let dec = BgpMPUpdates::decode_from(&params, &buf[..len]).unwrap();
+        assert_eq!(dec.nexthop, BgpAddr::V6("2001:db8::2".parse().unwrap()));
+        assert_eq!(dec.link_local, Some("fe80::2".parse().unwrap()));
+        assert_eq!(dec, upd);
+        let mut vpn = BgpMPUpdates::s4vpnv4u(
+            BgpIPv4RD::new(BgpRD::new(0, 0), "10.0.0.1".parse().unwrap()),
+            vec![],
+        );
+        vpn.set_nexthop("10.0.0.2".parse().unwrap()).unwrap();
+        assert!(
+            matches!(vpn.nexthop, BgpAddr::V4RD(ref n) if n.addr == std::net::Ipv4Addr::new(10, 0, 0, 2))
+        );
+        assert!(upd
+            .set_nexthop_rd(BgpRD::new(0, 0), "10.0.0.1".parse().unwrap())
+            .is_err());
+    }
+}
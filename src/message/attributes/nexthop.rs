@@ -11,6 +11,7 @@
 use crate::message::attributes::*;
 #[cfg(feature = "serialization")]
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
 /// BGP nexthop
 #[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
@@ -25,18 +26,50 @@ impl BgpNextHop {
     pub fn new(v: std::net::IpAddr) -> BgpNextHop {
         BgpNextHop { value: v }
     }
-    pub fn decode_from(peer: &BgpSessionParams, buf: &[u8]) -> Result<BgpNextHop, BgpError> {
-        if peer.peer_mode == BgpTransportMode::IPv6 && buf.len() >= 16 {
-            return Ok(BgpNextHop {
+    /// Decodes nexthop, address family is selected by attribute length (4 or 16 bytes).
+    pub fn decode_from(_peer: &BgpSessionParams, buf: &[u8]) -> Result<BgpNextHop, BgpError> {
+        match buf.len() {
+            4 => Ok(BgpNextHop {
+                value: std::net::IpAddr::V4(decode_addrv4_from(buf)?),
+            }),
+            16 | 32 => Ok(BgpNextHop {
                 value: std::net::IpAddr::V6(decode_addrv6_from(buf)?),
-            });
+            }),
+            _ => Err(BgpError::static_str("Invalid nexthop length")),
         }
-        if peer.peer_mode == BgpTransportMode::IPv4 && buf.len() >= 4 {
-            return Ok(BgpNextHop {
-                value: std::net::IpAddr::V4(decode_addrv4_from(buf)?),
-            });
+    }
+    /// Checks that nexthop family matches session transport.
+    pub fn check_transport(&self, peer: &BgpSessionParams) -> Result<(), BgpError> {
+        if BgpTransportMode::from(self.value) == peer.peer_mode {
+            Ok(())
+        } else {
+            Err(BgpError::from_string(format!(
+                "Nexthop {} does not match session transport {:?}",
+                self.value, peer.peer_mode
+            )))
         }
-        Err(BgpError::static_str("Invalid nexthop length"))
+    }
+}
+impl From<std::net::IpAddr> for BgpNextHop {
+    fn from(v: std::net::IpAddr) -> Self {
+        BgpNextHop::new(v)
+    }
+}
+impl From<std::net::Ipv4Addr> for BgpNextHop {
+    fn from(v: std::net::Ipv4Addr) -> Self {
+        BgpNextHop::new(std::net::IpAddr::V4(v))
+    }
+}
+impl From<std::net::Ipv6Addr> for BgpNextHop {
+    fn from(v: std::net::Ipv6Addr) -> Self {
+        BgpNextHop::new(std::net::IpAddr::V6(v))
+    }
+}
+impl FromStr for BgpNextHop {
+    type Err = std::net::AddrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(BgpNextHop::new(s.trim().parse()?))
     }
 }
 impl std::fmt::Debug for BgpNextHop {
@@ -59,16 +92,37 @@ impl BgpAttr for BgpNextHop {
         }
     }
     fn encode_to(&self, peer: &BgpSessionParams, buf: &mut [u8]) -> Result<usize, BgpError> {
-        if let std::net::IpAddr::V6(v) = self.value {
-            if peer.peer_mode == BgpTransportMode::IPv6 && buf.len() >= 16 {
-                return encode_addrv6_to(&v, buf);
-            }
-        };
-        if let std::net::IpAddr::V4(v) = self.value {
-            if peer.peer_mode == BgpTransportMode::IPv4 && buf.len() >= 4 {
-                return encode_addrv4_to(&v, buf);
-            }
-        };
-        Err(BgpError::static_str("Invalid nexthop type"))
+        self.check_transport(peer)?;
+        match self.value {
+            std::net::IpAddr::V4(v) => encode_addrv4_to(&v, buf),
+            std::net::IpAddr::V6(v) => encode_addrv6_to(&v, buf),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nexthop() {
+        let mut params = BgpSessionParams::new(
+            Asn(64512),
+            180,
+            BgpTransportMode::IPv4,
+            std::net::Ipv4Addr::new(1, 1, 1, 1),
+            vec![BgpCapability::SafiIPv4u],
+        );
+        let nh: BgpNextHop = "10.0.0.1".parse().unwrap();
+        assert_eq!(nh, BgpNextHop::from(std::net::Ipv4Addr::new(10, 0, 0, 1)));
+        let mut buf = [0_u8; 16];
+        assert_eq!(nh.encode_to(&params, &mut buf).unwrap(), 4);
+        assert_eq!(BgpNextHop::decode_from(&params, &buf[..4]).unwrap(), nh);
+        let nh6: BgpNextHop = "2001:db8::1".parse().unwrap();
+        assert!(nh6.encode_to(&params, &mut buf).is_err());
+        params.peer_mode = BgpTransportMode::IPv6;
+        assert_eq!(nh6.encode_to(&params, &mut buf).unwrap(), 16);
+        assert_eq!(BgpNextHop::decode_from(&params, &buf).unwrap(), nh6);
+        assert!(BgpNextHop::decode_from(&params, &buf[..5]).is_err());
     }
 }
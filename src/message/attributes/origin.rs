@@ -30,6 +30,19 @@ impl std::fmt::Display for BgpAttrOrigin {
         }
     }
 }
+impl std::str::FromStr for BgpAttrOrigin {
+    type Err = BgpError;
+
+    /// Parses "igp", "egp", "incomplete" or "i", "e", "?" case-insensitively.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "igp" | "i" => Ok(BgpAttrOrigin::Igp),
+            "egp" | "e" => Ok(BgpAttrOrigin::Egp),
+            "incomplete" | "?" => Ok(BgpAttrOrigin::Incomplete),
+            _ => Err(BgpError::from_string(format!("Invalid origin: {}", s))),
+        }
+    }
+}
 
 /// BGP origin path attribute
 #[derive(Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
@@ -84,6 +97,19 @@ impl std::fmt::Display for BgpOrigin {
         }
     }
 }
+impl std::str::FromStr for BgpOrigin {
+    type Err = BgpError;
+
+    /// Parses origin value, optionally prefixed with "origin" as in Display output.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let v = match s.get(..6) {
+            Some(p) if p.eq_ignore_ascii_case("origin") => &s[6..],
+            _ => s,
+        };
+        Ok(BgpOrigin::new(v.parse()?))
+    }
+}
 
 impl BgpAttr for BgpOrigin {
     fn attr(&self) -> BgpAttrParams {
@@ -113,3 +139,28 @@ impl BgpAttr for BgpOrigin {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_origin_from_str() {
+        assert_eq!("igp".parse::<BgpAttrOrigin>().unwrap(), BgpAttrOrigin::Igp);
+        assert_eq!("E".parse::<BgpAttrOrigin>().unwrap(), BgpAttrOrigin::Egp);
+        assert_eq!(
+            "?".parse::<BgpOrigin>().unwrap(),
+            BgpOrigin::new(BgpAttrOrigin::Incomplete)
+        );
+        for v in [
+            BgpAttrOrigin::Igp,
+            BgpAttrOrigin::Egp,
+            BgpAttrOrigin::Incomplete,
+        ] {
+            assert_eq!(v.to_string().parse::<BgpAttrOrigin>().unwrap(), v);
+            let o = BgpOrigin::new(v);
+            assert_eq!(o.to_string().parse::<BgpOrigin>().unwrap(), o);
+        }
+        assert!("unknown".parse::<BgpOrigin>().is_err());
+    }
+}
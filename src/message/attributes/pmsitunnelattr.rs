@@ -43,13 +43,22 @@ impl std::fmt::Display for BgpPMSITaIngressRepl {
         write!(f, "Ingress replication:{}", self.endpoint)
     }
 }
-#[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
 #[cfg(feature = "serialization")]
 #[derive(Serialize, Deserialize)]
 pub struct BgpPMSITaMLDP {
     pub rootnode: std::net::IpAddr,
+    /// raw opaque value TLVs
     pub opaque: Vec<u8>,
 }
+impl std::fmt::Debug for BgpPMSITaMLDP {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BgpPMSITaMLDP")
+            .field("rootnode", &self.rootnode)
+            .field("opaque", &HexDump(&self.opaque))
+            .finish()
+    }
+}
 impl std::fmt::Display for BgpPMSITaMLDP {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "mLDP P2MP LSP:{}", self.rootnode)
@@ -49,7 +49,7 @@ impl std::fmt::Debug for BgpAttrUnknown {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("BgpAttrUnknown")
             .field("params", &self.params)
-            .field("value", &self.value)
+            .field("value", &HexDump(&self.value))
             .finish()
     }
 }
@@ -74,3 +74,21 @@ impl BgpAttr for BgpAttrUnknown {
         Ok(self.value.len())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_alternate_debug() {
+        let attr = BgpAttrUnknown::decode_from(99, 192, &(0..20).collect::<Vec<u8>>()).unwrap();
+        assert_eq!(
+            format!("{:?}", attr),
+            "BgpAttrUnknown { params: BgpAttrParams { typecode: 99, flags: 192 }, value: [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19] }"
+        );
+        let dump = format!("{:#?}", attr);
+        assert!(dump
+            .contains("0000: 00 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f  |................|"));
+        assert!(dump.contains("0010: 10 11 12 13                                      |....|"));
+    }
+}
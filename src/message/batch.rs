@@ -0,0 +1,172 @@
+// Copyright 2021 Vladimir Melnikov.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! This module contains message batch for vectored writes
+
+use crate::message::{BgpAnyMessage, BgpMessageType};
+use crate::*;
+use std::io::IoSlice;
+
+/// Batch of encoded BGP messages with pooled buffers.
+///
+/// Buffers are kept between sends, so steady update generation does not allocate.
+#[derive(Debug, Default)]
+pub struct BgpMessageBatch {
+    bufs: Vec<Vec<u8>>,
+    lens: Vec<usize>,
+}
+
+impl BgpMessageBatch {
+    pub fn new() -> BgpMessageBatch {
+        BgpMessageBatch {
+            bufs: Vec::new(),
+            lens: Vec::new(),
+        }
+    }
+    /// Number of messages in batch.
+    pub fn len(&self) -> usize {
+        self.lens.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.lens.is_empty()
+    }
+    /// Total bytes in batch.
+    pub fn bytes_len(&self) -> usize {
+        self.lens.iter().sum()
+    }
+    /// Drops messages, keeping buffers for reuse.
+    pub fn clear(&mut self) {
+        self.lens.clear();
+    }
+    /// Encodes message with head into the next pooled buffer.
+    pub fn push(
+        &mut self,
+        peer: &BgpSessionParams,
+        messagetype: BgpMessageType,
+        msg: &impl BgpMessage,
+    ) -> Result<(), BgpError> {
+        let idx = self.lens.len();
+        if self.bufs.len() <= idx {
+            self.bufs.push(vec![0_u8; 4096]);
+        }
+        let buf = &mut self.bufs[idx];
+        let messagelen = msg.encode_to(peer, &mut buf[19..])?;
+        let lng = peer.prepare_message_buf(buf, messagetype, messagelen)?;
+        self.lens.push(lng);
+        Ok(())
+    }
+    /// Encodes any message with head into the next pooled buffer.
+    pub fn push_any(
+        &mut self,
+        peer: &BgpSessionParams,
+        msg: &BgpAnyMessage,
+    ) -> Result<(), BgpError> {
+        match msg {
+            BgpAnyMessage::Open(m) => self.push(peer, BgpMessageType::Open, m),
+            BgpAnyMessage::Update(m) => self.push(peer, BgpMessageType::Update, m),
+            BgpAnyMessage::Notification(m) => self.push(peer, BgpMessageType::Notification, m),
+            BgpAnyMessage::Keepalive(m) => self.push(peer, BgpMessageType::Keepalive, m),
+            BgpAnyMessage::Raw(m) => self.push(peer, BgpMessageType::Other(m.msgtype), m),
+        }
+    }
+    /// Returns encoded messages as IoSlice list.
+    pub fn io_slices(&self) -> Vec<IoSlice<'_>> {
+        self.lens
+            .iter()
+            .zip(self.bufs.iter())
+            .map(|(l, b)| IoSlice::new(&b[0..*l]))
+            .collect()
+    }
+    /// Writes all messages with write_vectored, handling partial writes, and clears batch.
+    pub fn write_to(&mut self, wrdst: &mut impl std::io::Write) -> Result<usize, BgpError> {
+        let total = self.bytes_len();
+        let mut written = 0;
+        while written < total {
+            let mut skip = written;
+            let mut slices = Vec::with_capacity(self.lens.len());
+            for (l, b) in self.lens.iter().zip(self.bufs.iter()) {
+                if skip >= *l {
+                    skip -= *l;
+                    continue;
+                }
+                slices.push(IoSlice::new(&b[skip..*l]));
+                skip = 0;
+            }
+            match wrdst.write_vectored(&slices) {
+                Ok(0) => {
+                    return Err(std::io::Error::from(std::io::ErrorKind::WriteZero).into());
+                }
+                Ok(n) => written += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        self.clear();
+        Ok(total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::keepalive::BgpKeepaliveMessage;
+
+    /// Writer accepting at most 7 bytes per call, vectored writes may span slices
+    struct SlowWriter(Vec<u8>, usize);
+    impl std::io::Write for SlowWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(7);
+            self.0.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+        fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+            self.1 += 1;
+            let mut left = 7;
+            for b in bufs {
+                let n = b.len().min(left);
+                self.0.extend_from_slice(&b[..n]);
+                left -= n;
+                if left == 0 {
+                    break;
+                }
+            }
+            Ok(7 - left)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_message_batch() {
+        let params = BgpSessionParams::new(
+            Asn(64512),
+            180,
+            BgpTransportMode::IPv4,
+            std::net::Ipv4Addr::new(1, 1, 1, 1),
+            vec![BgpCapability::SafiIPv4u],
+        );
+        let mut batch = BgpMessageBatch::new();
+        batch
+            .push(&params, BgpMessageType::Keepalive, &BgpKeepaliveMessage {})
+            .unwrap();
+        batch
+            .push_any(&params, &BgpAnyMessage::Open(params.open_message()))
+            .unwrap();
+        assert_eq!(batch.len(), 2);
+        assert_eq!(batch.io_slices()[0].len(), 19);
+        let total = batch.bytes_len();
+        let mut wr = SlowWriter(Vec::new(), 0);
+        assert_eq!(batch.write_to(&mut wr).unwrap(), total);
+        assert!(batch.is_empty());
+        assert_eq!(wr.0.len(), total);
+        assert_eq!(wr.1, total.div_ceil(7));
+        assert_eq!(wr.0[18], 4);
+        assert_eq!(wr.0[19 + 18], 1);
+    }
+}
@@ -13,7 +13,8 @@ use crate::*;
 pub struct BgpKeepaliveMessage {}
 
 impl BgpMessage for BgpKeepaliveMessage {
-    fn decode_from(&mut self, _peer: &BgpSessionParams, _buf: &[u8]) -> Result<(), BgpError> {
+    fn decode_from(&mut self, peer: &BgpSessionParams, _buf: &[u8]) -> Result<(), BgpError> {
+        peer.record_decode(BgpDecodeCategory::Message(4), &Ok(()));
         Ok(())
     }
     fn encode_to(&self, _peer: &BgpSessionParams, _buf: &mut [u8]) -> Result<usize, BgpError> {
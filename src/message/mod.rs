@@ -9,13 +9,17 @@
 //! This module contains BGP messages
 
 use crate::error::*;
+use crate::BgpMessage as _;
 use crate::*;
 
 pub mod attributes;
+pub mod batch;
 pub mod keepalive;
 pub mod notification;
 pub mod open;
 pub use open::*;
+pub mod raw;
+pub mod stream;
 pub mod update;
 pub use update::*;
 
@@ -25,13 +29,15 @@ pub trait BgpMessage {
     fn encode_to(&self, peer: &BgpSessionParams, buf: &mut [u8]) -> Result<usize, BgpError>;
 }
 
-/// Bgp message type: open, update, notification or keepalive.
+/// Bgp message type: open, update, notification, keepalive or any other type code.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum BgpMessageType {
     Open,
     Update,
     Notification,
     Keepalive,
+    /// message type not interpreted by this library (route refresh, experimental)
+    Other(u8),
 }
 
 impl BgpMessageType {
@@ -42,7 +48,8 @@ impl BgpMessageType {
             2 => Ok(BgpMessageType::Update),
             3 => Ok(BgpMessageType::Notification),
             4 => Ok(BgpMessageType::Keepalive),
-            _ => Err(BgpError::static_str("Invalid message type")),
+            0 => Err(BgpError::static_str("Invalid message type")),
+            n => Ok(BgpMessageType::Other(n)),
         }
     }
     /// encodes BGP message type into the byte code
@@ -52,6 +59,178 @@ impl BgpMessageType {
             BgpMessageType::Update => 2,
             BgpMessageType::Notification => 3,
             BgpMessageType::Keepalive => 4,
+            BgpMessageType::Other(n) => *n,
         }
     }
 }
+
+/// Maximum BGP message length, including head, without Extended Message capability (RFC 4271).
+pub const BGP_MAX_MESSAGE_LEN: usize = 4096;
+/// Maximum BGP message length, including head, with Extended Message capability (RFC 8654).
+pub const BGP_MAX_EXT_MESSAGE_LEN: usize = 65535;
+
+/// BGP message head (marker, length and type).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BgpMessageHead {
+    /// message type
+    pub msgtype: BgpMessageType,
+    /// total message length, including 19-byte head
+    pub total_len: usize,
+    /// message body length
+    pub body_len: usize,
+}
+
+impl BgpMessageHead {
+    /// constructs head for message body of given length
+    pub fn new(msgtype: BgpMessageType, body_len: usize) -> BgpMessageHead {
+        BgpMessageHead {
+            msgtype,
+            total_len: body_len + 19,
+            body_len,
+        }
+    }
+    /// returns minimum total message length for message type
+    pub fn min_len(msgtype: &BgpMessageType) -> usize {
+        match msgtype {
+            BgpMessageType::Open => 29,
+            BgpMessageType::Update => 23,
+            BgpMessageType::Notification => 21,
+            BgpMessageType::Keepalive => 19,
+            BgpMessageType::Other(_) => 19,
+        }
+    }
+    /// checks message length against message type,
+    /// returns error with Message Header Error/Bad Message Length notification.
+    pub fn validate(&self) -> Result<(), BgpError> {
+        let valid = match self.msgtype {
+            BgpMessageType::Keepalive => self.total_len == 19,
+            _ => self.total_len >= BgpMessageHead::min_len(&self.msgtype),
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(self.bad_length())
+        }
+    }
+    /// checks message length against maximum allowed on the session,
+    /// returns error with Message Header Error/Bad Message Length notification.
+    pub fn check_max_len(&self, max_len: usize) -> Result<(), BgpError> {
+        if self.total_len <= max_len {
+            Ok(())
+        } else {
+            Err(self.bad_length())
+        }
+    }
+    fn bad_length(&self) -> BgpError {
+        let mut n = notification::BgpNotificationMessage::from_code(1, 2);
+        n.set_data_u16(self.total_len as u16);
+        BgpError::notification(n)
+    }
+}
+
+/// Any decoded BGP message
+#[derive(Debug)]
+pub enum BgpAnyMessage {
+    Open(BgpOpenMessage),
+    Update(BgpUpdateMessage),
+    Notification(notification::BgpNotificationMessage),
+    Keepalive(keepalive::BgpKeepaliveMessage),
+    Raw(raw::BgpRawMessage),
+}
+
+impl BgpAnyMessage {
+    /// decodes message body of given type
+    pub fn decode_from(
+        peer: &BgpSessionParams,
+        msgtype: BgpMessageType,
+        buf: &[u8],
+    ) -> Result<BgpAnyMessage, BgpError> {
+        Ok(match msgtype {
+            BgpMessageType::Open => {
+                let mut msg = BgpOpenMessage::new();
+                msg.decode_from(peer, buf)?;
+                BgpAnyMessage::Open(msg)
+            }
+            BgpMessageType::Update => {
+                let mut msg = BgpUpdateMessage::new();
+                msg.decode_from(peer, buf)?;
+                BgpAnyMessage::Update(msg)
+            }
+            BgpMessageType::Notification => {
+                let mut msg = notification::BgpNotificationMessage::new();
+                msg.decode_from(peer, buf)?;
+                BgpAnyMessage::Notification(msg)
+            }
+            BgpMessageType::Keepalive => {
+                let mut msg = keepalive::BgpKeepaliveMessage {};
+                msg.decode_from(peer, buf)?;
+                BgpAnyMessage::Keepalive(msg)
+            }
+            BgpMessageType::Other(n) => {
+                let mut msg = raw::BgpRawMessage::new(n);
+                msg.decode_from(peer, buf)?;
+                BgpAnyMessage::Raw(msg)
+            }
+        })
+    }
+    /// encodes message body
+    pub fn encode_to(&self, peer: &BgpSessionParams, buf: &mut [u8]) -> Result<usize, BgpError> {
+        match self {
+            BgpAnyMessage::Open(msg) => msg.encode_to(peer, buf),
+            BgpAnyMessage::Update(msg) => msg.encode_to(peer, buf),
+            BgpAnyMessage::Notification(msg) => msg.encode_to(peer, buf),
+            BgpAnyMessage::Keepalive(msg) => msg.encode_to(peer, buf),
+            BgpAnyMessage::Raw(msg) => msg.encode_to(peer, buf),
+        }
+    }
+    /// returns message type
+    pub fn msgtype(&self) -> BgpMessageType {
+        match self {
+            BgpAnyMessage::Open(_) => BgpMessageType::Open,
+            BgpAnyMessage::Update(_) => BgpMessageType::Update,
+            BgpAnyMessage::Notification(_) => BgpMessageType::Notification,
+            BgpAnyMessage::Keepalive(_) => BgpMessageType::Keepalive,
+            BgpAnyMessage::Raw(msg) => BgpMessageType::Other(msg.msgtype),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_head_validate() {
+        let bad_len = |h: BgpMessageHead| {
+            h.validate()
+                .unwrap_err()
+                .get_notification()
+                .map(|n| (n.error_code, n.error_subcode, n.data_u16()))
+        };
+        assert!(BgpMessageHead::new(BgpMessageType::Keepalive, 0)
+            .validate()
+            .is_ok());
+        assert_eq!(
+            bad_len(BgpMessageHead::new(BgpMessageType::Keepalive, 1)),
+            Some((1, 2, 20))
+        );
+        assert_eq!(
+            bad_len(BgpMessageHead::new(BgpMessageType::Open, 9)),
+            Some((1, 2, 28))
+        );
+        assert!(BgpMessageHead::new(BgpMessageType::Update, 4)
+            .validate()
+            .is_ok());
+        let long = BgpMessageHead::new(BgpMessageType::Update, BGP_MAX_MESSAGE_LEN - 18);
+        assert!(long.validate().is_ok());
+        assert!(long.check_max_len(BGP_MAX_EXT_MESSAGE_LEN).is_ok());
+        assert_eq!(
+            long.check_max_len(BGP_MAX_MESSAGE_LEN)
+                .unwrap_err()
+                .get_notification()
+                .map(|n| (n.error_code, n.error_subcode, n.data_u16())),
+            Some((1, 2, 4097))
+        );
+        assert!(BgpMessageType::decode_from(0).is_err());
+    }
+}
@@ -6,16 +6,17 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use crate::{BgpError, BgpMessage, BgpSessionParams};
+use crate::{BgpCapability, BgpDecodeCategory, BgpError, BgpMessage, BgpSessionParams, HexDump};
 
 /// BGP notification message
+#[derive(Clone)]
 pub struct BgpNotificationMessage {
     /// error code
     pub error_code: u8,
     /// error sub-code
     pub error_subcode: u8,
-    /// extra data
-    pub data: u16,
+    /// data field
+    pub data: Vec<u8>,
 }
 impl BgpNotificationMessage {
     /// constructs new empty message
@@ -23,9 +24,57 @@ impl BgpNotificationMessage {
         BgpNotificationMessage {
             error_code: 0,
             error_subcode: 0,
-            data: 0,
+            data: Vec::new(),
         }
     }
+    /// returns data field as 16-bit number, as used by Bad Message Length notification
+    pub fn data_u16(&self) -> u16 {
+        match self.data.len() {
+            0 => 0,
+            1 => self.data[0] as u16,
+            _ => ((self.data[0] as u16) << 8) | (self.data[1] as u16),
+        }
+    }
+    /// sets data field to 16-bit number
+    pub fn set_data_u16(&mut self, v: u16) {
+        self.data = v.to_be_bytes().to_vec();
+    }
+    /// constructs notification with given error code and subcode
+    pub fn from_code(code: u8, subcode: u8) -> BgpNotificationMessage {
+        let mut ret = BgpNotificationMessage::new();
+        ret.error_code = code;
+        ret.error_subcode = subcode;
+        ret
+    }
+    /// constructs OPEN Message Error/Unsupported Capability notification
+    /// with capabilities listed in data field (RFC 5492).
+    pub fn unsupported_capability(
+        caps: &[BgpCapability],
+    ) -> Result<BgpNotificationMessage, BgpError> {
+        let mut ret = BgpNotificationMessage::from_code(2, 7);
+        for cap in caps.iter() {
+            let pos = ret.data.len();
+            ret.data.resize(pos + cap.bytes_len(), 0);
+            cap.fill_buffer(&mut ret.data[pos..])?;
+        }
+        Ok(ret)
+    }
+    /// returns capabilities listed in data field of Unsupported Capability notification.
+    pub fn get_capabilities(&self) -> Result<Vec<BgpCapability>, BgpError> {
+        let mut ret = Vec::new();
+        if self.error_code != 2 || self.error_subcode != 7 {
+            return Ok(ret);
+        }
+        let mut pos = 0;
+        while pos < self.data.len() {
+            let cap = BgpCapability::from_buffer(&self.data[pos..])?;
+            if let Ok(c) = cap.0 {
+                ret.push(c);
+            }
+            pos += cap.1;
+        }
+        Ok(ret)
+    }
     /// returns human-friendly error interpretation.
     pub fn error_text(&self) -> String {
         match self.error_code {
@@ -112,7 +161,7 @@ impl std::fmt::Debug for BgpNotificationMessage {
         f.debug_struct("BgpNotificationMessage")
             .field("error_code", &self.error_code)
             .field("error_subcode", &self.error_subcode)
-            .field("data", &self.data)
+            .field("data", &HexDump(&self.data))
             .finish()
     }
 }
@@ -128,34 +177,77 @@ impl std::fmt::Display for BgpNotificationMessage {
         )
     }
 }
+impl std::error::Error for BgpNotificationMessage {}
 impl Default for BgpNotificationMessage {
     fn default() -> Self {
         Self::new()
     }
 }
 impl BgpMessage for BgpNotificationMessage {
-    fn decode_from(&mut self, _peer: &BgpSessionParams, buf: &[u8]) -> Result<(), BgpError> {
+    fn decode_from(&mut self, peer: &BgpSessionParams, buf: &[u8]) -> Result<(), BgpError> {
         if buf.len() < 2 {
-            return Err(BgpError::static_str("Invalid notification message length"));
+            let r = Err(BgpError::static_str("Invalid notification message length"));
+            peer.record_decode(BgpDecodeCategory::Message(3), &r);
+            return r;
         }
         self.error_code = buf[0];
         self.error_subcode = buf[1];
-        if buf.len() == 3 {
-            self.data = buf[2] as u16;
-        }
-        if buf.len() > 3 {
-            self.data = ((buf[2] as u16) << 8) | (buf[3] as u16);
-        }
+        self.data = buf[2..].to_vec();
+        peer.record_decode(BgpDecodeCategory::Message(3), &Ok(()));
         Ok(())
     }
     fn encode_to(&self, _peer: &BgpSessionParams, buf: &mut [u8]) -> Result<usize, BgpError> {
-        if buf.len() < 4 {
+        if buf.len() < 2 + self.data.len() {
             return Err(BgpError::static_str("Invalid notification message length"));
         }
         buf[0] = self.error_code;
         buf[1] = self.error_subcode;
-        buf[2] = (self.data >> 8) as u8;
-        buf[3] = (self.data & 0xff) as u8;
-        Ok(4)
+        buf[2..2 + self.data.len()].copy_from_slice(&self.data);
+        Ok(2 + self.data.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Asn, BgpCapAddPath, BgpTransportMode};
+
+    #[test]
+    fn test_unsupported_capability() {
+        let params = BgpSessionParams::new(
+            Asn(64512),
+            180,
+            BgpTransportMode::IPv4,
+            std::net::Ipv4Addr::new(1, 1, 1, 1),
+            vec![
+                BgpCapability::SafiIPv4u,
+                BgpCapability::SafiVPNv4u,
+                BgpCapability::CapAddPath(vec![BgpCapAddPath {
+                    afi: 1,
+                    safi: 1,
+                    send: true,
+                    receive: true,
+                }]),
+            ],
+        );
+        let unmatched = params.unmatched_caps(&[BgpCapability::SafiIPv4u]);
+        assert_eq!(unmatched.len(), 2);
+        let msg = BgpNotificationMessage::unsupported_capability(&unmatched).unwrap();
+        let mut buf = [0_u8; 64];
+        let len = msg.encode_to(&params, &mut buf).unwrap();
+        assert_eq!(&buf[0..8], &[2, 7, 1, 4, 0, 1, 0, 128]);
+        let mut dec = BgpNotificationMessage::new();
+        dec.decode_from(&params, &buf[..len]).unwrap();
+        assert_eq!(dec.get_capabilities().unwrap(), unmatched);
+        let mut msg = BgpNotificationMessage::from_code(1, 2);
+        msg.set_data_u16(20);
+        let len = msg.encode_to(&params, &mut buf).unwrap();
+        assert_eq!(&buf[..len], &[1, 2, 0, 20]);
+        dec.decode_from(&params, &buf[..len]).unwrap();
+        assert_eq!(dec.data_u16(), 20);
+        let len = BgpNotificationMessage::from_code(6, 2)
+            .encode_to(&params, &mut buf)
+            .unwrap();
+        assert_eq!(&buf[..len], &[6, 2]);
     }
 }
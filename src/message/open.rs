@@ -6,13 +6,18 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use crate::{ntoh16, slice, slice_mut, BgpCapability, BgpError, BgpMessage, BgpSessionParams};
+use crate::message::notification::BgpNotificationMessage;
+use crate::{
+    add_capability_to, families_in, find_addpath_in, getn_u16, remove_capability_from, setn_u16,
+    slice, slice_mut, Asn, BgpCapAddPath, BgpCapability, BgpDecodeCategory, BgpError, BgpMessage,
+    BgpNegotiatedFamily, BgpSessionParams, HexDump,
+};
 use std::vec::Vec;
 /// BGP open message
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct BgpOpenMessage {
     /// Autonomous system number
-    pub as_num: u32,
+    pub as_num: Asn,
     /// Hold time in seconds
     pub hold_time: u16,
     /// router Id
@@ -21,33 +26,59 @@ pub struct BgpOpenMessage {
     pub caps: Vec<BgpCapability>,
 }
 
-#[repr(C, packed)]
-struct BgpOpenHead {
-    as_num: u16,
-    hold_time: u16,
-    routerid: [u8; 4],
-    caplen: u8,
-}
-
 impl BgpMessage for BgpOpenMessage {
-    fn decode_from(&mut self, _peer: &BgpSessionParams, buf: &[u8]) -> Result<(), BgpError> {
+    fn decode_from(&mut self, peer: &BgpSessionParams, buf: &[u8]) -> Result<(), BgpError> {
+        let r = self.decode_body(peer, buf);
+        peer.record_decode(BgpDecodeCategory::Message(1), &r);
+        r
+    }
+    fn encode_to(&self, _peer: &BgpSessionParams, buf: &mut [u8]) -> Result<usize, BgpError> {
+        if buf.len() < 10 {
+            return Err(BgpError::InsufficientBufferSize);
+        }
+        buf[0] = 4;
+        setn_u16(self.as_num.to_u16_or_trans(), &mut buf[1..3]);
+        setn_u16(self.hold_time, &mut buf[3..5]);
+        buf[5..9].copy_from_slice(&self.router_id.octets());
+        buf[9] = self
+            .caps
+            .iter()
+            .fold(0u32, |sum, i| sum + (i.bytes_len() as u32) + 2) as u8;
+        let mut pos: usize = 10;
+        for cp in self.caps.iter() {
+            let caplen = cp.bytes_len();
+            let capbuf = slice_mut(buf, pos, caplen + pos + 2)?;
+            capbuf[0] = 2; //capability
+            capbuf[1] = caplen as u8;
+            cp.fill_buffer(&mut capbuf[2..])?;
+            pos += 2 + caplen;
+        }
+        Ok(pos)
+    }
+}
+impl BgpOpenMessage {
+    fn decode_body(&mut self, peer: &BgpSessionParams, buf: &[u8]) -> Result<(), BgpError> {
         if buf.len() < 10 {
             return Err(BgpError::InsufficientBufferSize);
         }
         if buf[0] != 4 {
             return Err(BgpError::static_str("Invalid BGP version <> 4"));
         }
-        let ptr: *const u8 = slice(buf, 1, buf.len())?.as_ptr();
-        let ptr: *const BgpOpenHead = ptr as *const BgpOpenHead;
-        let ptr: &BgpOpenHead = unsafe { &*ptr };
-        self.as_num = ntoh16(ptr.as_num) as u32;
-        self.hold_time = ntoh16(ptr.hold_time);
-        self.router_id = std::net::Ipv4Addr::new(
-            ptr.routerid[0],
-            ptr.routerid[1],
-            ptr.routerid[2],
-            ptr.routerid[3],
-        );
+        self.as_num = Asn::from(getn_u16(&buf[1..3]));
+        self.hold_time = getn_u16(&buf[3..5]);
+        self.router_id = std::net::Ipv4Addr::new(buf[5], buf[6], buf[7], buf[8]);
+        if peer.check_router_id {
+            let octets = self.router_id.octets();
+            if self.router_id.is_unspecified()
+                || self.router_id.is_multicast()
+                || octets[0] >= 240
+                || self.router_id == peer.router_id
+            {
+                return Err(BgpError::notification(BgpNotificationMessage::from_code(
+                    2, 3,
+                )));
+            }
+        }
         self.caps.clear();
         let mut pos: usize = 10;
         while pos + 1 < buf.len() {
@@ -60,60 +91,61 @@ impl BgpMessage for BgpOpenMessage {
             let mut optlen = buf[pos + 1] as usize;
             pos += 2;
             while optlen > 0 {
-                let maybe_cap = BgpCapability::from_buffer(slice(buf, pos, pos + optlen)?)?;
+                let capbuf = slice(buf, pos, pos + optlen)?;
+                let cat = BgpDecodeCategory::Capability(capbuf[0]);
+                let maybe_cap = BgpCapability::from_buffer(capbuf);
+                match maybe_cap {
+                    Ok((Err(_), _)) => peer.record_ignored(cat),
+                    _ => peer.record_decode(cat, &maybe_cap),
+                }
+                let maybe_cap = maybe_cap?;
                 optlen -= maybe_cap.1;
                 pos += maybe_cap.1;
                 match maybe_cap.0 {
                     Ok(cap) => self.caps.push(cap),
                     Err((captype, data)) => log::trace!(
-                        "ignoring unknown capability code {} data {:x?}",
+                        "ignoring unknown capability code {} data:\n{:#?}",
                         captype,
-                        data
+                        HexDump(&data)
                     ),
                 }
             }
         }
         Ok(())
     }
-    fn encode_to(&self, _peer: &BgpSessionParams, buf: &mut [u8]) -> Result<usize, BgpError> {
-        if buf.len() < 10 {
-            return Err(BgpError::InsufficientBufferSize);
-        }
-        let ptr: *mut u8 = slice_mut(buf, 1, buf.len())?.as_mut_ptr();
-        let ptr: *mut BgpOpenHead = ptr as *mut BgpOpenHead;
-        let ptr: &mut BgpOpenHead = unsafe { &mut *ptr };
-        buf[0] = 4;
-        ptr.as_num = ntoh16(if self.as_num < 65536 {
-            self.as_num as u16
-        } else {
-            23456
-        });
-        ptr.hold_time = ntoh16(self.hold_time);
-        ptr.routerid = self.router_id.octets();
-        ptr.caplen = self
-            .caps
-            .iter()
-            .fold(0u32, |sum, i| sum + (i.bytes_len() as u32) + 2) as u8;
-        let mut pos: usize = 10;
-        for cp in self.caps.iter() {
-            let caplen = cp.bytes_len();
-            buf[pos] = 2; //capability
-            buf[pos + 1] = caplen as u8;
-            cp.fill_buffer(slice_mut(buf, pos + 2, caplen + pos + 2)?)?;
-            pos += 2 + caplen;
-        }
-        Ok(pos)
-    }
-}
-impl BgpOpenMessage {
     pub fn new() -> BgpOpenMessage {
         BgpOpenMessage {
-            as_num: 0,
+            as_num: Asn(0),
             hold_time: 180,
             router_id: std::net::Ipv4Addr::new(127, 0, 0, 1),
             caps: Vec::new(),
         }
     }
+    /// Check for capability
+    pub fn check_capability(&self, cp: &BgpCapability) -> bool {
+        self.caps.iter().any(|x| x == cp)
+    }
+    /// Search for first capability with given code.
+    pub fn find_capability(&self, code: u8) -> Option<&BgpCapability> {
+        self.caps.iter().find(|x| x.code() == code)
+    }
+    /// Add capability, skipping duplicates. AddPath entries are merged into existing block,
+    /// 32-bit AS capability replaces existing one.
+    pub fn add_capability(&mut self, cp: BgpCapability) {
+        add_capability_to(&mut self.caps, cp)
+    }
+    /// Remove capability
+    pub fn remove_capability(&mut self, cp: &BgpCapability) {
+        remove_capability_from(&mut self.caps, cp)
+    }
+    /// Search for specified addpath capability.
+    pub fn find_addpath(&self, afi: u16, safi: u8) -> Option<&BgpCapAddPath> {
+        find_addpath_in(&self.caps, afi, safi)
+    }
+    /// Address families advertised in this message with AddPath state.
+    pub fn negotiated_families(&self) -> impl Iterator<Item = BgpNegotiatedFamily> {
+        families_in(&self.caps)
+    }
 }
 impl Default for BgpOpenMessage {
     fn default() -> Self {
@@ -124,7 +156,7 @@ impl Default for BgpOpenMessage {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::BgpTransportMode;
+    use crate::{BgpAfiSafi, BgpTransportMode};
 
     #[test]
     fn test_good_open() {
@@ -133,17 +165,17 @@ mod tests {
         let caps = vec![
             BgpCapability::SafiIPv4u,
             BgpCapability::CapRR,
-            BgpCapability::CapASN32(65450),
+            BgpCapability::CapASN32(Asn(65450)),
         ];
         let params = BgpSessionParams::new(
-            65001,
+            Asn(65001),
             30,
             BgpTransportMode::IPv4,
             "10.0.0.1".parse().unwrap(),
             caps.clone(),
         );
         let msg = BgpOpenMessage {
-            as_num: 200,
+            as_num: Asn(200),
             router_id: "10.0.0.1".parse().unwrap(),
             caps,
             hold_time: 180,
@@ -172,6 +204,32 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_open_head_layout() {
+        let params = BgpSessionParams::new(
+            Asn(65001),
+            30,
+            BgpTransportMode::IPv4,
+            "10.0.0.1".parse().unwrap(),
+            vec![],
+        );
+        let msg = BgpOpenMessage {
+            as_num: Asn(70000),
+            hold_time: 0x1234,
+            router_id: "10.1.2.3".parse().unwrap(),
+            caps: vec![],
+        };
+        let mut buf = vec![0_u8; 32];
+        assert_eq!(msg.encode_to(&params, &mut buf).unwrap(), 10);
+        assert_eq!(buf[..10], [4, 0x5b, 0xa0, 0x12, 0x34, 10, 1, 2, 3, 0]);
+        let mut dec = BgpOpenMessage::new();
+        dec.decode_from(&params, &buf[..10]).unwrap();
+        assert_eq!(dec.as_num, Asn(23456));
+        assert_eq!(dec.hold_time, 0x1234);
+        assert_eq!(dec.router_id, msg.router_id);
+        assert!(msg.encode_to(&params, &mut buf[..9]).is_err());
+    }
+
     #[test]
     fn test_concatenated_open() {
         // Setup
@@ -179,17 +237,17 @@ mod tests {
         let caps = vec![
             BgpCapability::SafiIPv4u,
             BgpCapability::CapRR,
-            BgpCapability::CapASN32(65450),
+            BgpCapability::CapASN32(Asn(65450)),
         ];
         let params = BgpSessionParams::new(
-            65001,
+            Asn(65001),
             30,
             BgpTransportMode::IPv4,
             "10.0.0.1".parse().unwrap(),
             caps.clone(),
         );
         let msg = BgpOpenMessage {
-            as_num: 200,
+            as_num: Asn(200),
             router_id: "10.0.0.1".parse().unwrap(),
             caps,
             hold_time: 180,
@@ -242,17 +300,17 @@ mod tests {
         let caps = vec![
             BgpCapability::SafiIPv4u,
             BgpCapability::CapRR,
-            BgpCapability::CapASN32(65450),
+            BgpCapability::CapASN32(Asn(65450)),
         ];
         let params = BgpSessionParams::new(
-            65001,
+            Asn(65001),
             30,
             BgpTransportMode::IPv4,
             "10.0.0.1".parse().unwrap(),
             caps.clone(),
         );
         let mut msg = BgpOpenMessage {
-            as_num: 200,
+            as_num: Asn(200),
             router_id: "10.0.0.1".parse().unwrap(),
             caps,
             hold_time: 180,
@@ -275,17 +333,17 @@ mod tests {
         let caps = vec![
             BgpCapability::SafiIPv4u,
             BgpCapability::CapRR,
-            BgpCapability::CapASN32(65450),
+            BgpCapability::CapASN32(Asn(65450)),
         ];
         let params = BgpSessionParams::new(
-            65001,
+            Asn(65001),
             30,
             BgpTransportMode::IPv4,
             "10.0.0.1".parse().unwrap(),
             caps.clone(),
         );
         let msg = BgpOpenMessage {
-            as_num: 200,
+            as_num: Asn(200),
             router_id: "10.0.0.1".parse().unwrap(),
             caps,
             hold_time: 180,
@@ -312,10 +370,10 @@ mod tests {
         let caps = vec![
             BgpCapability::SafiIPv4u,
             BgpCapability::CapRR,
-            BgpCapability::CapASN32(65450),
+            BgpCapability::CapASN32(Asn(65450)),
         ];
         let params = BgpSessionParams::new(
-            65001,
+            Asn(65001),
             30,
             BgpTransportMode::IPv4,
             "10.0.0.1".parse().unwrap(),
@@ -334,17 +392,17 @@ mod tests {
         let caps = vec![
             BgpCapability::SafiIPv4u,
             BgpCapability::CapRR,
-            BgpCapability::CapASN32(65450),
+            BgpCapability::CapASN32(Asn(65450)),
         ];
         let params = BgpSessionParams::new(
-            65001,
+            Asn(65001),
             30,
             BgpTransportMode::IPv4,
             "10.0.0.1".parse().unwrap(),
             caps.clone(),
         );
         let msg = BgpOpenMessage {
-            as_num: 200,
+            as_num: Asn(200),
             router_id: "10.0.0.1".parse().unwrap(),
             caps,
             hold_time: 180,
@@ -368,17 +426,17 @@ mod tests {
         let caps = vec![
             BgpCapability::SafiIPv4u,
             BgpCapability::CapRR,
-            BgpCapability::CapASN32(65450),
+            BgpCapability::CapASN32(Asn(65450)),
         ];
         let params = BgpSessionParams::new(
-            65001,
+            Asn(65001),
             30,
             BgpTransportMode::IPv4,
             "10.0.0.1".parse().unwrap(),
             caps.clone(),
         );
         let msg = BgpOpenMessage {
-            as_num: 200,
+            as_num: Asn(200),
             router_id: "10.0.0.1".parse().unwrap(),
             caps,
             hold_time: 180,
@@ -395,17 +453,17 @@ mod tests {
         let caps = vec![
             BgpCapability::SafiIPv4u,
             BgpCapability::CapRR,
-            BgpCapability::CapASN32(65450),
+            BgpCapability::CapASN32(Asn(65450)),
         ];
         let params = BgpSessionParams::new(
-            65001,
+            Asn(65001),
             30,
             BgpTransportMode::IPv4,
             "10.0.0.1".parse().unwrap(),
             caps.clone(),
         );
         let msg = BgpOpenMessage {
-            as_num: 200,
+            as_num: Asn(200),
             router_id: "10.0.0.1".parse().unwrap(),
             caps,
             hold_time: 180,
@@ -414,4 +472,77 @@ mod tests {
         let encode = msg.encode_to(&params, &mut buf);
         assert!(matches!(encode, Err(BgpError::InsufficientBufferSize)));
     }
+
+    #[test]
+    fn test_open_router_id_check() {
+        let mut buf = vec![0_u8; 4096];
+        let mut params = BgpSessionParams::new(
+            Asn(65001),
+            30,
+            BgpTransportMode::IPv4,
+            "10.0.0.1".parse().unwrap(),
+            vec![BgpCapability::SafiIPv4u],
+        );
+        let mut msg = BgpOpenMessage::new();
+        msg.router_id = "10.0.0.1".parse().unwrap();
+        let len = msg.encode_to(&params, &mut buf).unwrap();
+        let mut dec = BgpOpenMessage::new();
+        assert!(dec.decode_from(&params, &buf[..len]).is_ok());
+        params.check_router_id = true;
+        let err = dec.decode_from(&params, &buf[..len]).unwrap_err();
+        let notification = err.get_notification().unwrap();
+        assert_eq!(
+            (notification.error_code, notification.error_subcode),
+            (2, 3)
+        );
+        msg.router_id = "10.0.0.2".parse().unwrap();
+        let len = msg.encode_to(&params, &mut buf).unwrap();
+        assert!(dec.decode_from(&params, &buf[..len]).is_ok());
+    }
+
+    #[test]
+    fn test_open_capability_api() {
+        let mut msg = BgpOpenMessage::new();
+        msg.add_capability(BgpCapability::SafiIPv4u);
+        msg.add_capability(BgpCapability::SafiIPv4u);
+        msg.add_capability(BgpCapability::CapASN32(Asn(65001)));
+        msg.add_capability(BgpCapability::CapASN32(Asn(65002)));
+        msg.add_capability(BgpCapability::addpath_for_all(
+            &[BgpCapability::SafiIPv4u],
+            true,
+            false,
+        ));
+        msg.add_capability(BgpCapability::addpath_for_all(
+            &[BgpCapability::SafiIPv4u, BgpCapability::SafiIPv6u],
+            true,
+            true,
+        ));
+        msg.add_capability(BgpCapability::SafiIPv6u);
+        msg.add_capability(BgpCapability::gr_for(
+            &[crate::BgpAfiSafi::IPV4U],
+            120,
+            false,
+        ));
+        msg.add_capability(BgpCapability::gr_for(&[BgpAfiSafi::IPV4U], 90, true));
+        msg.add_capability(BgpCapability::CapFQDN("a".to_string(), "".to_string()));
+        msg.add_capability(BgpCapability::CapFQDN("b".to_string(), "".to_string()));
+        assert_eq!(msg.caps.len(), 6);
+        assert_eq!(msg.find_capability(64).unwrap().restart_time(), Some(90));
+        assert_eq!(
+            msg.find_capability(73),
+            Some(&BgpCapability::CapFQDN("b".to_string(), "".to_string()))
+        );
+        assert_eq!(
+            msg.find_capability(65),
+            Some(&BgpCapability::CapASN32(Asn(65002)))
+        );
+        assert!(msg.find_addpath(1, 1).unwrap().receive);
+        msg.remove_capability(&BgpCapability::CapAddPath(vec![msg
+            .find_addpath(1, 1)
+            .unwrap()
+            .clone()]));
+        assert!(msg.find_addpath(1, 1).is_none());
+        assert!(msg.find_addpath(2, 1).is_some());
+        assert!(msg.check_capability(&BgpCapability::SafiIPv4u));
+    }
 }
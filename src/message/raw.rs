@@ -0,0 +1,77 @@
+// Copyright 2021 Vladimir Melnikov.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! This module contains opaque carrier for message types not interpreted by this library
+
+use crate::*;
+
+/// Opaque BGP message of unknown or experimental type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BgpRawMessage {
+    /// message type code
+    pub msgtype: u8,
+    /// message body
+    pub data: Vec<u8>,
+}
+
+impl BgpRawMessage {
+    pub fn new(msgtype: u8) -> BgpRawMessage {
+        BgpRawMessage {
+            msgtype,
+            data: Vec::new(),
+        }
+    }
+}
+
+impl BgpMessage for BgpRawMessage {
+    fn decode_from(&mut self, peer: &BgpSessionParams, buf: &[u8]) -> Result<(), BgpError> {
+        self.data = buf.to_vec();
+        peer.record_ignored(BgpDecodeCategory::Message(self.msgtype));
+        Ok(())
+    }
+    fn encode_to(&self, _peer: &BgpSessionParams, buf: &mut [u8]) -> Result<usize, BgpError> {
+        if buf.len() < self.data.len() {
+            return Err(BgpError::insufficient_buffer_size());
+        }
+        buf[0..self.data.len()].copy_from_slice(&self.data);
+        Ok(self.data.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::message::stream::BgpMessageStream;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_raw_message_passthrough() {
+        let params = BgpSessionParams::new(
+            Asn(64512),
+            180,
+            BgpTransportMode::IPv4,
+            std::net::Ipv4Addr::new(1, 1, 1, 1),
+            vec![BgpCapability::SafiIPv4u],
+        );
+        let mut raw = BgpRawMessage::new(5);
+        raw.data = vec![0, 1, 0, 1];
+        let mut batch = BgpMessageBatch::new();
+        batch
+            .push_any(&params, &BgpAnyMessage::Raw(raw.clone()))
+            .unwrap();
+        let mut data = Vec::new();
+        batch.write_to(&mut data).unwrap();
+        let msgs: Vec<_> = BgpMessageStream::new(&data[..], params).collect();
+        match &msgs[0] {
+            Ok((head, BgpAnyMessage::Raw(m))) => {
+                assert_eq!(head.msgtype, BgpMessageType::Other(5));
+                assert_eq!(*m, raw);
+            }
+            m => panic!("unexpected {:?}", m),
+        }
+    }
+}
@@ -0,0 +1,171 @@
+// Copyright 2021 Vladimir Melnikov.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! This module contains iterator over BGP messages in byte stream
+
+use crate::message::{BgpAnyMessage, BgpMessageHead};
+use crate::*;
+
+/// Iterator over BGP messages read from byte stream.
+///
+/// Stops on clean end of stream at message boundary.
+/// After any error the stream is out of sync, so iteration stops.
+pub struct BgpMessageStream<R: std::io::Read> {
+    rdsrc: R,
+    /// Session parameters used to decode messages.
+    pub params: BgpSessionParams,
+    /// Maximum accepted message length, taken from session parameters by default.
+    pub max_len: usize,
+    buf: Vec<u8>,
+    done: bool,
+}
+
+impl<R: std::io::Read> BgpMessageStream<R> {
+    pub fn new(rdsrc: R, params: BgpSessionParams) -> BgpMessageStream<R> {
+        BgpMessageStream {
+            rdsrc,
+            max_len: params.max_message_len,
+            params,
+            buf: Vec::new(),
+            done: false,
+        }
+    }
+    /// Returns underlying reader.
+    pub fn into_inner(self) -> R {
+        self.rdsrc
+    }
+    /// Reads exactly buf.len() bytes. Returns false on clean end of stream before first byte.
+    fn fill(rdsrc: &mut R, buf: &mut [u8]) -> Result<bool, BgpError> {
+        let mut pos = 0;
+        while pos < buf.len() {
+            match rdsrc.read(&mut buf[pos..]) {
+                Ok(0) => {
+                    if pos == 0 {
+                        return Ok(false);
+                    }
+                    return Err(BgpError::static_str("Unexpected end of BGP message stream"));
+                }
+                Ok(n) => pos += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(true)
+    }
+    fn next_message(&mut self) -> Result<Option<(BgpMessageHead, BgpAnyMessage)>, BgpError> {
+        let mut headbuf = [0_u8; 19];
+        if !Self::fill(&mut self.rdsrc, &mut headbuf)? {
+            return Ok(None);
+        }
+        let head = self
+            .params
+            .decode_message_head_max(&headbuf, self.max_len)?;
+        self.buf.resize(head.body_len, 0);
+        if head.body_len > 0 && !Self::fill(&mut self.rdsrc, &mut self.buf)? {
+            return Err(BgpError::static_str("Unexpected end of BGP message stream"));
+        }
+        let msg = BgpAnyMessage::decode_from(&self.params, head.msgtype.clone(), &self.buf)?;
+        Ok(Some((head, msg)))
+    }
+}
+
+impl<R: std::io::Read> Iterator for BgpMessageStream<R> {
+    type Item = Result<(BgpMessageHead, BgpAnyMessage), BgpError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.next_message() {
+            Ok(Some(m)) => Some(Ok(m)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::BgpMessageType;
+
+    #[test]
+    fn test_message_stream() {
+        let params = BgpSessionParams::new(
+            Asn(64512),
+            180,
+            BgpTransportMode::IPv4,
+            std::net::Ipv4Addr::new(1, 1, 1, 1),
+            vec![BgpCapability::SafiIPv4u],
+        );
+        let mut buf = vec![0_u8; 4096];
+        let len = params
+            .open_message()
+            .encode_to(&params, &mut buf[19..])
+            .unwrap();
+        let len = params
+            .prepare_message_buf(&mut buf, BgpMessageType::Open, len)
+            .unwrap();
+        let mut data = buf[..len].to_vec();
+        params
+            .prepare_message_buf(&mut buf, BgpMessageType::Keepalive, 0)
+            .unwrap();
+        data.extend_from_slice(&buf[..19]);
+        let msgs: Vec<_> = BgpMessageStream::new(&data[..], params.clone()).collect();
+        assert_eq!(msgs.len(), 2);
+        assert!(matches!(msgs[0], Ok((_, BgpAnyMessage::Open(_)))));
+        assert!(matches!(msgs[1], Ok((_, BgpAnyMessage::Keepalive(_)))));
+        // truncated stream
+        let mut stream = BgpMessageStream::new(&data[..len - 1], params.clone());
+        assert!(stream.next().unwrap().is_err());
+        assert!(stream.next().is_none());
+        // oversized message
+        let mut stream = BgpMessageStream::new(&data[..], params);
+        stream.max_len = 20;
+        assert!(stream.next().unwrap().is_err());
+    }
+    #[test]
+    fn test_message_stream_max_len() {
+        let params = BgpSessionParams::new(
+            Asn(64512),
+            180,
+            BgpTransportMode::IPv4,
+            std::net::Ipv4Addr::new(1, 1, 1, 1),
+            vec![BgpCapability::SafiIPv4u],
+        );
+        let mut upd = crate::message::update::BgpUpdateMessage::new();
+        upd.updates = crate::afi::BgpAddrs::IPV4U(
+            (0..1300_u32)
+                .map(|i| {
+                    crate::afi::BgpAddrV4::new(std::net::Ipv4Addr::from(0x0a00_0000 + (i << 8)), 24)
+                })
+                .collect(),
+        );
+        let mut buf = vec![0_u8; 8192];
+        let len = upd.encode_to(&params, &mut buf[19..]).unwrap();
+        let len = params
+            .prepare_message_buf(&mut buf, BgpMessageType::Update, len)
+            .unwrap();
+        assert!(len > 5000);
+        let mut stream = BgpMessageStream::new(&buf[..len], params.clone());
+        assert_eq!(stream.max_len, 4096);
+        assert!(stream.next().unwrap().is_err());
+        let mut stream = BgpMessageStream::new(&buf[..len], params);
+        stream.max_len = 65535;
+        assert!(matches!(
+            stream.next(),
+            Some(Ok((_, BgpAnyMessage::Update(u)))) if u.updates.len() == 1300
+        ));
+    }
+}
@@ -0,0 +1,118 @@
+// Copyright 2021 Vladimir Melnikov.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! This module contains arena for batch update decoding, enabled by `arena` feature
+//!
+//! Message bodies, attributes locations and decoded attribute lists are placed
+//! into caller-provided [bumpalo::Bump], decoded messages borrow from it.
+//! Resetting the bump after a batch releases everything at once, so steady
+//! stream processing does not hit global allocator per message.
+
+use crate::message::update::lazy::scan_update;
+use crate::prelude::*;
+use crate::*;
+use bumpalo::collections::Vec as BumpVec;
+use bumpalo::Bump;
+
+/// Update messages decoder placing data into bump arena
+#[derive(Debug, Clone, Copy)]
+pub struct BgpUpdateArena<'b> {
+    bump: &'b Bump,
+}
+
+impl<'b> BgpUpdateArena<'b> {
+    pub fn new(bump: &'b Bump) -> BgpUpdateArena<'b> {
+        BgpUpdateArena { bump }
+    }
+    /// Copies update message body into arena and records its attributes boundaries.
+    pub fn decode(
+        &self,
+        peer: &BgpSessionParams,
+        buf: &[u8],
+    ) -> Result<BgpUpdateRef<'b>, BgpError> {
+        let mut attrs = BumpVec::new_in(self.bump);
+        let r = scan_update(buf, |a| attrs.push(a));
+        peer.record_decode(BgpDecodeCategory::Message(2), &r);
+        let (withdraws_range, updates_range) = r?;
+        Ok(BgpUpdateRef {
+            data: self.bump.alloc_slice_copy(buf),
+            withdraws_range,
+            attrs: attrs.into_bump_slice(),
+            updates_range,
+        })
+    }
+    /// Parses all path attributes of message into arena-backed list.
+    pub fn decode_attrs(
+        &self,
+        peer: &BgpSessionParams,
+        upd: &BgpUpdateRef<'_>,
+    ) -> Result<BumpVec<'b, BgpAttrItem>, BgpError> {
+        let mut ret = BumpVec::with_capacity_in(upd.attrs.len(), self.bump);
+        for span in upd.attrs.iter() {
+            ret.push(BgpAttrItem::decode_from(
+                peer,
+                span.typecode,
+                span.flags,
+                span.range.len(),
+                upd.attr_bytes(span),
+            )?);
+        }
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_arena() {
+        let params = BgpSessionParams::new(
+            Asn(65001),
+            30,
+            BgpTransportMode::IPv4,
+            "10.0.0.1".parse().unwrap(),
+            vec![],
+        );
+        let mut buf = vec![0_u8; 4096];
+        let mut bump = Bump::with_capacity(4096);
+        for _ in 0..2 {
+            let arena = BgpUpdateArena::new(&bump);
+            let mut batch = Vec::new();
+            for i in 0..3_u32 {
+                let mut msg = BgpUpdateMessage::new();
+                msg.updates = BgpAddrs::IPV4U(vec![BgpAddrV4::new(
+                    std::net::Ipv4Addr::new(10, i as u8, 0, 0),
+                    16,
+                )]);
+                msg.attrs = vec![
+                    BgpAttrItem::ASPath(BgpASpath {
+                        value: vec![BgpAS::new(65100 + i)],
+                    }),
+                    BgpAttrItem::NextHop(BgpNextHop {
+                        value: "10.0.0.1".parse().unwrap(),
+                    }),
+                ];
+                let len = msg.encode_to(&params, &mut buf).unwrap();
+                batch.push(arena.decode(&params, &buf[..len]).unwrap());
+            }
+            assert!(arena.decode(&params, &[0, 0, 0, 10, 64]).is_err());
+            let asns: Vec<Asn> = batch
+                .iter()
+                .map(|u| u.get_attr_aspath(&params).unwrap().unwrap().value[0].value)
+                .collect();
+            assert_eq!(asns, vec![Asn(65100), Asn(65101), Asn(65102)]);
+            let attrs = arena.decode_attrs(&params, &batch[2]).unwrap();
+            assert_eq!(attrs.len(), 2);
+            assert_eq!(batch[2].updates(&params).unwrap().len(), 1);
+            drop(attrs);
+            drop(batch);
+            bump.reset();
+        }
+    }
+}
@@ -0,0 +1,260 @@
+// Copyright 2021 Vladimir Melnikov.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! This module contains lazily parsed BGP update message
+//!
+//! Only attribute boundaries are recorded on decode, attributes and NLRI
+//! are parsed on request.
+
+use crate::message::update::{decode_unicast_nlri, BgpAttrFrames};
+use crate::prelude::*;
+use crate::*;
+use std::ops::Range;
+
+/// Path attribute location within lazily decoded update
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BgpAttrSpan {
+    /// attribute type code
+    pub typecode: u8,
+    /// attribute flags
+    pub flags: u8,
+    /// attribute value byte range in message body
+    pub range: Range<usize>,
+}
+
+/// BGP update message with deferred attributes and NLRI parsing
+#[derive(Debug, Clone, Default)]
+pub struct BgpLazyUpdateMessage {
+    /// message body
+    pub data: Vec<u8>,
+    /// withdrawn routes byte range
+    pub withdraws_range: Range<usize>,
+    /// path attributes locations
+    pub attrs: Vec<BgpAttrSpan>,
+    /// NLRI byte range
+    pub updates_range: Range<usize>,
+}
+
+/// Borrowed view of update message with deferred attributes and NLRI parsing
+#[derive(Debug, Clone)]
+pub struct BgpUpdateRef<'a> {
+    /// message body
+    pub data: &'a [u8],
+    /// withdrawn routes byte range
+    pub withdraws_range: Range<usize>,
+    /// path attributes locations
+    pub attrs: &'a [BgpAttrSpan],
+    /// NLRI byte range
+    pub updates_range: Range<usize>,
+}
+
+impl<'a> BgpUpdateRef<'a> {
+    /// Finds attribute location by type code.
+    pub fn find_attr(&self, typecode: u8) -> Option<&'a BgpAttrSpan> {
+        self.attrs.iter().find(|a| a.typecode == typecode)
+    }
+    /// Returns raw attribute value.
+    pub fn attr_bytes(&self, span: &BgpAttrSpan) -> &'a [u8] {
+        &self.data[span.range.clone()]
+    }
+    /// Parses attribute by type code, None if attribute is absent.
+    pub fn decode_attr(
+        &self,
+        peer: &BgpSessionParams,
+        typecode: u8,
+    ) -> Result<Option<BgpAttrItem>, BgpError> {
+        match self.find_attr(typecode) {
+            None => Ok(None),
+            Some(span) => Ok(Some(BgpAttrItem::decode_from(
+                peer,
+                span.typecode,
+                span.flags,
+                span.range.len(),
+                self.attr_bytes(span),
+            )?)),
+        }
+    }
+    /// Parses AS path attribute only.
+    pub fn get_attr_aspath(&self, peer: &BgpSessionParams) -> Result<Option<BgpASpath>, BgpError> {
+        match self.decode_attr(peer, 2)? {
+            Some(BgpAttrItem::ASPath(p)) => Ok(Some(p)),
+            _ => Ok(None),
+        }
+    }
+    /// Parses withdrawn routes field.
+    pub fn withdraws(&self, peer: &BgpSessionParams) -> Result<BgpAddrs, BgpError> {
+        decode_unicast_nlri(peer, &self.data[self.withdraws_range.clone()])
+    }
+    /// Parses NLRI field.
+    pub fn updates(&self, peer: &BgpSessionParams) -> Result<BgpAddrs, BgpError> {
+        decode_unicast_nlri(peer, &self.data[self.updates_range.clone()])
+    }
+    /// Parses whole message.
+    pub fn to_update(&self, peer: &BgpSessionParams) -> Result<BgpUpdateMessage, BgpError> {
+        let mut attrs = Vec::with_capacity(self.attrs.len());
+        for span in self.attrs.iter() {
+            attrs.push(BgpAttrItem::decode_from(
+                peer,
+                span.typecode,
+                span.flags,
+                span.range.len(),
+                self.attr_bytes(span),
+            )?);
+        }
+        Ok(BgpUpdateMessage {
+            updates: self.updates(peer)?,
+            withdraws: self.withdraws(peer)?,
+            attrs,
+        })
+    }
+}
+
+/// Scans update message body, passes attributes locations to sink.
+/// Returns withdrawn routes and NLRI byte ranges.
+pub(crate) fn scan_update(
+    buf: &[u8],
+    mut sink: impl FnMut(BgpAttrSpan),
+) -> Result<(Range<usize>, Range<usize>), BgpError> {
+    let withdraws_end = 2 + getn_u16(slice(buf, 0, 2)?) as usize;
+    if buf.len() < withdraws_end + 2 {
+        return Err(BgpError::InsufficientBufferSize);
+    }
+    let pathattr_end = withdraws_end + 2 + getn_u16(&buf[withdraws_end..]) as usize;
+    if pathattr_end > buf.len() {
+        return Err(BgpError::protocol_error());
+    }
+    for frame in BgpAttrFrames::new(buf, withdraws_end + 2, pathattr_end) {
+        match frame {
+            Ok((_, span)) => sink(span),
+            Err(_) => return Err(BgpError::protocol_error()),
+        }
+    }
+    Ok((2..withdraws_end, pathattr_end..buf.len()))
+}
+
+impl BgpLazyUpdateMessage {
+    pub fn new() -> BgpLazyUpdateMessage {
+        Default::default()
+    }
+    /// Borrowed view of this message.
+    pub fn view(&self) -> BgpUpdateRef<'_> {
+        BgpUpdateRef {
+            data: &self.data,
+            withdraws_range: self.withdraws_range.clone(),
+            attrs: &self.attrs,
+            updates_range: self.updates_range.clone(),
+        }
+    }
+    /// Finds attribute location by type code.
+    pub fn find_attr(&self, typecode: u8) -> Option<&BgpAttrSpan> {
+        self.view().find_attr(typecode)
+    }
+    /// Returns raw attribute value.
+    pub fn attr_bytes(&self, span: &BgpAttrSpan) -> &[u8] {
+        &self.data[span.range.clone()]
+    }
+    /// Parses attribute by type code, None if attribute is absent.
+    pub fn decode_attr(
+        &self,
+        peer: &BgpSessionParams,
+        typecode: u8,
+    ) -> Result<Option<BgpAttrItem>, BgpError> {
+        self.view().decode_attr(peer, typecode)
+    }
+    /// Parses AS path attribute only.
+    pub fn get_attr_aspath(&self, peer: &BgpSessionParams) -> Result<Option<BgpASpath>, BgpError> {
+        self.view().get_attr_aspath(peer)
+    }
+    /// Parses withdrawn routes field.
+    pub fn withdraws(&self, peer: &BgpSessionParams) -> Result<BgpAddrs, BgpError> {
+        self.view().withdraws(peer)
+    }
+    /// Parses NLRI field.
+    pub fn updates(&self, peer: &BgpSessionParams) -> Result<BgpAddrs, BgpError> {
+        self.view().updates(peer)
+    }
+    /// Parses whole message.
+    pub fn to_update(&self, peer: &BgpSessionParams) -> Result<BgpUpdateMessage, BgpError> {
+        self.view().to_update(peer)
+    }
+}
+
+impl BgpMessage for BgpLazyUpdateMessage {
+    fn decode_from(&mut self, peer: &BgpSessionParams, buf: &[u8]) -> Result<(), BgpError> {
+        let mut attrs = Vec::new();
+        let r = scan_update(buf, |a| attrs.push(a)).map(|(withdraws_range, updates_range)| {
+            self.data = buf.to_vec();
+            self.withdraws_range = withdraws_range;
+            self.updates_range = updates_range;
+            self.attrs = attrs;
+        });
+        peer.record_decode(BgpDecodeCategory::Message(2), &r);
+        r
+    }
+    fn encode_to(&self, _peer: &BgpSessionParams, buf: &mut [u8]) -> Result<usize, BgpError> {
+        if buf.len() < self.data.len() {
+            return Err(BgpError::insufficient_buffer_size());
+        }
+        buf[0..self.data.len()].copy_from_slice(&self.data);
+        Ok(self.data.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lazy_update() {
+        let params = BgpSessionParams::new(
+            Asn(65001),
+            30,
+            BgpTransportMode::IPv4,
+            "10.0.0.1".parse().unwrap(),
+            vec![],
+        );
+        let mut msg = BgpUpdateMessage::new();
+        msg.updates = BgpAddrs::IPV4U(vec![BgpAddrV4::new("10.1.0.0".parse().unwrap(), 16)]);
+        msg.withdraws = BgpAddrs::IPV4U(vec![BgpAddrV4::new("10.2.0.0".parse().unwrap(), 16)]);
+        msg.attrs = vec![
+            BgpAttrItem::Origin(BgpOrigin {
+                value: BgpAttrOrigin::Igp,
+            }),
+            BgpAttrItem::ASPath(BgpASpath {
+                value: vec![BgpAS::new(65100), BgpAS::new(65101)],
+            }),
+            BgpAttrItem::NextHop(BgpNextHop {
+                value: "10.0.0.1".parse().unwrap(),
+            }),
+        ];
+        let mut buf = vec![0_u8; 4096];
+        let len = msg.encode_to(&params, &mut buf).unwrap();
+        let mut lazy = BgpLazyUpdateMessage::new();
+        lazy.decode_from(&params, &buf[..len]).unwrap();
+        assert_eq!(lazy.attrs.len(), 3);
+        assert!(lazy.find_attr(5).is_none());
+        assert_eq!(
+            lazy.get_attr_aspath(&params).unwrap().unwrap().value,
+            vec![BgpAS::new(65100), BgpAS::new(65101)]
+        );
+        let full = lazy.to_update(&params).unwrap();
+        assert_eq!(full.updates, msg.updates);
+        assert_eq!(full.withdraws, msg.withdraws);
+        assert_eq!(full.attrs, msg.attrs);
+        // truncated attributes, previous content is kept intact
+        assert!(lazy.decode_from(&params, &buf[..len - 12]).is_err());
+        assert_eq!(lazy.data, &buf[..len]);
+        assert_eq!(lazy.to_update(&params).unwrap().attrs, msg.attrs);
+        // attribute header crossing path attributes boundary, same framing as eager decoder
+        let bad = [0, 0, 0, 2, 0x40, 1, 0, 24, 10, 0, 0];
+        assert!(lazy.decode_from(&params, &bad).is_err());
+        let partial = BgpUpdateMessage::decode_partial(&params, &bad);
+        assert_eq!(partial.errors[0].section, BgpUpdateSection::PathAttributes);
+        assert_eq!(partial.errors[0].offset, 4);
+    }
+}
@@ -11,8 +11,19 @@
 use crate::prelude::*;
 use crate::*;
 
+#[cfg(feature = "arena")]
+mod arena;
+mod lazy;
+mod partial;
+mod visitor;
+#[cfg(feature = "arena")]
+pub use arena::*;
+pub use lazy::*;
+pub use partial::*;
+pub use visitor::*;
+
 /// BGP update message, which carries routing information.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BgpUpdateMessage {
     /// NLRI updates
     pub updates: BgpAddrs,
@@ -32,57 +43,35 @@ impl BgpUpdateMessage {
     }
     /// returns origin attribute.
     pub fn get_attr_origin(&self) -> Option<&BgpOrigin> {
-        for i in self.attrs.iter() {
-            if let BgpAttrItem::Origin(n) = i {
-                return Some(n);
-            }
-        }
-        None
+        self.attrs[..].get_attr_origin()
     }
     /// returns aspath attribute.
     pub fn get_attr_aspath(&self) -> Option<&BgpASpath> {
-        for i in self.attrs.iter() {
-            if let BgpAttrItem::ASPath(n) = i {
-                return Some(n);
-            }
-        }
-        None
+        self.attrs[..].get_attr_aspath()
+    }
+    /// returns MED attribute.
+    pub fn get_attr_med(&self) -> Option<&BgpMED> {
+        self.attrs[..].get_attr_med()
+    }
+    /// returns local preference attribute.
+    pub fn get_attr_localpref(&self) -> Option<&BgpLocalpref> {
+        self.attrs[..].get_attr_localpref()
     }
     /// returns community list attribute.
     pub fn get_attr_communitylist(&self) -> Option<&BgpCommunityList> {
-        for i in self.attrs.iter() {
-            if let BgpAttrItem::CommunityList(n) = i {
-                return Some(n);
-            }
-        }
-        None
+        self.attrs[..].get_attr_communitylist()
     }
     /// returns large community list attribute.
     pub fn get_attr_largecommunitylist(&self) -> Option<&BgpLargeCommunityList> {
-        for i in self.attrs.iter() {
-            if let BgpAttrItem::LargeCommunityList(n) = i {
-                return Some(n);
-            }
-        }
-        None
+        self.attrs[..].get_attr_largecommunitylist()
     }
     /// returns extended community list attribute.
     pub fn get_attr_extcommunitylist(&self) -> Option<&BgpExtCommunityList> {
-        for i in self.attrs.iter() {
-            if let BgpAttrItem::ExtCommunityList(n) = i {
-                return Some(n);
-            }
-        }
-        None
+        self.attrs[..].get_attr_extcommunitylist()
     }
     /// returns next hop attribute.
     pub fn get_attr_nexthop(&self) -> Option<&BgpNextHop> {
-        for i in self.attrs.iter() {
-            if let BgpAttrItem::NextHop(n) = i {
-                return Some(n);
-            }
-        }
-        None
+        self.attrs[..].get_attr_nexthop()
     }
     /// returns MPUpdates
     pub fn get_mpupdates(&self) -> Option<&BgpMPUpdates> {
@@ -108,101 +97,209 @@ impl Default for BgpUpdateMessage {
         Self::new()
     }
 }
-impl BgpMessage for BgpUpdateMessage {
-    fn decode_from(&mut self, peer: &BgpSessionParams, buf: &[u8]) -> Result<(), BgpError> {
-        let mut curpos: usize = 0;
-        let withdraws_length = getn_u16(slice(buf, curpos, curpos + 2)?) as usize;
-        curpos += 2;
-        let withdraws_end = curpos + withdraws_length;
-        if buf.len() <= withdraws_end {
-            return Err(BgpError::InsufficientBufferSize);
+/// Decodes withdrawn routes or NLRI field of update message for session transport family.
+pub(crate) fn decode_unicast_nlri(
+    peer: &BgpSessionParams,
+    buf: &[u8],
+) -> Result<BgpAddrs, BgpError> {
+    let afi = match peer.peer_mode {
+        BgpTransportMode::IPv4 => 1,
+        BgpTransportMode::IPv6 => 2,
+    };
+    let addpath = peer.check_addpath_receive(afi, 1) || (peer.fuzzy_pathid && is_addpath_nlri(buf));
+    Ok(match (peer.peer_mode, addpath) {
+        (BgpTransportMode::IPv4, false) => BgpAddrs::IPV4U(decode_bgpitems_from(buf)?.0),
+        (BgpTransportMode::IPv4, true) => BgpAddrs::IPV4UP(decode_pathid_bgpitems_from(buf)?.0),
+        (BgpTransportMode::IPv6, false) => BgpAddrs::IPV6U(decode_bgpitems_from(buf)?.0),
+        (BgpTransportMode::IPv6, true) => BgpAddrs::IPV6UP(decode_pathid_bgpitems_from(buf)?.0),
+    })
+}
+impl BgpUpdateMessage {
+    /// Walks update message body, decoded parts are stored into self.
+    /// Parts that fail are passed to sink with their offset and skipped where their boundaries are known.
+    pub(crate) fn decode_body(
+        &mut self,
+        peer: &BgpSessionParams,
+        buf: &[u8],
+        sink: &mut impl FnMut(BgpUpdateSection, usize, BgpError),
+    ) {
+        if buf.len() < 2 {
+            sink(
+                BgpUpdateSection::Withdraws,
+                0,
+                BgpError::InsufficientBufferSize,
+            );
+            return;
         }
-        match peer.peer_mode {
-            BgpTransportMode::IPv4 => {
-                if peer.check_addpath_receive(1, 1)
-                    || (peer.fuzzy_pathid && is_addpath_nlri(slice(buf, curpos, withdraws_end)?))
-                {
-                    let r = decode_pathid_bgpitems_from(slice(buf, curpos, withdraws_end)?)?;
-                    self.withdraws = BgpAddrs::IPV4UP(r.0);
-                } else {
-                    let r = decode_bgpitems_from(slice(buf, curpos, withdraws_end)?)?;
-                    self.withdraws = BgpAddrs::IPV4U(r.0);
-                }
-            }
-            BgpTransportMode::IPv6 => {
-                if peer.check_addpath_receive(2, 1)
-                    || (peer.fuzzy_pathid && is_addpath_nlri(slice(buf, curpos, withdraws_end)?))
-                {
-                    let r = decode_pathid_bgpitems_from(slice(buf, curpos, withdraws_end)?)?;
-                    self.withdraws = BgpAddrs::IPV6UP(r.0);
-                } else {
-                    let r = decode_bgpitems_from(slice(buf, curpos, withdraws_end)?)?;
-                    self.withdraws = BgpAddrs::IPV6U(r.0);
-                }
-            }
-        };
-        curpos = withdraws_end;
-        let pathattr_len = getn_u16(slice(buf, curpos, curpos + 2)?) as usize;
-        curpos += 2;
+        let withdraws_end = 2 + getn_u16(&buf[0..2]) as usize;
+        if buf.len() < withdraws_end + 2 {
+            sink(
+                BgpUpdateSection::Withdraws,
+                2,
+                BgpError::InsufficientBufferSize,
+            );
+            return;
+        }
+        match decode_unicast_nlri(peer, &buf[2..withdraws_end]) {
+            Ok(w) => self.withdraws = w,
+            Err(e) => sink(BgpUpdateSection::Withdraws, 2, e),
+        }
+        let pathattr_len = getn_u16(&buf[withdraws_end..withdraws_end + 2]) as usize;
         log::trace!("Path attributes length: {:?}", pathattr_len);
-        let pathattr_end = curpos + pathattr_len;
-        if pathattr_end > buf.len() {
-            return Err(BgpError::protocol_error());
+        let mut pathattr_end = withdraws_end + 2 + pathattr_len;
+        let nlri_valid = pathattr_end <= buf.len();
+        if !nlri_valid {
+            sink(
+                BgpUpdateSection::PathAttributes,
+                withdraws_end,
+                BgpError::protocol_error(),
+            );
+            pathattr_end = buf.len();
         }
-        while curpos < pathattr_end {
-            //flags 0
-            //tc 1
-            let flags = buf[curpos];
-            let tc = buf[curpos + 1];
-            let attrlen = if (flags & 16) > 0 {
-                curpos += 4;
-                getn_u16(slice(buf, curpos - 2, curpos)?) as usize
-            } else {
-                curpos += 3;
-                buf[curpos - 1] as usize
+        for frame in BgpAttrFrames::new(buf, withdraws_end + 2, pathattr_end) {
+            let (curpos, span) = match frame {
+                Ok(f) => f,
+                Err((section, pos)) => {
+                    sink(section, pos, BgpError::protocol_error());
+                    break;
+                }
             };
-            if (curpos + attrlen) > pathattr_end {
-                return Err(BgpError::protocol_error());
-            }
-            log::trace!("PA flags {:?} TC {:?} len {:?}", flags, tc, attrlen);
+            log::trace!(
+                "PA flags {:?} TC {:?} len {:?}",
+                span.flags,
+                span.typecode,
+                span.range.len()
+            );
             //https://www.iana.org/assignments/bgp-parameters/bgp-parameters.xhtml
-            self.attrs.push(BgpAttrItem::decode_from(
+            match BgpAttrItem::decode_from(
                 peer,
-                tc,
-                flags,
-                attrlen,
-                slice(buf, curpos, curpos + attrlen)?,
-            )?);
-            curpos += attrlen;
+                span.typecode,
+                span.flags,
+                span.range.len(),
+                &buf[span.range.clone()],
+            ) {
+                Ok(a) => self.attrs.push(a),
+                Err(e) => sink(BgpUpdateSection::Attribute(span.typecode), curpos, e),
+            }
         }
-        match peer.peer_mode {
-            BgpTransportMode::IPv4 => {
-                if peer.check_addpath_receive(1, 1)
-                    || (peer.fuzzy_pathid && is_addpath_nlri(slice(buf, curpos, buf.len())?))
-                {
-                    let r = decode_pathid_bgpitems_from(slice(buf, curpos, buf.len())?)?;
-                    self.updates = BgpAddrs::IPV4UP(r.0);
-                } else {
-                    let r = decode_bgpitems_from(slice(buf, curpos, buf.len())?)?;
-                    self.updates = BgpAddrs::IPV4U(r.0);
+        if let Some(peer_as) = peer.check_first_as {
+            if let Some(aspath) = self.get_attr_aspath() {
+                if let Err(e) = aspath.check_first_as(peer, peer_as) {
+                    sink(BgpUpdateSection::Attribute(2), withdraws_end + 2, e);
                 }
             }
-            BgpTransportMode::IPv6 => {
-                if peer.check_addpath_receive(2, 1)
-                    || (peer.fuzzy_pathid && is_addpath_nlri(slice(buf, curpos, buf.len())?))
-                {
-                    let r = decode_pathid_bgpitems_from(slice(buf, curpos, buf.len())?)?;
-                    self.updates = BgpAddrs::IPV6UP(r.0);
-                } else {
-                    let r = decode_bgpitems_from(slice(buf, curpos, buf.len())?)?;
-                    self.updates = BgpAddrs::IPV6U(r.0);
-                }
+        }
+        if nlri_valid {
+            match decode_unicast_nlri(peer, &buf[pathattr_end..]) {
+                Ok(u) => self.updates = u,
+                Err(e) => sink(BgpUpdateSection::Nlri, pathattr_end, e),
             }
-        };
+        }
         log::trace!("Update: {:?}", self);
+    }
+}
+/// Path attributes framing iterator, shared by eager and lazy decoders.
+///
+/// Yields attribute header offset and its location, or failed section and offset.
+/// Iteration stops after first framing error.
+pub(crate) struct BgpAttrFrames<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    end: usize,
+}
+impl<'a> BgpAttrFrames<'a> {
+    /// Frames attributes within buf[pos..end], end must not exceed buffer length.
+    pub(crate) fn new(buf: &'a [u8], pos: usize, end: usize) -> BgpAttrFrames<'a> {
+        BgpAttrFrames { buf, pos, end }
+    }
+}
+impl Iterator for BgpAttrFrames<'_> {
+    type Item = Result<(usize, BgpAttrSpan), (BgpUpdateSection, usize)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.end {
+            return None;
+        }
+        let curpos = self.pos;
+        let flags = self.buf[curpos];
+        let hdrlen = if (flags & 16) > 0 { 4 } else { 3 };
+        if curpos + hdrlen > self.end {
+            self.pos = self.end;
+            return Some(Err((BgpUpdateSection::PathAttributes, curpos)));
+        }
+        let typecode = self.buf[curpos + 1];
+        let attrlen = if hdrlen == 4 {
+            getn_u16(&self.buf[curpos + 2..curpos + 4]) as usize
+        } else {
+            self.buf[curpos + 2] as usize
+        };
+        let start = curpos + hdrlen;
+        if start + attrlen > self.end {
+            self.pos = self.end;
+            return Some(Err((BgpUpdateSection::Attribute(typecode), curpos)));
+        }
+        self.pos = start + attrlen;
+        Some(Ok((
+            curpos,
+            BgpAttrSpan {
+                typecode,
+                flags,
+                range: start..start + attrlen,
+            },
+        )))
+    }
+}
+impl BgpMessage for BgpUpdateMessage {
+    fn decode_from(&mut self, peer: &BgpSessionParams, buf: &[u8]) -> Result<(), BgpError> {
+        *self = BgpUpdateMessage::decode_partial(peer, buf).into_result()?;
         Ok(())
     }
     fn encode_to(&self, peer: &BgpSessionParams, buf: &mut [u8]) -> Result<usize, BgpError> {
+        if peer.sort_nlri {
+            let mut sorted = self.clone();
+            sorted.sort();
+            return sorted.encode_body(peer, buf);
+        }
+        self.encode_body(peer, buf)
+    }
+}
+impl BgpUpdateMessage {
+    /// Builds message withdrawing given NLRI of any afi/safi.
+    /// Unicast family of session transport mode (IPv4 or IPv6) is withdrawn
+    /// in withdrawn routes field, other families with MP_UNREACH_NLRI.
+    /// Path identifiers are preserved.
+    pub fn withdraw_for(peer: &BgpSessionParams, addrs: &BgpAddrs) -> BgpUpdateMessage {
+        let mut ret = BgpUpdateMessage::new();
+        match (peer.peer_mode, addrs) {
+            (_, BgpAddrs::None) => {}
+            (BgpTransportMode::IPv4, BgpAddrs::IPV4U(_) | BgpAddrs::IPV4UP(_))
+            | (BgpTransportMode::IPv6, BgpAddrs::IPV6U(_) | BgpAddrs::IPV6UP(_)) => {
+                ret.withdraws = addrs.clone()
+            }
+            _ => ret.attrs.push(BgpAttrItem::MPWithdraws(BgpMPWithdraws {
+                addrs: addrs.clone(),
+            })),
+        }
+        ret
+    }
+    /// Sorts NLRI and path attributes. Attributes are ordered by type code,
+    /// with MP_UNREACH_NLRI placed before MP_REACH_NLRI.
+    pub fn sort(&mut self) {
+        self.updates.sort();
+        self.withdraws.sort();
+        for attr in self.attrs.iter_mut() {
+            match attr {
+                BgpAttrItem::MPUpdates(u) => u.addrs.sort(),
+                BgpAttrItem::MPWithdraws(w) => w.addrs.sort(),
+                _ => {}
+            }
+        }
+        self.attrs.sort_by_key(|a| match a {
+            BgpAttrItem::MPWithdraws(_) => (14, false),
+            a => (a.attr().typecode, true),
+        });
+    }
+    fn encode_body(&self, peer: &BgpSessionParams, buf: &mut [u8]) -> Result<usize, BgpError> {
         let mut curpos: usize = 0;
         //withdraws main
         match peer.peer_mode {
@@ -299,7 +396,7 @@ mod tests {
         let mut buf = vec![0_u8; 4096];
         let mut msg = BgpUpdateMessage::new();
         let params = BgpSessionParams::new(
-            65001,
+            Asn(65001),
             30,
             BgpTransportMode::IPv4,
             "10.0.0.1".parse().unwrap(),
@@ -347,7 +444,7 @@ mod tests {
         let mut buf = vec![0_u8; 4096];
         let mut msg = BgpUpdateMessage::new();
         let params = BgpSessionParams::new(
-            65001,
+            Asn(65001),
             30,
             BgpTransportMode::IPv4,
             "10.0.0.1".parse().unwrap(),
@@ -396,7 +493,7 @@ mod tests {
         let buf = vec![0_u8; 0];
         let mut msg = BgpUpdateMessage::new();
         let params = BgpSessionParams::new(
-            65001,
+            Asn(65001),
             30,
             BgpTransportMode::IPv4,
             "10.0.0.1".parse().unwrap(),
@@ -432,4 +529,122 @@ mod tests {
         let decode = msg.decode_from(&params, &buf);
         assert!(matches!(decode, Err(BgpError::InsufficientBufferSize)));
     }
+
+    #[test]
+    fn test_first_as() {
+        let mut params = BgpSessionParams::new(
+            Asn(65001),
+            30,
+            BgpTransportMode::IPv4,
+            "10.0.0.1".parse().unwrap(),
+            vec![],
+        );
+        let mut msg = BgpUpdateMessage::new();
+        msg.updates = BgpAddrs::IPV4U(vec![BgpAddrV4::new("10.1.0.0".parse().unwrap(), 16)]);
+        msg.attrs = vec![
+            BgpAttrItem::ASPath(BgpASpath::from(vec![65100, 65101])),
+            BgpAttrItem::NextHop(BgpNextHop {
+                value: "10.0.0.2".parse().unwrap(),
+            }),
+        ];
+        let mut buf = vec![0_u8; 4096];
+        let len = msg.encode_to(&params, &mut buf).unwrap();
+        params.check_first_as = Some(Asn(65100));
+        assert!(BgpUpdateMessage::new()
+            .decode_from(&params, &buf[..len])
+            .is_ok());
+        params.check_first_as = Some(Asn(65200));
+        let err = BgpUpdateMessage::new()
+            .decode_from(&params, &buf[..len])
+            .unwrap_err();
+        let n = err.get_notification().unwrap();
+        assert_eq!((n.error_code, n.error_subcode), (3, 11));
+        assert_eq!(msg.get_attr_aspath().unwrap().first_as(), Some(Asn(65100)));
+        // 4-byte peer AS on 2-byte session
+        let path = BgpASpath::from(vec![AS_TRANS.0, 65101]);
+        assert!(path.check_first_as(&params, Asn(4200000001)).is_err());
+        params.has_as32bit = false;
+        assert!(path.check_first_as(&params, Asn(4200000001)).is_ok());
+        assert!(path.check_first_as(&params, Asn(65100)).is_err());
+    }
+
+    #[test]
+    fn test_withdraw_for() {
+        let params = BgpSessionParams::new(
+            Asn(65001),
+            30,
+            BgpTransportMode::IPv4,
+            "10.0.0.1".parse().unwrap(),
+            vec![],
+        );
+        let mut buf = vec![0_u8; 4096];
+        let v4 = BgpAddrs::IPV4U(vec!["10.1.0.0/16".parse().unwrap()]);
+        let vpn = BgpAddrs::VPNV4U(vec![WithRd::new(
+            BgpRD::new(65000, 1),
+            "10.2.0.0/16".parse::<BgpAddrV4>().unwrap(),
+        )
+        .labeled(MplsLabels::fromvec(vec![0x800000]))]);
+        let v6 = BgpAddrs::IPV6UP(vec![WithPathId::new(
+            5,
+            "2001:db8::/32".parse::<BgpAddrV6>().unwrap(),
+        )]);
+        let msg = BgpUpdateMessage::withdraw_for(&params, &v4);
+        assert_eq!(msg.withdraws, v4);
+        assert!(msg.attrs.is_empty());
+        let mut params6 = params.clone();
+        params6.peer_mode = BgpTransportMode::IPv6;
+        assert_eq!(BgpUpdateMessage::withdraw_for(&params6, &v6).withdraws, v6);
+        assert!(BgpUpdateMessage::withdraw_for(&params6, &v4)
+            .withdraws
+            .is_empty());
+        for peer in [&params, &params6] {
+            for addrs in [&v4, &vpn, &v6] {
+                let len = BgpUpdateMessage::withdraw_for(peer, addrs)
+                    .encode_to(peer, &mut buf)
+                    .unwrap();
+                let mut decoded = BgpUpdateMessage::new();
+                decoded.decode_from(peer, &buf[..len]).unwrap();
+                let got = match decoded.attrs.first() {
+                    Some(BgpAttrItem::MPWithdraws(w)) => w.addrs.clone(),
+                    _ => decoded.withdraws,
+                };
+                assert_eq!(&got, addrs);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sorted_encode() {
+        let mut params = BgpSessionParams::new(
+            Asn(65001),
+            30,
+            BgpTransportMode::IPv4,
+            "10.0.0.1".parse().unwrap(),
+            vec![],
+        );
+        params.sort_nlri = true;
+        let nets: Vec<BgpAddrV4> = ["10.2.0.0/16", "10.1.0.0/16", "10.3.0.0/16"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let nh = BgpAttrItem::NextHop(BgpNextHop {
+            value: "10.0.0.2".parse().unwrap(),
+        });
+        let origin = BgpAttrItem::Origin(BgpOrigin::new(BgpAttrOrigin::Igp));
+        let mut a = BgpUpdateMessage::new();
+        a.updates = BgpAddrs::IPV4U(nets.clone());
+        a.attrs = vec![nh.clone(), origin.clone()];
+        let mut b = BgpUpdateMessage::new();
+        b.updates = BgpAddrs::IPV4U(nets.into_iter().rev().collect());
+        b.attrs = vec![origin, nh];
+        let mut bufa = vec![0_u8; 4096];
+        let mut bufb = vec![0_u8; 4096];
+        let lena = a.encode_to(&params, &mut bufa).unwrap();
+        let lenb = b.encode_to(&params, &mut bufb).unwrap();
+        assert_eq!(bufa[..lena], bufb[..lenb]);
+        let mut dec = BgpUpdateMessage::new();
+        dec.decode_from(&params, &bufa[..lena]).unwrap();
+        assert!(matches!(dec.attrs[0], BgpAttrItem::Origin(_)));
+        assert!(matches!(dec.updates, BgpAddrs::IPV4U(ref v) if v[0].to_string() == "10.1.0.0/16"));
+    }
 }
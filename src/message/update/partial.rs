@@ -0,0 +1,152 @@
+// Copyright 2021 Vladimir Melnikov.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! This module contains best-effort update message decoding
+//!
+//! Unlike [BgpMessage::decode_from], which fails on first error, partial decode
+//! skips broken parts whose boundaries are known and collects errors,
+//! so collectors can salvage data from corrupt feeds.
+
+use crate::prelude::*;
+use crate::*;
+
+/// Update message part that failed to decode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BgpUpdateSection {
+    /// withdrawn routes field
+    Withdraws,
+    /// path attributes framing, remaining attributes are lost
+    PathAttributes,
+    /// single path attribute by typecode, it is skipped
+    Attribute(u8),
+    /// NLRI field
+    Nlri,
+}
+
+impl std::fmt::Display for BgpUpdateSection {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BgpUpdateSection::Withdraws => write!(f, "withdraws"),
+            BgpUpdateSection::PathAttributes => write!(f, "path attributes"),
+            BgpUpdateSection::Attribute(tc) => write!(f, "attribute {}", tc),
+            BgpUpdateSection::Nlri => write!(f, "nlri"),
+        }
+    }
+}
+
+/// Error found during partial decode
+#[derive(Debug)]
+pub struct BgpPartialError {
+    /// failed part
+    pub section: BgpUpdateSection,
+    /// offset of failed part in message body
+    pub offset: usize,
+    /// decode error
+    pub error: BgpError,
+}
+
+impl std::fmt::Display for BgpPartialError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} at {}: {}", self.section, self.offset, self.error)
+    }
+}
+
+/// Partially decoded update message
+#[derive(Debug, Default)]
+pub struct BgpPartialUpdate {
+    /// successfully decoded parts
+    pub msg: BgpUpdateMessage,
+    /// errors for parts that failed
+    pub errors: Vec<BgpPartialError>,
+}
+
+impl BgpPartialUpdate {
+    /// Returns true if whole message was decoded.
+    pub fn is_complete(&self) -> bool {
+        self.errors.is_empty()
+    }
+    /// Converts to result, failing with first error if any.
+    pub fn into_result(mut self) -> Result<BgpUpdateMessage, BgpError> {
+        if self.errors.is_empty() {
+            Ok(self.msg)
+        } else {
+            Err(self.errors.swap_remove(0).error)
+        }
+    }
+}
+
+impl BgpUpdateMessage {
+    /// Decodes update message body as much as possible.
+    /// Attributes with valid framing but broken value are skipped,
+    /// withdraws and NLRI are decoded independently from attributes.
+    pub fn decode_partial(peer: &BgpSessionParams, buf: &[u8]) -> BgpPartialUpdate {
+        let mut ret = BgpPartialUpdate::default();
+        ret.msg
+            .decode_body(peer, buf, &mut |section, offset, error| {
+                ret.errors.push(BgpPartialError {
+                    section,
+                    offset,
+                    error,
+                })
+            });
+        if let Some(m) = &peer.metrics {
+            match ret.errors.first() {
+                None => m.record_ok(BgpDecodeCategory::Message(2)),
+                Some(e) => m.record_error(BgpDecodeCategory::Message(2), &e.error),
+            }
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_partial() {
+        let peer = BgpSessionParams::new(
+            Asn(64512),
+            180,
+            BgpTransportMode::IPv4,
+            std::net::Ipv4Addr::new(1, 1, 1, 1),
+            vec![BgpCapability::CapASN32(Asn(64512))],
+        );
+        let mut msg = BgpUpdateMessage::new();
+        msg.updates = BgpAddrs::IPV4U(vec![BgpAddrV4::new("10.1.0.0".parse().unwrap(), 16)]);
+        msg.attrs = vec![
+            BgpAttrItem::Origin(BgpOrigin::new(BgpAttrOrigin::Igp)),
+            BgpAttrItem::MED(BgpMED::new(10)),
+        ];
+        let mut buf = [0u8; 256];
+        let sz = msg.encode_to(&peer, &mut buf).unwrap();
+        let good = BgpUpdateMessage::decode_partial(&peer, &buf[..sz]);
+        assert!(good.is_complete());
+        assert_eq!(good.msg.attrs.len(), 2);
+        // corrupt ORIGIN value, MED and NLRI must survive
+        buf[7] = 7;
+        let part = BgpUpdateMessage::decode_partial(&peer, &buf[..sz]);
+        assert_eq!(part.errors.len(), 1);
+        assert_eq!(part.errors[0].section, BgpUpdateSection::Attribute(1));
+        assert!(part.msg.get_attr_origin().is_none());
+        assert_eq!(part.msg.get_attr_med().map(|m| m.value), Some(10));
+        assert_eq!(part.msg.updates.len(), 1);
+        let mut full = BgpUpdateMessage::new();
+        assert!(full.decode_from(&peer, &buf[..sz]).is_err());
+        assert!(part.into_result().is_err());
+        // failed decode is counted by original error kind
+        let metrics = std::sync::Arc::new(BgpDecodeMetrics::new());
+        let mut peer = peer;
+        peer.metrics = Some(metrics.clone());
+        assert!(full.decode_from(&peer, &buf[..sz]).is_err());
+        assert_eq!(metrics.get(BgpDecodeCategory::Message(2)).errors, 1);
+        // both attribute and message are counted with invalid ORIGIN error
+        assert_eq!(metrics.error_kinds().get("Static"), Some(&2));
+        assert_eq!(metrics.error_kinds().get("DynStr"), None);
+    }
+}
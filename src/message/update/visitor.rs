@@ -0,0 +1,127 @@
+// Copyright 2021 Vladimir Melnikov.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! This module contains visitor API for update messages
+//!
+//! [BgpUpdateMessage::visit] walks withdrawals, path attributes and announcements
+//! in this order. All callbacks have empty default implementations,
+//! so visitor overrides only what it needs.
+
+use crate::prelude::*;
+
+/// Update message visitor with typed callbacks.
+pub trait UpdateVisitor {
+    /// Withdrawn NLRI, both from withdrawn routes field and MP_UNREACH_NLRI.
+    fn visit_withdraw(&mut self, _addrs: &BgpAddrs) {}
+    /// Announced NLRI, both from NLRI field and MP_REACH_NLRI.
+    /// Next hop is None for NLRI field, it is carried by NEXT_HOP attribute.
+    fn visit_announce(&mut self, _nexthop: Option<&BgpAddr>, _addrs: &BgpAddrs) {}
+    /// Any path attribute, dispatches to typed callbacks by default.
+    fn visit_attr(&mut self, attr: &BgpAttrItem) {
+        walk_attr(self, attr)
+    }
+    fn visit_origin(&mut self, _origin: &BgpOrigin) {}
+    fn visit_aspath(&mut self, _aspath: &BgpASpath) {}
+    fn visit_nexthop(&mut self, _nexthop: &BgpNextHop) {}
+    fn visit_med(&mut self, _med: &BgpMED) {}
+    fn visit_localpref(&mut self, _localpref: &BgpLocalpref) {}
+    fn visit_communities(&mut self, _communities: &BgpCommunityList) {}
+    fn visit_ext_communities(&mut self, _communities: &BgpExtCommunityList) {}
+    fn visit_large_communities(&mut self, _communities: &BgpLargeCommunityList) {}
+    /// Attributes without dedicated callback.
+    fn visit_other_attr(&mut self, _attr: &BgpAttrItem) {}
+}
+
+/// Dispatches attribute to typed visitor callback.
+/// MP_REACH_NLRI and MP_UNREACH_NLRI are passed to announce and withdraw callbacks.
+pub fn walk_attr<V: UpdateVisitor + ?Sized>(v: &mut V, attr: &BgpAttrItem) {
+    match attr {
+        BgpAttrItem::Origin(a) => v.visit_origin(a),
+        BgpAttrItem::ASPath(a) => v.visit_aspath(a),
+        BgpAttrItem::NextHop(a) => v.visit_nexthop(a),
+        BgpAttrItem::MED(a) => v.visit_med(a),
+        BgpAttrItem::LocalPref(a) => v.visit_localpref(a),
+        BgpAttrItem::CommunityList(a) => v.visit_communities(a),
+        BgpAttrItem::ExtCommunityList(a) => v.visit_ext_communities(a),
+        BgpAttrItem::LargeCommunityList(a) => v.visit_large_communities(a),
+        BgpAttrItem::MPUpdates(a) => v.visit_announce(Some(&a.nexthop), &a.addrs),
+        BgpAttrItem::MPWithdraws(a) => v.visit_withdraw(&a.addrs),
+        a => v.visit_other_attr(a),
+    }
+}
+
+impl BgpUpdateMessage {
+    /// Walks withdrawals, path attributes and announcements with visitor.
+    pub fn visit<V: UpdateVisitor + ?Sized>(&self, v: &mut V) {
+        if !self.withdraws.is_empty() {
+            v.visit_withdraw(&self.withdraws);
+        }
+        for attr in self.attrs.iter() {
+            v.visit_attr(attr);
+        }
+        if !self.updates.is_empty() {
+            v.visit_announce(None, &self.updates);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Stats {
+        announced: usize,
+        withdrawn: usize,
+        origin_as: Vec<Asn>,
+        others: usize,
+    }
+    impl UpdateVisitor for Stats {
+        fn visit_withdraw(&mut self, addrs: &BgpAddrs) {
+            self.withdrawn += addrs.len();
+        }
+        fn visit_announce(&mut self, _nexthop: Option<&BgpAddr>, addrs: &BgpAddrs) {
+            self.announced += addrs.len();
+        }
+        fn visit_aspath(&mut self, aspath: &BgpASpath) {
+            self.origin_as.extend(aspath.value.last().map(|a| a.value));
+        }
+        fn visit_other_attr(&mut self, _attr: &BgpAttrItem) {
+            self.others += 1;
+        }
+    }
+
+    #[test]
+    fn test_update_visitor() {
+        let mut msg = BgpUpdateMessage::new();
+        msg.updates = BgpAddrs::IPV4U(vec![BgpAddrV4::new("10.1.0.0".parse().unwrap(), 16)]);
+        msg.withdraws = BgpAddrs::IPV4U(vec![BgpAddrV4::new("10.2.0.0".parse().unwrap(), 16)]);
+        msg.attrs = vec![
+            BgpAttrItem::ASPath(BgpASpath {
+                value: vec![BgpAS::new(65100), BgpAS::new(65101)],
+            }),
+            BgpAttrItem::AtomicAggregate(BgpAtomicAggregate {
+                value: std::net::IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 1)),
+            }),
+            BgpAttrItem::MPUpdates(BgpMPUpdates {
+                nexthop: BgpAddr::V6("2001:db8::1".parse().unwrap()),
+                link_local: None,
+                addrs: BgpAddrs::IPV6U(vec![
+                    BgpAddrV6::new("2001:db8:1::".parse().unwrap(), 48),
+                    BgpAddrV6::new("2001:db8:2::".parse().unwrap(), 48),
+                ]),
+            }),
+        ];
+        let mut stats = Stats::default();
+        msg.visit(&mut stats);
+        assert_eq!(stats.announced, 3);
+        assert_eq!(stats.withdrawn, 1);
+        assert_eq!(stats.origin_as, vec![Asn(65101)]);
+        assert_eq!(stats.others, 1);
+    }
+}
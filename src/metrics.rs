@@ -0,0 +1,221 @@
+// Copyright 2021 Vladimir Melnikov.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! This module contains optional decode metrics registry
+//!
+//! Attach registry to session parameters and scrape it periodically:
+//!
+//! ```
+//! use zettabgp::prelude::*;
+//! use std::sync::Arc;
+//!
+//! let metrics = Arc::new(BgpDecodeMetrics::new());
+//! let mut params = BgpSessionParams::new(Asn(64512), 180, BgpTransportMode::IPv4, std::net::Ipv4Addr::new(1, 1, 1, 1), Vec::new());
+//! params.metrics = Some(metrics.clone());
+//! let mut upd = BgpUpdateMessage::new();
+//! assert!(upd.decode_from(&params, &[0, 0, 0, 0]).is_ok());
+//! assert_eq!(metrics.get(BgpDecodeCategory::Message(2)).ok, 1);
+//! ```
+
+use crate::error::BgpError;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+/// Decode outcome category
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BgpDecodeCategory {
+    /// BGP message by type code
+    Message(u8),
+    /// path attribute by typecode
+    Attribute(u8),
+    /// NLRI by afi and safi
+    AfiSafi(u16, u8),
+    /// OPEN message capability by code
+    Capability(u8),
+}
+
+impl std::fmt::Display for BgpDecodeCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BgpDecodeCategory::Message(n) => write!(f, "message:{}", n),
+            BgpDecodeCategory::Attribute(n) => write!(f, "attribute:{}", n),
+            BgpDecodeCategory::AfiSafi(afi, safi) => write!(f, "afisafi:{}/{}", afi, safi),
+            BgpDecodeCategory::Capability(n) => write!(f, "capability:{}", n),
+        }
+    }
+}
+
+/// Decode outcome counters for single category
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BgpDecodeCounters {
+    /// successfully decoded
+    pub ok: u64,
+    /// failed to decode
+    pub errors: u64,
+    /// not interpreted, but tolerated (unknown attributes, capabilities)
+    pub ignored: u64,
+}
+
+#[derive(Default)]
+struct AtomicCounters {
+    ok: AtomicU64,
+    errors: AtomicU64,
+    ignored: AtomicU64,
+}
+
+impl AtomicCounters {
+    fn load(&self) -> BgpDecodeCounters {
+        BgpDecodeCounters {
+            ok: self.ok.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            ignored: self.ignored.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Decode metrics registry, shared between sessions with Arc.
+///
+/// Counters are atomic, categories and error kinds maps are write-locked only when
+/// category or kind is seen first time.
+#[derive(Default)]
+pub struct BgpDecodeMetrics {
+    counters: RwLock<BTreeMap<BgpDecodeCategory, AtomicCounters>>,
+    errors: RwLock<BTreeMap<&'static str, AtomicU64>>,
+}
+
+impl BgpDecodeMetrics {
+    /// Creates a new empty registry
+    pub fn new() -> BgpDecodeMetrics {
+        Default::default()
+    }
+    fn update(&self, cat: BgpDecodeCategory, f: impl Fn(&AtomicCounters) -> &AtomicU64) {
+        if let Ok(m) = self.counters.read() {
+            if let Some(c) = m.get(&cat) {
+                f(c).fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+        if let Ok(mut m) = self.counters.write() {
+            f(m.entry(cat).or_default()).fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    /// Counts successful decode
+    pub fn record_ok(&self, cat: BgpDecodeCategory) {
+        self.update(cat, |c| &c.ok);
+    }
+    /// Counts tolerated but not interpreted item
+    pub fn record_ignored(&self, cat: BgpDecodeCategory) {
+        self.update(cat, |c| &c.ignored);
+    }
+    /// Counts failed decode and its error kind
+    pub fn record_error(&self, cat: BgpDecodeCategory, e: &BgpError) {
+        self.update(cat, |c| &c.errors);
+        let kind = e.kind();
+        if let Ok(m) = self.errors.read() {
+            if let Some(c) = m.get(kind) {
+                c.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+        if let Ok(mut m) = self.errors.write() {
+            m.entry(kind).or_default().fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    /// Counts decode result
+    pub fn record<T>(&self, cat: BgpDecodeCategory, r: &Result<T, BgpError>) {
+        match r {
+            Ok(_) => self.record_ok(cat),
+            Err(e) => self.record_error(cat, e),
+        }
+    }
+    /// Returns counters for given category
+    pub fn get(&self, cat: BgpDecodeCategory) -> BgpDecodeCounters {
+        match self.counters.read() {
+            Ok(m) => m.get(&cat).map(AtomicCounters::load).unwrap_or_default(),
+            Err(_) => BgpDecodeCounters::default(),
+        }
+    }
+    /// Returns copy of all category counters
+    pub fn counters(&self) -> BTreeMap<BgpDecodeCategory, BgpDecodeCounters> {
+        match self.counters.read() {
+            Ok(m) => m.iter().map(|(k, v)| (*k, v.load())).collect(),
+            Err(_) => BTreeMap::new(),
+        }
+    }
+    /// Returns copy of error counters by error kind
+    pub fn error_kinds(&self) -> BTreeMap<&'static str, u64> {
+        match self.errors.read() {
+            Ok(m) => m
+                .iter()
+                .map(|(k, v)| (*k, v.load(Ordering::Relaxed)))
+                .collect(),
+            Err(_) => BTreeMap::new(),
+        }
+    }
+    /// Clears all counters
+    pub fn reset(&self) {
+        if let Ok(mut m) = self.counters.write() {
+            m.clear();
+        }
+        if let Ok(mut m) = self.errors.write() {
+            m.clear();
+        }
+    }
+}
+
+impl std::fmt::Debug for BgpDecodeMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BgpDecodeMetrics")
+            .field("counters", &self.counters())
+            .field("errors", &self.error_kinds())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics_record() {
+        let m = BgpDecodeMetrics::new();
+        m.record_ok(BgpDecodeCategory::Attribute(1));
+        m.record_ignored(BgpDecodeCategory::Attribute(99));
+        m.record::<()>(
+            BgpDecodeCategory::AfiSafi(1, 1),
+            &Err(BgpError::insufficient_buffer_size()),
+        );
+        assert_eq!(m.get(BgpDecodeCategory::Attribute(1)).ok, 1);
+        assert_eq!(m.get(BgpDecodeCategory::Attribute(99)).ignored, 1);
+        assert_eq!(m.get(BgpDecodeCategory::AfiSafi(1, 1)).errors, 1);
+        assert_eq!(
+            m.error_kinds().get("InsufficientBufferSize").copied(),
+            Some(1)
+        );
+        m.reset();
+        assert!(m.counters().is_empty());
+        assert!(m.error_kinds().is_empty());
+        // concurrent sessions sharing registry
+        let m = std::sync::Arc::new(m);
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let m = m.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..1000 {
+                        m.record_ok(BgpDecodeCategory::Message(2));
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+        assert_eq!(m.get(BgpDecodeCategory::Message(2)).ok, 4000);
+    }
+}
@@ -23,13 +23,23 @@ pub use crate::afi::ipv6::*;
 pub use crate::afi::mvpn::*;
 pub use crate::afi::vpls::*;
 pub use crate::afi::*;
+pub use crate::asn::*;
+pub use crate::collector::*;
 pub use crate::error::*;
+pub use crate::format::*;
+pub use crate::llgr::*;
+pub use crate::metrics::*;
+pub use crate::routeleak::*;
+pub use crate::routeserver::*;
 pub use crate::util::*;
 pub use crate::*;
 
+pub use crate::message::batch::*;
 pub use crate::message::keepalive::*;
 pub use crate::message::notification::*;
 pub use crate::message::open::*;
+pub use crate::message::raw::*;
+pub use crate::message::stream::*;
 pub use crate::message::update::*;
 pub use crate::message::*;
 pub use crate::BgpMessage;
@@ -40,6 +50,7 @@ pub use crate::message::attributes::atomicaggregate::*;
 pub use crate::message::attributes::attrset::*;
 pub use crate::message::attributes::clusterlist::*;
 pub use crate::message::attributes::community::*;
+pub use crate::message::attributes::communitynames::*;
 pub use crate::message::attributes::extcommunity::*;
 pub use crate::message::attributes::localpref::*;
 pub use crate::message::attributes::med::*;
@@ -0,0 +1,286 @@
+// Copyright 2021 Vladimir Melnikov.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! This module contains route leak analyzer (RFC 9234 and valley-free check)
+//!
+//! BGP Role capability and Only-To-Customer (OTC) path attribute are not decoded
+//! by this library yet, so OTC is taken from unknown path attribute with typecode 35,
+//! and local role is configured by application.
+
+use crate::asn::Asn;
+use crate::message::attributes::unknown::BgpAttrUnknown;
+use crate::message::attributes::*;
+use crate::message::update::BgpUpdateMessage;
+#[cfg(feature = "serialization")]
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Only-To-Customer path attribute typecode
+pub const BGP_ATTR_OTC: u8 = 35;
+
+/// BGP Role of the local speaker on a session (RFC 9234)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub enum BgpRole {
+    Provider,
+    RouteServer,
+    RouteServerClient,
+    Customer,
+    Peer,
+}
+
+impl BgpRole {
+    /// Decodes role from capability value.
+    pub fn decode_from(code: u8) -> Option<BgpRole> {
+        match code {
+            0 => Some(BgpRole::Provider),
+            1 => Some(BgpRole::RouteServer),
+            2 => Some(BgpRole::RouteServerClient),
+            3 => Some(BgpRole::Customer),
+            4 => Some(BgpRole::Peer),
+            _ => None,
+        }
+    }
+    /// Encodes role into capability value.
+    pub fn encode(&self) -> u8 {
+        match self {
+            BgpRole::Provider => 0,
+            BgpRole::RouteServer => 1,
+            BgpRole::RouteServerClient => 2,
+            BgpRole::Customer => 3,
+            BgpRole::Peer => 4,
+        }
+    }
+}
+
+/// Relationship of one AS to another
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub enum BgpAsRelation {
+    /// first AS is provider of second one
+    ProviderOf,
+    /// first AS is customer of second one
+    CustomerOf,
+    /// lateral peering
+    PeerOf,
+}
+
+/// Detected route leak kind
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub enum BgpLeakKind {
+    /// Route with OTC received from customer or route server client
+    OtcFromCustomer { otc: Asn },
+    /// Route from lateral peer has OTC not equal to peer AS
+    OtcMismatch { otc: Asn },
+    /// AS path goes down or across and then up or across again
+    ValleyFree { from: Asn, via: Asn, to: Asn },
+}
+
+/// Route leak report for single update
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct BgpLeakReport {
+    /// AS number of peer the update was received from
+    pub peer_as: Asn,
+    /// detected leak
+    pub kind: BgpLeakKind,
+}
+
+/// Route leak analyzer with configured AS relationships
+#[derive(Debug, Clone, Default)]
+pub struct BgpLeakAnalyzer {
+    relations: BTreeMap<(Asn, Asn), BgpAsRelation>,
+}
+
+impl BgpLeakAnalyzer {
+    pub fn new() -> BgpLeakAnalyzer {
+        BgpLeakAnalyzer {
+            relations: BTreeMap::new(),
+        }
+    }
+    /// Registers provider-customer relationship.
+    pub fn add_customer(&mut self, provider: Asn, customer: Asn) {
+        self.relations
+            .insert((provider, customer), BgpAsRelation::ProviderOf);
+        self.relations
+            .insert((customer, provider), BgpAsRelation::CustomerOf);
+    }
+    /// Registers lateral peering relationship.
+    pub fn add_peer(&mut self, a: Asn, b: Asn) {
+        self.relations.insert((a, b), BgpAsRelation::PeerOf);
+        self.relations.insert((b, a), BgpAsRelation::PeerOf);
+    }
+    /// Returns relationship of AS a to AS b, if known.
+    pub fn relation(&self, a: Asn, b: Asn) -> Option<BgpAsRelation> {
+        self.relations.get(&(a, b)).copied()
+    }
+    /// Returns OTC attribute value from update, if any.
+    pub fn get_otc(upd: &BgpUpdateMessage) -> Option<Asn> {
+        upd.attrs.iter().find_map(|a| match a {
+            BgpAttrItem::Unknown(u) if u.params.typecode == BGP_ATTR_OTC && u.value.len() == 4 => {
+                Some(Asn(crate::getn_u32(&u.value)))
+            }
+            _ => None,
+        })
+    }
+    /// Checks OTC attribute against local role on session with peer_as (RFC 9234 ingress rules).
+    pub fn check_otc(
+        &self,
+        role: BgpRole,
+        peer_as: Asn,
+        upd: &BgpUpdateMessage,
+    ) -> Option<BgpLeakReport> {
+        let kind = match (role, BgpLeakAnalyzer::get_otc(upd)) {
+            (BgpRole::Provider, Some(otc)) | (BgpRole::RouteServer, Some(otc)) => {
+                BgpLeakKind::OtcFromCustomer { otc }
+            }
+            (BgpRole::Peer, Some(otc)) if otc != peer_as => BgpLeakKind::OtcMismatch { otc },
+            _ => return None,
+        };
+        Some(BgpLeakReport { peer_as, kind })
+    }
+    /// Adds OTC attribute with peer_as to route received from provider, peer or route server,
+    /// if it has no OTC yet (RFC 9234 ingress rules). Returns true if attribute was added.
+    pub fn apply_otc(role: BgpRole, peer_as: Asn, upd: &mut BgpUpdateMessage) -> bool {
+        match role {
+            BgpRole::Customer | BgpRole::RouteServerClient | BgpRole::Peer => {}
+            _ => return false,
+        }
+        if BgpLeakAnalyzer::get_otc(upd).is_some() {
+            return false;
+        }
+        let mut u = BgpAttrUnknown::new(BGP_ATTR_OTC, 0xc0);
+        u.value = peer_as.0.to_be_bytes().to_vec();
+        upd.attrs.push(BgpAttrItem::Unknown(u));
+        true
+    }
+    /// Checks AS path for valley-free violations using configured relationships.
+    /// Pairs with unknown relationship are skipped.
+    pub fn check_valley_free(&self, peer_as: Asn, upd: &BgpUpdateMessage) -> Vec<BgpLeakReport> {
+        let mut ret = Vec::new();
+        let aspath = match upd.get_attr_aspath() {
+            Some(p) => p,
+            None => return ret,
+        };
+        let mut path: Vec<Asn> = aspath.value.iter().map(|a| a.asn()).collect();
+        path.dedup();
+        // walk from origin towards us, route goes from path[i+1] to path[i]
+        let mut descending = false;
+        for i in (1..path.len()).rev() {
+            let rel = match self.relation(path[i - 1], path[i]) {
+                Some(r) => r,
+                None => continue,
+            };
+            let uphill = rel == BgpAsRelation::ProviderOf;
+            if descending && (uphill || rel == BgpAsRelation::PeerOf) {
+                ret.push(BgpLeakReport {
+                    peer_as,
+                    kind: BgpLeakKind::ValleyFree {
+                        from: path[i + 1],
+                        via: path[i],
+                        to: path[i - 1],
+                    },
+                });
+            }
+            if !uphill {
+                descending = true;
+            }
+        }
+        ret
+    }
+    /// Runs all checks on decoded update.
+    pub fn analyze(
+        &self,
+        role: BgpRole,
+        peer_as: Asn,
+        upd: &BgpUpdateMessage,
+    ) -> Vec<BgpLeakReport> {
+        let mut ret = Vec::new();
+        if let Some(r) = self.check_otc(role, peer_as, upd) {
+            ret.push(r);
+        }
+        ret.extend(self.check_valley_free(peer_as, upd));
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    fn mkupdate(path: &[u32], otc: Option<u32>) -> BgpUpdateMessage {
+        let mut upd = BgpUpdateMessage::new();
+        upd.attrs.push(BgpAttrItem::ASPath(BgpASpath {
+            value: path.iter().map(|a| BgpAS::new(*a)).collect(),
+        }));
+        if let Some(otc) = otc {
+            let mut u = BgpAttrUnknown::new(BGP_ATTR_OTC, 0xc0);
+            u.value = otc.to_be_bytes().to_vec();
+            upd.attrs.push(BgpAttrItem::Unknown(u));
+        }
+        upd
+    }
+
+    #[test]
+    fn test_leak_otc() {
+        let an = BgpLeakAnalyzer::new();
+        let upd = mkupdate(&[65001], Some(65001));
+        assert_eq!(BgpLeakAnalyzer::get_otc(&upd), Some(Asn(65001)));
+        assert!(an.check_otc(BgpRole::Peer, Asn(65001), &upd).is_none());
+        assert_eq!(
+            an.check_otc(BgpRole::Provider, Asn(65001), &upd)
+                .map(|r| r.kind),
+            Some(BgpLeakKind::OtcFromCustomer { otc: Asn(65001) })
+        );
+        let mut upd = mkupdate(&[65001], None);
+        assert!(an.check_otc(BgpRole::Customer, Asn(65001), &upd).is_none());
+        assert!(!BgpLeakAnalyzer::apply_otc(
+            BgpRole::Provider,
+            Asn(65001),
+            &mut upd
+        ));
+        assert!(BgpLeakAnalyzer::apply_otc(
+            BgpRole::Customer,
+            Asn(65001),
+            &mut upd
+        ));
+        assert_eq!(BgpLeakAnalyzer::get_otc(&upd), Some(Asn(65001)));
+        assert!(!BgpLeakAnalyzer::apply_otc(
+            BgpRole::Customer,
+            Asn(65001),
+            &mut upd
+        ));
+    }
+    #[test]
+    fn test_leak_valley_free() {
+        let mut an = BgpLeakAnalyzer::new();
+        // 65002 is customer of both 65001 and 65003
+        an.add_customer(Asn(65001), Asn(65002));
+        an.add_customer(Asn(65003), Asn(65002));
+        an.add_peer(Asn(65000), Asn(65001));
+        // route learned by 65002 from provider 65003 and announced to provider 65001
+        let reps = an.check_valley_free(Asn(65001), &mkupdate(&[65001, 65002, 65002, 65003], None));
+        assert_eq!(
+            reps,
+            vec![BgpLeakReport {
+                peer_as: Asn(65001),
+                kind: BgpLeakKind::ValleyFree {
+                    from: Asn(65003),
+                    via: Asn(65002),
+                    to: Asn(65001)
+                }
+            }]
+        );
+        // customer route going up and then across is fine
+        assert!(an
+            .check_valley_free(Asn(65001), &mkupdate(&[65000, 65001, 65002], None))
+            .is_empty());
+    }
+}
@@ -0,0 +1,395 @@
+// Copyright 2021 Vladimir Melnikov.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! This module contains route server with per-client Adj-RIB-Out views
+//!
+//! There is no Loc-RIB in this library, so application feeds Loc-RIB changes
+//! (best path per IPv4/IPv6 unicast prefix) and gets minimal update stream for every client.
+//! Unicast family of client transport mode is sent in plain NLRI fields, the other one
+//! with MP_REACH_NLRI/MP_UNREACH_NLRI, messages are split to fit [BGP_MAX_MESSAGE_LEN].
+
+use crate::message::BGP_MAX_MESSAGE_LEN;
+use crate::prelude::*;
+use std::collections::BTreeMap;
+
+/// Client export policy hook. Returns false to reject route, may modify path attributes.
+pub type BgpRsExportPolicy = Box<dyn Fn(&BgpNet, &mut Vec<BgpAttrItem>) -> bool + Send + Sync>;
+
+/// Route server client
+pub struct BgpRsClient {
+    /// Client session params
+    pub params: BgpSessionParams,
+    /// Do not prepend route server AS number (RFC 7947),
+    /// eBGP egress rules are applied in any case
+    pub transparent_as: bool,
+    /// Export policy
+    pub policy: Option<BgpRsExportPolicy>,
+    /// Adj-RIB-Out, path attributes by prefix
+    pub rib_out: BTreeMap<BgpNet, Vec<BgpAttrItem>>,
+}
+
+impl BgpRsClient {
+    pub fn new(params: BgpSessionParams) -> BgpRsClient {
+        BgpRsClient {
+            params,
+            transparent_as: true,
+            policy: None,
+            rib_out: BTreeMap::new(),
+        }
+    }
+    /// Applies eBGP egress rules, policy and AS prepend to Loc-RIB route.
+    /// Routes of other than IPv4/IPv6 unicast families and routes without
+    /// next hop of the same family are not exported.
+    fn export(&self, asn: Asn, net: &BgpNet, attrs: &[BgpAttrItem]) -> Option<Vec<BgpAttrItem>> {
+        let nexthop = attrs.get_attr_nexthop().map(|n| n.value);
+        match (net, nexthop) {
+            (BgpNet::V4(_), Some(std::net::IpAddr::V4(_)))
+            | (BgpNet::V6(_), Some(std::net::IpAddr::V6(_))) => {}
+            _ => {
+                log::warn!(
+                    "Route server: {} with next hop {:?} is not exportable",
+                    net,
+                    nexthop
+                );
+                return None;
+            }
+        }
+        let mut attrs = attrs.to_vec();
+        if !self.transparent_as {
+            for attr in attrs.iter_mut() {
+                if let BgpAttrItem::ASPath(p) = attr {
+                    p.value.insert(0, BgpAS::from(asn));
+                }
+            }
+        }
+        ebgp_egress(&mut attrs);
+        if let Some(policy) = &self.policy {
+            if !policy(net, &mut attrs) {
+                return None;
+            }
+        }
+        Some(attrs)
+    }
+}
+
+impl std::fmt::Debug for BgpRsClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BgpRsClient")
+            .field("params", &self.params)
+            .field("transparent_as", &self.transparent_as)
+            .field("policy", &self.policy.is_some())
+            .field("rib_out", &self.rib_out.len())
+            .finish()
+    }
+}
+
+/// Route server with per-client Adj-RIB-Out
+#[derive(Debug, Default)]
+pub struct BgpRouteServer {
+    /// Route server AS number
+    pub as_num: Asn,
+    /// Clients by peer address
+    pub clients: BTreeMap<std::net::IpAddr, BgpRsClient>,
+}
+
+impl BgpRouteServer {
+    pub fn new(as_num: Asn) -> BgpRouteServer {
+        BgpRouteServer {
+            as_num,
+            clients: BTreeMap::new(),
+        }
+    }
+    /// Registers client.
+    pub fn add_client(&mut self, addr: std::net::IpAddr, client: BgpRsClient) {
+        self.clients.insert(addr, client);
+    }
+    /// Removes client.
+    pub fn remove_client(&mut self, addr: &std::net::IpAddr) -> Option<BgpRsClient> {
+        self.clients.remove(addr)
+    }
+    /// Applies Loc-RIB changes (None means prefix removed) learned from source client,
+    /// returns update stream for every affected client.
+    /// Routes are not sent back to the client they were learned from.
+    pub fn loc_rib_changed(
+        &mut self,
+        source: Option<&std::net::IpAddr>,
+        changes: &[(BgpNet, Option<Vec<BgpAttrItem>>)],
+    ) -> Vec<(std::net::IpAddr, Vec<BgpUpdateMessage>)> {
+        let mut ret = Vec::new();
+        for (addr, client) in self.clients.iter_mut() {
+            let mut announces: BTreeMap<Vec<BgpAttrItem>, Vec<BgpNet>> = BTreeMap::new();
+            let mut withdraws: Vec<BgpNet> = Vec::new();
+            for (net, route) in changes.iter() {
+                let exported = if source == Some(addr) {
+                    None
+                } else {
+                    route
+                        .as_ref()
+                        .and_then(|attrs| client.export(self.as_num, net, attrs))
+                };
+                match exported {
+                    Some(attrs) => {
+                        if client.rib_out.get(net) != Some(&attrs) {
+                            client.rib_out.insert(net.clone(), attrs.clone());
+                            announces.entry(attrs).or_default().push(net.clone());
+                        }
+                    }
+                    None => {
+                        if client.rib_out.remove(net).is_some() {
+                            withdraws.push(net.clone());
+                        }
+                    }
+                }
+            }
+            let mut msgs = Vec::new();
+            if !withdraws.is_empty() {
+                msgs.extend(BgpRouteServer::build_withdraws(&client.params, &withdraws));
+            }
+            for (attrs, nets) in announces.into_iter() {
+                msgs.extend(BgpRouteServer::build_updates(&client.params, &attrs, &nets));
+            }
+            if !msgs.is_empty() {
+                ret.push((*addr, msgs));
+            }
+        }
+        ret
+    }
+    fn split(nets: &[BgpNet]) -> (Vec<BgpAddrV4>, Vec<BgpAddrV6>) {
+        let mut v4 = Vec::new();
+        let mut v6 = Vec::new();
+        for net in nets.iter() {
+            match net {
+                BgpNet::V4(n) => v4.push(n.clone()),
+                BgpNet::V6(n) => v6.push(n.clone()),
+                // never exported, see BgpRsClient::export
+                BgpNet::MAC(_) => {}
+            }
+        }
+        (v4, v6)
+    }
+    /// Splits prefixes into messages not longer than BGP_MAX_MESSAGE_LEN.
+    /// Message size is estimated from message without NLRI plus encoded prefix lengths.
+    fn pack<T: Clone>(
+        params: &BgpSessionParams,
+        items: &[T],
+        bits: impl Fn(&T) -> u8,
+        mkmsg: impl Fn(Vec<T>) -> BgpUpdateMessage,
+    ) -> Vec<BgpUpdateMessage> {
+        let mut ret = Vec::new();
+        if items.is_empty() {
+            return ret;
+        }
+        let mut buf = vec![0_u8; BGP_MAX_MESSAGE_LEN];
+        // one more byte for MP attribute switching to extended length
+        let overhead = mkmsg(Vec::new())
+            .encode_to(params, &mut buf)
+            .map(|l| l + 19 + 1)
+            .unwrap_or(BGP_MAX_MESSAGE_LEN);
+        let mut chunk = Vec::new();
+        let mut len = overhead;
+        for item in items.iter() {
+            let itemlen = 1 + (bits(item) as usize).div_ceil(8);
+            if !chunk.is_empty() && len + itemlen > BGP_MAX_MESSAGE_LEN {
+                ret.push(mkmsg(std::mem::take(&mut chunk)));
+                len = overhead;
+            }
+            chunk.push(item.clone());
+            len += itemlen;
+        }
+        ret.push(mkmsg(chunk));
+        ret
+    }
+    fn build_withdraws(params: &BgpSessionParams, nets: &[BgpNet]) -> Vec<BgpUpdateMessage> {
+        let (v4, v6) = BgpRouteServer::split(nets);
+        let mut ret = BgpRouteServer::pack(
+            params,
+            &v4,
+            |n| n.prefixlen,
+            |v| BgpUpdateMessage::withdraw_for(params, &BgpAddrs::IPV4U(v)),
+        );
+        ret.extend(BgpRouteServer::pack(
+            params,
+            &v6,
+            |n| n.prefixlen,
+            |v| BgpUpdateMessage::withdraw_for(params, &BgpAddrs::IPV6U(v)),
+        ));
+        ret
+    }
+    /// Announce message - NLRI field for unicast family of transport mode,
+    /// MP_REACH_NLRI with next hop taken from NEXT_HOP attribute otherwise.
+    fn announce(
+        params: &BgpSessionParams,
+        attrs: &[BgpAttrItem],
+        addrs: BgpAddrs,
+    ) -> BgpUpdateMessage {
+        let mut upd = BgpUpdateMessage::new();
+        let native = matches!(
+            (params.peer_mode, &addrs),
+            (BgpTransportMode::IPv4, BgpAddrs::IPV4U(_))
+                | (BgpTransportMode::IPv6, BgpAddrs::IPV6U(_))
+        );
+        if native {
+            upd.attrs = attrs.to_vec();
+            upd.updates = addrs;
+            return upd;
+        }
+        let mut nexthop = BgpAddr::None;
+        for attr in attrs.iter() {
+            match attr {
+                BgpAttrItem::NextHop(nh) => {
+                    nexthop = match nh.value {
+                        std::net::IpAddr::V4(a) => BgpAddr::V4(a),
+                        std::net::IpAddr::V6(a) => BgpAddr::V6(a),
+                    }
+                }
+                a => upd.attrs.push(a.clone()),
+            }
+        }
+        upd.attrs.push(BgpAttrItem::MPUpdates(BgpMPUpdates {
+            nexthop,
+            link_local: None,
+            addrs,
+        }));
+        upd
+    }
+    fn build_updates(
+        params: &BgpSessionParams,
+        attrs: &[BgpAttrItem],
+        nets: &[BgpNet],
+    ) -> Vec<BgpUpdateMessage> {
+        let (v4, v6) = BgpRouteServer::split(nets);
+        let mut ret = BgpRouteServer::pack(
+            params,
+            &v4,
+            |n| n.prefixlen,
+            |v| BgpRouteServer::announce(params, attrs, BgpAddrs::IPV4U(v)),
+        );
+        ret.extend(BgpRouteServer::pack(
+            params,
+            &v6,
+            |n| n.prefixlen,
+            |v| BgpRouteServer::announce(params, attrs, BgpAddrs::IPV6U(v)),
+        ));
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mkparams() -> BgpSessionParams {
+        BgpSessionParams::new(
+            Asn(64512),
+            180,
+            BgpTransportMode::IPv4,
+            std::net::Ipv4Addr::new(1, 1, 1, 1),
+            vec![BgpCapability::SafiIPv4u],
+        )
+    }
+
+    #[test]
+    fn test_route_server() {
+        let a: std::net::IpAddr = "10.0.0.1".parse().unwrap();
+        let b: std::net::IpAddr = "10.0.0.2".parse().unwrap();
+        let mut rs = BgpRouteServer::new(Asn(64512));
+        rs.add_client(a, BgpRsClient::new(mkparams()));
+        let mut cb = BgpRsClient::new(mkparams());
+        cb.transparent_as = false;
+        cb.policy = Some(Box::new(|net, _| *net != "10.2.0.0/16".parse().unwrap()));
+        rs.add_client(b, cb);
+        let attrs = vec![
+            BgpAttrItem::ASPath(BgpASpath {
+                value: vec![BgpAS::new(65001)],
+            }),
+            BgpAttrItem::NextHop(BgpNextHop { value: a }),
+        ];
+        let changes = vec![
+            ("10.1.0.0/16".parse().unwrap(), Some(attrs.clone())),
+            ("10.2.0.0/16".parse().unwrap(), Some(attrs.clone())),
+        ];
+        let out = rs.loc_rib_changed(Some(&a), &changes);
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].0, b);
+        let upd = &out[0].1[0];
+        assert_eq!(upd.updates.len(), 1);
+        assert_eq!(upd.get_attr_aspath().unwrap().value.len(), 2);
+        // same change again gives nothing
+        assert!(rs.loc_rib_changed(Some(&a), &changes).is_empty());
+        let out = rs.loc_rib_changed(Some(&a), &[("10.1.0.0/16".parse().unwrap(), None)]);
+        assert_eq!(out[0].1[0].withdraws.len(), 1);
+        assert!(rs.clients[&b].rib_out.is_empty());
+    }
+    #[test]
+    fn test_route_server_transparent_egress() {
+        let a: std::net::IpAddr = "10.0.0.1".parse().unwrap();
+        let b: std::net::IpAddr = "10.0.0.2".parse().unwrap();
+        let mut rs = BgpRouteServer::new(Asn(64512));
+        rs.add_client(b, BgpRsClient::new(mkparams()));
+        let attrs = vec![
+            BgpAttrItem::ASPath(BgpASpath {
+                value: vec![BgpAS::new(65001)],
+            }),
+            BgpAttrItem::NextHop(BgpNextHop { value: a }),
+            BgpAttrItem::LocalPref(BgpLocalpref::new(200)),
+            BgpAttrItem::OriginatorID(BgpOriginatorID::new("1.1.1.1".parse().unwrap())),
+        ];
+        let out = rs.loc_rib_changed(Some(&a), &[("10.1.0.0/16".parse().unwrap(), Some(attrs))]);
+        let upd = &out[0].1[0];
+        assert_eq!(upd.get_attr_aspath().unwrap().value.len(), 1);
+        assert!(upd.get_attr_localpref().is_none());
+        assert!(!upd
+            .attrs
+            .iter()
+            .any(|x| matches!(x, BgpAttrItem::OriginatorID(_))));
+    }
+    #[test]
+    fn test_route_server_encoding() {
+        let a: std::net::IpAddr = "10.0.0.1".parse().unwrap();
+        let b: std::net::IpAddr = "10.0.0.2".parse().unwrap();
+        let mut params6 = mkparams();
+        params6.peer_mode = BgpTransportMode::IPv6;
+        let mut rs = BgpRouteServer::new(Asn(64512));
+        rs.add_client(b, BgpRsClient::new(params6.clone()));
+        let attrs4 = vec![BgpAttrItem::NextHop(BgpNextHop { value: a })];
+        let attrs6 = vec![BgpAttrItem::NextHop(
+            "2001:db8::1".parse::<BgpNextHop>().unwrap(),
+        )];
+        let mut changes: Vec<(BgpNet, Option<Vec<BgpAttrItem>>)> = (0..2000_u32)
+            .map(|i| {
+                let net = std::net::Ipv4Addr::from(0x0a000000 | (i << 8));
+                (BgpNet::V4(BgpAddrV4::new(net, 24)), Some(attrs4.clone()))
+            })
+            .collect();
+        changes.push(("2001:db8:1::/48".parse().unwrap(), Some(attrs6.clone())));
+        // v6 route with v4 next hop is not exportable
+        changes.push(("2001:db8:2::/48".parse().unwrap(), Some(attrs4.clone())));
+        let out = rs.loc_rib_changed(Some(&a), &changes);
+        let msgs = &out[0].1;
+        assert!(msgs.len() > 2);
+        assert_eq!(rs.clients[&b].rib_out.len(), 2001);
+        let mut buf = vec![0_u8; BGP_MAX_MESSAGE_LEN];
+        let mut v4 = 0;
+        let mut v6 = 0;
+        for msg in msgs.iter() {
+            let len = msg.encode_to(&params6, &mut buf[19..]).unwrap();
+            assert!(len + 19 <= BGP_MAX_MESSAGE_LEN);
+            v6 += msg.updates.len();
+            if let Some(mp) = msg.get_mpupdates() {
+                assert_eq!(mp.nexthop, BgpAddr::V4("10.0.0.1".parse().unwrap()));
+                v4 += mp.addrs.len();
+            }
+        }
+        assert_eq!((v4, v6), (2000, 1));
+        let out = rs.loc_rib_changed(Some(&a), &[(changes[0].0.clone(), None)]);
+        match out[0].1[0].attrs.first() {
+            Some(BgpAttrItem::MPWithdraws(w)) => assert_eq!(w.addrs.len(), 1),
+            _ => panic!("expected MP_UNREACH_NLRI"),
+        }
+    }
+}
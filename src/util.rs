@@ -106,6 +106,47 @@ pub(crate) fn is_addpath_nlri(b: &[u8]) -> bool {
         b[0] == 0 && b[1] == 0
     }
 }
+/// Byte buffer wrapper for debug output.
+///
+/// Plain `{:?}` prints the bytes like a slice, alternate `{:#?}` prints an offset-annotated hex dump:
+/// ```
+/// use zettabgp::util::HexDump;
+///
+/// assert_eq!(format!("{:#?}", HexDump(&[0x40, 0x01, 0x01, 0x00])), "0000: 40 01 01 00  |@...|");
+/// ```
+pub struct HexDump<'a>(pub &'a [u8]);
+
+impl std::fmt::Debug for HexDump<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !f.alternate() {
+            return write!(f, "{:?}", self.0);
+        }
+        for (n, chunk) in self.0.chunks(16).enumerate() {
+            if n > 0 {
+                f.write_str("\n")?;
+            }
+            write!(f, "{:04x}:", n * 16)?;
+            for b in chunk.iter() {
+                write!(f, " {:02x}", b)?;
+            }
+            if self.0.len() > 16 {
+                for _ in chunk.len()..16 {
+                    f.write_str("   ")?;
+                }
+            }
+            f.write_str("  |")?;
+            for b in chunk.iter() {
+                if b.is_ascii_graphic() || *b == b' ' {
+                    write!(f, "{}", *b as char)?;
+                } else {
+                    f.write_str(".")?;
+                }
+            }
+            f.write_str("|")?;
+        }
+        Ok(())
+    }
+}
 /// Returns BgpError::InsufficientBufferSize if slicing is out of bounds
 pub fn slice<T>(buf: &[T], start: usize, end: usize) -> Result<&[T], BgpError> {
     if start <= end && end <= buf.len() {